@@ -0,0 +1,211 @@
+//! Automatic gain control (AGC) tuning.
+//!
+//! The AGC keeps the amplitude out of the channel filter at `AGCCTRL2.MAGN_TARGET` by adjusting
+//! the LNA, LNA2 and DVGA gains, see the `AGCCTRL2`–`AGCCTRL0` register descriptions in the data
+//! sheet (TI document SWRS061I). The presets follow design note DN022 "CC11xx OOK/ASK register
+//! settings" (TI document SWRA215) and the SmartRF Studio settings for the data rates they are
+//! named after.
+
+use hal::spi::SpiDevice;
+
+use crate::lowlevel::registers::*;
+use crate::{
+    AgcFreeze, AgcLnaPriority, CarrierSenseAbsoluteThreshold, CarrierSenseRelativeThreshold,
+    Cc1101, ChannelFilterSamples, DecisionBoundary, Error, FilterLength, HystLevel, MaxDvgaGain,
+    MaxLnaGain, TargetAmplitude, WaitTime,
+};
+
+/// Every field of `AGCCTRL2`, `AGCCTRL1` and `AGCCTRL0`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AgcProfile {
+    /// `AGCCTRL2.MAX_DVGA_GAIN`.
+    pub max_dvga_gain: MaxDvgaGain,
+    /// `AGCCTRL2.MAX_LNA_GAIN`.
+    pub max_lna_gain: MaxLnaGain,
+    /// `AGCCTRL2.MAGN_TARGET`.
+    pub magn_target: TargetAmplitude,
+    /// `AGCCTRL1.AGC_LNA_PRIORITY`.
+    pub lna_priority: AgcLnaPriority,
+    /// `AGCCTRL1.CARRIER_SENSE_REL_THR`.
+    pub carrier_sense_relative_threshold: CarrierSenseRelativeThreshold,
+    /// `AGCCTRL1.CARRIER_SENSE_ABS_THR`.
+    pub carrier_sense_threshold: CarrierSenseAbsoluteThreshold,
+    /// `AGCCTRL0.HYST_LEVEL`.
+    pub hysteresis: HystLevel,
+    /// `AGCCTRL0.WAIT_TIME`.
+    pub wait_time: WaitTime,
+    /// `AGCCTRL0.AGC_FREEZE`.
+    pub freeze: AgcFreeze,
+    /// `AGCCTRL0.FILTER_LENGTH`.
+    pub filter_length: FilterLength,
+}
+
+impl Default for AgcProfile {
+    /// Reset values of `AGCCTRL2`, `AGCCTRL1` and `AGCCTRL0`.
+    fn default() -> Self {
+        AgcProfile {
+            max_dvga_gain: MaxDvgaGain::AllEnabled,
+            max_lna_gain: MaxLnaGain::Max,
+            magn_target: TargetAmplitude::Db33,
+            lna_priority: AgcLnaPriority::LnaFirst,
+            carrier_sense_relative_threshold: CarrierSenseRelativeThreshold::Disabled,
+            carrier_sense_threshold: CarrierSenseAbsoluteThreshold::Db0,
+            hysteresis: HystLevel::Medium,
+            wait_time: WaitTime::Samples16,
+            freeze: AgcFreeze::Normal,
+            filter_length: FilterLength::FrequencyModulation(ChannelFilterSamples::Samples16),
+        }
+    }
+}
+
+impl AgcProfile {
+    /// OOK/ASK reception, with an 8 dB decision boundary (DN022).
+    ///
+    /// Reducing `LNA2` gain first keeps the noise floor low while the signal is off.
+    pub const fn ook() -> Self {
+        AgcProfile {
+            max_dvga_gain: MaxDvgaGain::AllEnabled,
+            max_lna_gain: MaxLnaGain::Max,
+            magn_target: TargetAmplitude::Db33,
+            lna_priority: AgcLnaPriority::Lna2First,
+            carrier_sense_relative_threshold: CarrierSenseRelativeThreshold::Disabled,
+            carrier_sense_threshold: CarrierSenseAbsoluteThreshold::Db0,
+            hysteresis: HystLevel::Medium,
+            wait_time: WaitTime::Samples16,
+            freeze: AgcFreeze::Normal,
+            filter_length: FilterLength::AmplitudeModulation(DecisionBoundary::Db8),
+        }
+    }
+
+    /// 2-FSK/GFSK at low data rates in a narrow channel filter, e.g. 1.2–38.4 kBaud.
+    ///
+    /// The highest DVGA gain setting is disabled, which improves selectivity.
+    pub const fn narrowband_fsk() -> Self {
+        AgcProfile {
+            max_dvga_gain: MaxDvgaGain::HighestDisabled,
+            max_lna_gain: MaxLnaGain::Max,
+            magn_target: TargetAmplitude::Db33,
+            lna_priority: AgcLnaPriority::LnaFirst,
+            carrier_sense_relative_threshold: CarrierSenseRelativeThreshold::Disabled,
+            carrier_sense_threshold: CarrierSenseAbsoluteThreshold::Db0,
+            hysteresis: HystLevel::Medium,
+            wait_time: WaitTime::Samples16,
+            freeze: AgcFreeze::Normal,
+            filter_length: FilterLength::FrequencyModulation(ChannelFilterSamples::Samples16),
+        }
+    }
+
+    /// GFSK/MSK at high data rates, e.g. 250–500 kBaud.
+    ///
+    /// A higher target and longer averaging suit the wide channel filter.
+    pub const fn high_data_rate_fsk() -> Self {
+        AgcProfile {
+            max_dvga_gain: MaxDvgaGain::Highest3Disabled,
+            max_lna_gain: MaxLnaGain::Max,
+            magn_target: TargetAmplitude::Db42,
+            lna_priority: AgcLnaPriority::Lna2First,
+            carrier_sense_relative_threshold: CarrierSenseRelativeThreshold::Disabled,
+            carrier_sense_threshold: CarrierSenseAbsoluteThreshold::Db0,
+            hysteresis: HystLevel::Medium,
+            wait_time: WaitTime::Samples32,
+            freeze: AgcFreeze::Normal,
+            filter_length: FilterLength::FrequencyModulation(ChannelFilterSamples::Samples32),
+        }
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Configure every field of the AGC.
+    pub fn set_agc_profile(&mut self, profile: &AgcProfile) -> Result<(), Error<SpiE>> {
+        self.0.write_register(config::AGCCTRL2, |w| {
+            w.max_dvga_gain(profile.max_dvga_gain.into())
+                .max_lna_gain(profile.max_lna_gain.into())
+                .magn_target(profile.magn_target.into())
+        })?;
+        self.0.write_register(config::AGCCTRL1, |w| {
+            w.agc_lna_priority(profile.lna_priority.into())
+                .carrier_sense_rel_thr(profile.carrier_sense_relative_threshold.into())
+                .carrier_sense_abs_thr(profile.carrier_sense_threshold.into())
+        })?;
+        self.0.write_register(config::AGCCTRL0, |w| {
+            w.hyst_level(profile.hysteresis.into())
+                .wait_time(profile.wait_time.into())
+                .agc_freeze(profile.freeze.into())
+                .filter_length(profile.filter_length.into())
+        })?;
+        Ok(())
+    }
+
+    /// Freeze both the analogue and the digital gain at their current setting, or let the AGC
+    /// adjust them again.
+    ///
+    /// Freezing once a sync word is seen, e.g. on a GDO pin, keeps a strong interferer that
+    /// starts during the packet from changing the gain under it. Unfreezing selects
+    /// [`AgcFreeze::Normal`], which replaces [`AgcFreeze::OnSyncWord`] if that was set.
+    pub fn freeze_agc(&mut self, freeze: bool) -> Result<(), Error<SpiE>> {
+        let mode = if freeze {
+            AgcFreeze::All
+        } else {
+            AgcFreeze::Normal
+        };
+        self.set_agc_freeze(mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChip, SimSpi};
+    use crate::RadioConfig;
+    use core::cell::RefCell;
+
+    fn agcctrl(config: &RadioConfig) -> [u8; 3] {
+        let bytes = config.as_bytes();
+        [bytes[0x1B], bytes[0x1C], bytes[0x1D]]
+    }
+
+    #[test]
+    fn test_presets() {
+        let reset = RadioConfig::default();
+        assert_eq!(agcctrl(&reset.agc_profile(&AgcProfile::default())), agcctrl(&reset));
+        assert_eq!(agcctrl(&reset.agc_profile(&AgcProfile::ook())), [0x03, 0x00, 0x91]);
+        assert_eq!(agcctrl(&reset.agc_profile(&AgcProfile::narrowband_fsk())), [0x43, 0x40, 0x91]);
+        assert_eq!(
+            agcctrl(&reset.agc_profile(&AgcProfile::high_data_rate_fsk())),
+            [0xC7, 0x00, 0xB2]
+        );
+    }
+
+    #[test]
+    fn test_settings() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        let profile = AgcProfile {
+            carrier_sense_threshold: CarrierSenseAbsoluteThreshold::DbMinus7,
+            freeze: AgcFreeze::OnSyncWord,
+            ..AgcProfile::high_data_rate_fsk()
+        };
+        cc1101.set_agc_profile(&profile).unwrap();
+        assert_eq!(chip.borrow().config(), RadioConfig::default().agc_profile(&profile));
+
+        cc1101.freeze_agc(true).unwrap();
+        assert_eq!(chip.borrow().config().read(config::AGCCTRL0).agc_freeze(), 3);
+        cc1101.freeze_agc(false).unwrap();
+        cc1101.set_agc_hysteresis(HystLevel::Large).unwrap();
+        cc1101.set_agc_wait_time(WaitTime::Samples8).unwrap();
+        cc1101.set_carrier_sense_threshold(CarrierSenseAbsoluteThreshold::Disabled).unwrap();
+
+        let expected = RadioConfig::default()
+            .agc_profile(&profile)
+            .agc_freeze(AgcFreeze::Normal)
+            .agc_hysteresis(HystLevel::Large)
+            .agc_wait_time(WaitTime::Samples8)
+            .carrier_sense_threshold(CarrierSenseAbsoluteThreshold::Disabled);
+        assert_eq!(chip.borrow().config(), expected);
+        assert_eq!(expected.read(config::AGCCTRL1).carrier_sense_abs_thr(), 0b1000);
+    }
+}
@@ -0,0 +1,253 @@
+//! Time on air of packets.
+//!
+//! A packet on air consists of the preamble, the sync word, and the length byte, address byte,
+//! payload and CRC, see section 15 "Packet Handling Hardware Support" of the data sheet (TI
+//! document SWRS061I). With FEC, everything after the sync word is padded with one or two
+//! trellis terminator bytes to an even length and then doubled, see TI design note DN504 "FEC
+//! Implementation". Manchester encoding doubles every bit, and 4-FSK sends two bits per symbol.
+//!
+//! No SPI access is needed: the packet format is taken from a [`RadioConfig`] snapshot, or
+//! given as the same high level settings the driver setters take.
+
+use crate::lowlevel::convert::from_drate;
+use crate::lowlevel::registers::*;
+use crate::{
+    AddressCheck, LengthConfig, ModulationFormat, NumPreamble, PacketLength, RadioConfig, SyncMode,
+};
+
+/// Preamble bytes per `MDMCFG1.NUM_PREAMBLE` setting.
+const PREAMBLE_BYTES: [u8; 8] = [2, 3, 4, 6, 8, 12, 16, 24];
+
+/// Sync word bytes sent for a `MDMCFG2.SYNC_MODE` setting.
+const fn sync_bytes(sync_mode: u8) -> u8 {
+    match sync_mode & 0x3 {
+        0 => 0,
+        3 => 4,
+        _ => 2,
+    }
+}
+
+/// Packet format and symbol rate determining the time on air.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Airtime {
+    preamble_bytes: u8,
+    sync_bytes: u8,
+    length_byte: bool,
+    address_byte: bool,
+    crc: bool,
+    manchester: bool,
+    fec: bool,
+    bits_per_symbol: u8,
+    /// Symbol rate, in units of 2^-28 baud.
+    scaled_baud: u64,
+    /// Crystal frequency, in Hertz.
+    fxosc: u64,
+}
+
+impl Airtime {
+    /// Packet format of the radio's reset values, at `baud` symbols per second as programmed
+    /// by [`crate::Cc1101::set_data_rate`].
+    pub fn new(baud: u64) -> Self {
+        Self::from_config(&RadioConfig::default().data_rate(baud))
+    }
+
+    /// Packet format and symbol rate of the configuration `config`, for its crystal frequency.
+    pub fn from_config(config: &RadioConfig) -> Self {
+        let mdmcfg2 = config.read(config::MDMCFG2);
+        let mdmcfg1 = config.read(config::MDMCFG1);
+        let pktctrl0 = config.read(config::PKTCTRL0);
+        let drate_m = config.read(config::MDMCFG3).drate_m() as u64;
+        let drate_e = config.read(config::MDMCFG4).drate_e();
+        let fxosc = config.crystal_hz();
+
+        Airtime {
+            preamble_bytes: PREAMBLE_BYTES[mdmcfg1.num_preamble() as usize],
+            sync_bytes: sync_bytes(mdmcfg2.sync_mode()),
+            length_byte: pktctrl0.length_config() == LengthConfig::VARIABLE as u8,
+            address_byte: config.read(config::PKTCTRL1).adr_chk() != AddressCheck::DISABLED as u8,
+            crc: pktctrl0.crc_en() != 0,
+            manchester: mdmcfg2.manchester_en() != 0,
+            fec: mdmcfg1.fec_en() != 0,
+            bits_per_symbol: Self::bits_per_symbol(mdmcfg2.mod_format()),
+            scaled_baud: ((256 + drate_m) << drate_e) * fxosc,
+            fxosc,
+        }
+    }
+
+    fn bits_per_symbol(mod_format: u8) -> u8 {
+        if mod_format == ModulationFormat::QuaternaryFrequencyShiftKeying as u8 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Symbol rate, as programmed by [`crate::Cc1101::set_data_rate`].
+    pub fn data_rate(self, baud: u64) -> Self {
+        let (mantissa, exponent) = from_drate(baud, self.fxosc);
+        Airtime {
+            scaled_baud: ((256 + mantissa as u64) << exponent) * self.fxosc,
+            ..self
+        }
+    }
+
+    /// Preamble length.
+    pub fn num_preamble(self, num_preamble: NumPreamble) -> Self {
+        Airtime {
+            preamble_bytes: PREAMBLE_BYTES[u8::from(num_preamble) as usize],
+            ..self
+        }
+    }
+
+    /// Sync word mode, 16 or 32 sync bits.
+    pub fn sync_mode(self, sync_mode: SyncMode) -> Self {
+        Airtime {
+            sync_bytes: sync_bytes(sync_mode.fields().0.into()),
+            ..self
+        }
+    }
+
+    /// Packet length mode, a length byte is sent in variable packet length mode.
+    pub fn packet_length(self, length: PacketLength) -> Self {
+        Airtime {
+            length_byte: matches!(length, PacketLength::Variable(_)),
+            ..self
+        }
+    }
+
+    /// Whether an address byte is sent.
+    pub fn address(self, enable: bool) -> Self {
+        Airtime {
+            address_byte: enable,
+            ..self
+        }
+    }
+
+    /// Whether a CRC is sent.
+    pub fn crc(self, enable: bool) -> Self {
+        Airtime {
+            crc: enable,
+            ..self
+        }
+    }
+
+    /// Manchester encoding.
+    pub fn manchester_encoding(self, enable: bool) -> Self {
+        Airtime {
+            manchester: enable,
+            ..self
+        }
+    }
+
+    /// Forward error correction with interleaving.
+    pub fn fec(self, enable: bool) -> Self {
+        Airtime {
+            fec: enable,
+            ..self
+        }
+    }
+
+    /// Modulation format, 4-FSK sends two bits per symbol.
+    pub fn modulation_format(self, mod_format: ModulationFormat) -> Self {
+        Airtime {
+            bits_per_symbol: Self::bits_per_symbol(mod_format as u8),
+            ..self
+        }
+    }
+
+    /// Bytes sent after the sync word for `payload_len` bytes of payload, after FEC encoding.
+    pub fn coded_bytes(&self, payload_len: usize) -> u64 {
+        let data = self.length_byte as u64
+            + self.address_byte as u64
+            + payload_len as u64
+            + 2 * self.crc as u64;
+        if self.fec {
+            // One or two trellis terminator bytes pad the data to an even length, and the rate
+            // 1/2 code doubles it.
+            4 * (data + 1).div_ceil(2)
+        } else {
+            data
+        }
+    }
+
+    /// Symbols sent for a packet with `payload_len` bytes of payload, from the first preamble
+    /// bit to the last CRC bit.
+    pub fn symbols(&self, payload_len: usize) -> u64 {
+        let bytes = self.preamble_bytes as u64 + self.sync_bytes as u64;
+        let mut bits = 8 * (bytes + self.coded_bytes(payload_len));
+        if self.manchester {
+            bits *= 2;
+        }
+        bits.div_ceil(self.bits_per_symbol as u64)
+    }
+
+    /// Time on air of a packet with `payload_len` bytes of payload, in nanoseconds, rounded up.
+    pub fn time_on_air_ns(&self, payload_len: usize) -> u64 {
+        let symbols = self.symbols(payload_len) as u128;
+        (symbols * (1_000_000_000 << 28)).div_ceil(self.scaled_baud as u128) as u64
+    }
+
+    /// Time on air of a packet with `payload_len` bytes of payload, in microseconds, rounded
+    /// up.
+    pub fn time_on_air_us(&self, payload_len: usize) -> u64 {
+        self.time_on_air_ns(payload_len).div_ceil(1_000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_on_air() {
+        // Reset values: 115.051 kBaud, 4 preamble bytes, 16-bit sync, length byte and CRC.
+        let config = RadioConfig::default();
+        assert_eq!(Airtime::from_config(&config).time_on_air_us(10), 1322);
+
+        let config = config
+            .data_rate(38_383)
+            .num_preamble(NumPreamble::Eight)
+            .sync_mode(SyncMode::Match16of16(0xD391))
+            .packet_length(PacketLength::Fixed(20))
+            .crc(false);
+        let airtime = Airtime::from_config(&config);
+        assert_eq!(airtime.symbols(20), 240);
+        assert_eq!(airtime.time_on_air_us(20), 6253);
+        assert_eq!(
+            Airtime::from_config(&config.manchester_encoding(true)).time_on_air_us(20),
+            12506
+        );
+        // 20 bytes, plus 2 trellis terminator bytes, FEC encoded.
+        assert_eq!(Airtime::from_config(&config.fec(true)).coded_bytes(20), 44);
+        assert_eq!(Airtime::from_config(&config.fec(true)).time_on_air_us(20), 11255);
+        // 21 bytes, plus 1 trellis terminator byte.
+        assert_eq!(Airtime::from_config(&config.fec(true)).coded_bytes(21), 44);
+        let config = config.sync_mode(SyncMode::Match30of32(0xD391));
+        assert_eq!(Airtime::from_config(&config).symbols(20), 256);
+        let config = config.modulation_format(ModulationFormat::QuaternaryFrequencyShiftKeying);
+        assert_eq!(Airtime::from_config(&config).symbols(20), 128);
+    }
+
+    #[test]
+    fn test_settings() {
+        let config = RadioConfig::default()
+            .data_rate(9_992)
+            .num_preamble(NumPreamble::Six)
+            .sync_mode(SyncMode::Match30of32(0x1234))
+            .packet_length(PacketLength::Variable(60))
+            .address_filter(crate::AddressFilter::Device(0x42))
+            .crc(true)
+            .manchester_encoding(true);
+        let airtime = Airtime::new(9_992)
+            .num_preamble(NumPreamble::Six)
+            .sync_mode(SyncMode::Match30of32(0x1234))
+            .packet_length(PacketLength::Variable(60))
+            .address(true)
+            .crc(true)
+            .manchester_encoding(true);
+        assert_eq!(airtime, Airtime::from_config(&config));
+        // 6 + 4 + 1 + 1 + 32 + 2 bytes, Manchester encoded.
+        assert_eq!(airtime.symbols(32), 736);
+        assert_eq!(airtime.time_on_air_us(32), 73_655);
+    }
+}
@@ -0,0 +1,2233 @@
+//! Async twin of the high level API, built on `embedded-hal-async`.
+//!
+//! Enabled with the `async` feature. Register definitions, conversions and error types are
+//! shared with the blocking [`crate::Cc1101`], as are the FIFO chunking of streaming, the RSSI
+//! timing of sweeps and the backoff of listen-before-talk, so that only the register accesses
+//! are mirrored here.
+
+use embedded_hal_async::{delay::DelayNs, spi::SpiDevice};
+use hal::digital::{InputPin, PinState};
+
+use crate::channel::carrier_hz;
+use crate::lbt::next_random;
+use crate::lowlevel::{self, access::*, convert::*, registers::*};
+use crate::output_power::pa_power_index;
+use crate::scanner::rssi_timing;
+use crate::stream::{rx_chunk, rx_expected, rx_expected_variable, rx_status_len, tx_chunk};
+use crate::stream::{SavedGdo, SavedRegisters, BYTE_COUNTER_SPAN};
+use crate::types::PacketFraming;
+use crate::wor::{rx_time_for, rx_timeout_ns};
+use crate::{check_channel_bandwidth, check_crystal, check_data_rate};
+use crate::{
+    check_deviation, check_freq_if, check_frequency, check_frequency_offset, gdo_inv, in_rx,
+    machine_state, off_state, FifoFields, RxPacket, RxStatus, UserError,
+};
+use crate::{
+    AddressCheck, AddressFilter, AgcLnaPriority, AutoCalibration, CarrierSenseAbsoluteThreshold,
+    CarrierSenseRelativeThreshold, CcaMode, Error, FifoThreshold, FilterLength, GdoCfg,
+    LengthConfig, MachineState, MaxDvgaGain, MaxLnaGain, ModulationFormat, NumPreamble,
+    PacketFormat, PacketLength, PacketStatus, PollBudget, RadioMode, Rounded, SyncMode,
+    TargetAmplitude,
+};
+use crate::{
+    AgcFreeze, AgcProfile, BitSyncConfig, ChannelPlan, FocConfig, FrequencyOffsetTracker,
+    FrontEndConfig, FrontEndCurrent, HystLevel, PoTimeout, PowerBand, PreambleQualityThreshold,
+    RadioConfig, RcCalibration, ReceiverProfile, RxAttenuation, RxOffMode, RxTime, RxTimeQualifier,
+    RxWindowConfig, RxWindowEnd, TxOffMode, WaitTime, WakeOnRadioConfig, WakeOnRadioSettings,
+    CONFIG_REGISTERS, PATABLE_SIZE,
+};
+use crate::{
+    ConfigError, FscalCache, FscalEntry, Gdo, LbtConfig, RssiBin, ScanConfig, ScanRange,
+    StreamConfig, StreamLength, FIFO_SIZE_MAX,
+};
+
+/// Async high level API for interacting with the CC1101 radio chip.
+pub struct Cc1101<SPI>(lowlevel::asynch::Cc1101<SPI>);
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    pub fn new(spi: SPI) -> Result<Self, Error<SpiE>> {
+        Ok(Cc1101(lowlevel::asynch::Cc1101::new(spi)?))
+    }
+
+    /// Driver for a radio clocked by a `fxosc` Hertz crystal, rather than the usual 26 MHz.
+    ///
+    /// All frequency, data rate, bandwidth and timing conversions use it. Fails with
+    /// [`crate::ConfigError::Crystal`] outside of 26–27 MHz.
+    pub fn with_crystal(spi: SPI, fxosc: u64) -> Result<Self, Error<SpiE>> {
+        check_crystal(fxosc)?;
+        let mut cc1101 = lowlevel::asynch::Cc1101::new(spi)?;
+        cc1101.fxosc = fxosc;
+        Ok(Cc1101(cc1101))
+    }
+
+    /// Crystal frequency, in Hertz.
+    pub fn crystal_hz(&self) -> u64 {
+        self.0.fxosc
+    }
+
+    /// Last Chip Status Byte
+    pub fn get_chip_status(&mut self) -> Option<StatusByte> {
+        self.0.status
+    }
+
+    /// Command Strobe: Reset chip
+    pub async fn reset_chip(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.strobe(command::SRES).await?;
+        Ok(())
+    }
+
+    /// Command Strobe: Enable and calibrate frequency synthesizer
+    pub async fn enable_and_cal_freq_synth(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.strobe(command::SFSTXON).await?;
+        Ok(())
+    }
+
+    /// Command Strobe: Turn off crystal oscillator
+    pub async fn turn_off_xosc(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.strobe(command::SXOFF).await?;
+        Ok(())
+    }
+
+    /// Command Strobe: Calibrate frequency synthesizer and turn it off
+    pub async fn cal_freq_synth_and_turn_off(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.strobe(command::SCAL).await?;
+        Ok(())
+    }
+
+    /// Command Strobe: Enable RX
+    pub async fn enable_rx(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.strobe(command::SRX).await?;
+        Ok(())
+    }
+
+    /// Command Strobe: Enable TX
+    pub async fn enable_tx(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.strobe(command::STX).await?;
+        Ok(())
+    }
+
+    /// Command Strobe: Exit RX / TX, turn off frequency synthesizer
+    pub async fn exit_rx_tx(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.strobe(command::SIDLE).await?;
+        Ok(())
+    }
+
+    /// Command Strobe: Start automatic RX polling sequence (Wake-on-Radio)
+    pub async fn start_wake_on_radio(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.strobe(command::SWOR).await?;
+        Ok(())
+    }
+
+    /// Command Strobe: Enter power down mode when CSn goes high
+    pub async fn enter_power_down_mode(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.strobe(command::SPWD).await?;
+        Ok(())
+    }
+
+    /// Command Strobe: Flush the RX FIFO buffer
+    pub async fn flush_rx_fifo_buffer(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.strobe(command::SFRX).await?;
+        Ok(())
+    }
+
+    /// Command Strobe: Flush the TX FIFO buffer
+    pub async fn flush_tx_fifo_buffer(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.strobe(command::SFTX).await?;
+        Ok(())
+    }
+
+    /// Command Strobe: Reset real time clock to Event1 value
+    pub async fn reset_rtc_to_event1(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.strobe(command::SWORRST).await?;
+        Ok(())
+    }
+
+    /// Command Strobe: No operation. May be used to get access to the chip status byte
+    pub async fn no_operation(&mut self) -> Result<(), Error<SpiE>> {
+        self.0.strobe(command::SNOP).await?;
+        Ok(())
+    }
+
+    /// Set the GDO0 Output Pin Configuration
+    pub async fn set_gdo0_config(&mut self, config: GdoCfg) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::IOCFG0, |r| r.gdo0_cfg(config.into())).await?;
+        Ok(())
+    }
+
+    /// Set the GDO1 Output Pin Configuration
+    pub async fn set_gdo1_config(&mut self, config: GdoCfg) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::IOCFG1, |r| r.gdo1_cfg(config.into())).await?;
+        Ok(())
+    }
+
+    /// Set the GDO2 Output Pin Configuration
+    pub async fn set_gdo2_config(&mut self, config: GdoCfg) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::IOCFG2, |r| r.gdo2_cfg(config.into())).await?;
+        Ok(())
+    }
+
+    /// Set the GDO0 Active Output State
+    pub async fn set_gdo0_active_state(&mut self, state: PinState) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::IOCFG0, |r| r.gdo0_inv(gdo_inv(state))).await?;
+        Ok(())
+    }
+
+    /// Set the GDO1 Active Output State
+    pub async fn set_gdo1_active_state(&mut self, state: PinState) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::IOCFG1, |r| r.gdo1_inv(gdo_inv(state))).await?;
+        Ok(())
+    }
+
+    /// Set the GDO2 Active Output State
+    pub async fn set_gdo2_active_state(&mut self, state: PinState) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::IOCFG2, |r| r.gdo2_inv(gdo_inv(state))).await?;
+        Ok(())
+    }
+
+    /// Enable analog temperature sensor
+    pub async fn temperature_sensor_enable(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        match enable {
+            true => {
+                // Write 0 in all other register bits when using temperature sensor.
+                self.0
+                    .write_register(config::IOCFG0, |w| {
+                        w.temp_sensor_enable(enable as u8).gdo0_inv(0).gdo0_cfg(0)
+                    })
+                    .await?;
+            }
+            false => {
+                self.0
+                    .modify_register(config::IOCFG0, |r| r.temp_sensor_enable(enable as u8))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Set the output drive strength on the GDO pins
+    pub async fn set_gdo_drive_strength(&mut self, high_strength: bool) -> Result<(), Error<SpiE>> {
+        // Set high (1) or low (0) output drive strength on the GDO pins.
+        self.0.modify_register(config::IOCFG1, |r| r.gdo1_ds(high_strength as u8)).await?;
+        Ok(())
+    }
+
+    /// Sets the carrier frequency (in Hertz), returning the frequency achieved.
+    ///
+    /// Fails with [`crate::ConfigError::Frequency`] outside of the 300–348, 387–464 and 779–928 MHz
+    /// bands. Waits for IDLE without a bound, prefer [`Self::set_frequency_within`].
+    pub async fn set_frequency(&mut self, hz: u64) -> Result<Rounded, Error<SpiE>> {
+        check_frequency(hz)?;
+
+        // Before altering any frequency programming register we
+        // must stop the frequency synthesizer by going to Idle mode.
+        // See section 21 "Frequency Programming" of the data sheet
+        // (TI document SWRS061I).
+        self.set_radio_mode(RadioMode::Idle).await?;
+        self.write_frequency(hz).await
+    }
+
+    /// Sets the carrier frequency (in Hertz), returning the frequency achieved, going to IDLE
+    /// first with [`Self::set_radio_mode_within`].
+    ///
+    /// See [`crate::Cc1101::set_frequency_within`].
+    pub async fn set_frequency_within<D: DelayNs>(
+        &mut self,
+        hz: u64,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<Rounded, Error<SpiE>> {
+        check_frequency(hz)?;
+        self.set_radio_mode_within(RadioMode::Idle, delay, budget).await?;
+        self.write_frequency(hz).await
+    }
+
+    /// Write `FREQ2/1/0`, with the frequency synthesizer stopped.
+    async fn write_frequency(&mut self, hz: u64) -> Result<Rounded, Error<SpiE>> {
+        let (freq0, freq1, freq2) = from_frequency(hz, self.0.fxosc);
+        self.0.write_register(config::FREQ0, |w| w.freq(freq0)).await?;
+        self.0.write_register(config::FREQ1, |w| w.freq(freq1)).await?;
+        self.0.write_register(config::FREQ2, |w| w.freq(freq2)).await?;
+        Ok(Rounded::new(hz, to_frequency(freq0, freq1, freq2, self.0.fxosc)))
+    }
+
+    /// Gets the carrier frequency (in Hertz).
+    pub async fn get_frequency(&mut self) -> Result<u64, Error<SpiE>> {
+        let freq2 = self.0.read_register(config::FREQ2).await?.freq();
+        let freq1 = self.0.read_register(config::FREQ1).await?.freq();
+        let freq0 = self.0.read_register(config::FREQ0).await?.freq();
+        Ok(to_frequency(freq0, freq1, freq2, self.0.fxosc))
+    }
+
+    /// Sets the frequency synthesizer intermediate frequency (in Hertz), returning the
+    /// intermediate frequency achieved.
+    pub async fn set_freq_if(&mut self, hz: u64) -> Result<Rounded, Error<SpiE>> {
+        check_freq_if(hz, self.0.fxosc)?;
+        let value = from_freq_if(hz, self.0.fxosc);
+        self.0.write_register(config::FSCTRL1, |w| w.freq_if(value)).await?;
+        Ok(Rounded::new(hz, to_freq_if(value, self.0.fxosc)))
+    }
+
+    /// Sets the target value for the averaged amplitude from the digital channel filter.
+    pub async fn set_magn_target(&mut self, target: TargetAmplitude) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::AGCCTRL2, |r| r.magn_target(target.into())).await?;
+        Ok(())
+    }
+
+    /// Sets the maximum allowable DVGA gain.
+    pub async fn set_max_dvga_gain(&mut self, gain: MaxDvgaGain) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::AGCCTRL2, |r| r.max_dvga_gain(gain.into())).await?;
+        Ok(())
+    }
+
+    /// Sets the maximum allowable `LNA + LNA2` gain.
+    pub async fn set_max_lna_gain(&mut self, gain: MaxLnaGain) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::AGCCTRL2, |r| r.max_lna_gain(gain.into())).await?;
+        Ok(())
+    }
+
+    /// Sets AGC gain-reduction priority between `LNA` and `LNA2`.
+    ///
+    /// `AgcLnaPriority::LnaFirst` decreases `LNA` gain first.
+    /// `AgcLnaPriority::Lna2First` decreases `LNA2` gain to minimum first.
+    pub async fn set_agc_lna_priority(
+        &mut self,
+        priority: AgcLnaPriority,
+    ) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::AGCCTRL1, |r| r.agc_lna_priority(priority.into())).await?;
+        Ok(())
+    }
+
+    /// Sets the relative RSSI increase threshold for asserting carrier sense.
+    pub async fn set_carrier_sense_relative_threshold(
+        &mut self,
+        threshold: CarrierSenseRelativeThreshold,
+    ) -> Result<(), Error<SpiE>> {
+        self.0
+            .modify_register(config::AGCCTRL1, |r| r.carrier_sense_rel_thr(threshold.into()))
+            .await?;
+        Ok(())
+    }
+
+    /// Sets the absolute RSSI threshold for asserting carrier sense, relative to the
+    /// `MAGN_TARGET` setting in `AGCCTRL2`.
+    pub async fn set_carrier_sense_threshold(
+        &mut self,
+        threshold: CarrierSenseAbsoluteThreshold,
+    ) -> Result<(), Error<SpiE>> {
+        self.0
+            .modify_register(config::AGCCTRL1, |r| r.carrier_sense_abs_thr(threshold.into()))
+            .await?;
+        Ok(())
+    }
+
+    /// Sets the filter length (in FSK/MSK mode) or decision boundary (in OOK/ASK mode) for the AGC.
+    pub async fn set_filter_length(
+        &mut self,
+        filter_length: FilterLength,
+    ) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::AGCCTRL0, |r| r.filter_length(filter_length.into())).await?;
+        Ok(())
+    }
+
+    /// Sets the level of hysteresis on the magnitude deviation of the AGC.
+    pub async fn set_agc_hysteresis(&mut self, level: HystLevel) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::AGCCTRL0, |r| r.hyst_level(level.into())).await?;
+        Ok(())
+    }
+
+    /// Sets the number of channel filter samples the AGC waits after a gain adjustment.
+    pub async fn set_agc_wait_time(&mut self, wait_time: WaitTime) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::AGCCTRL0, |r| r.wait_time(wait_time.into())).await?;
+        Ok(())
+    }
+
+    /// Sets when the AGC gain is frozen, see also [`Self::freeze_agc`].
+    pub async fn set_agc_freeze(&mut self, freeze: AgcFreeze) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::AGCCTRL0, |r| r.agc_freeze(freeze.into())).await?;
+        Ok(())
+    }
+
+    /// Configures when to run automatic calibration.
+    pub async fn set_autocalibration(
+        &mut self,
+        autocal: AutoCalibration,
+    ) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM0, |r| r.fs_autocal(autocal.into())).await?;
+        Ok(())
+    }
+
+    /// Sets how long to wait after the crystal oscillator has stabilized before `CHP_RDYn`
+    /// goes low, when leaving SLEEP.
+    pub async fn set_po_timeout(&mut self, timeout: PoTimeout) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM0, |r| r.po_timeout(timeout.into())).await?;
+        Ok(())
+    }
+
+    /// Enables the pin radio control option, where the radio state follows the `SI`, `SCLK`
+    /// and `SO` pins while `CSn` is high.
+    pub async fn pin_control_enable(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM0, |r| r.pin_ctrl_en(enable as u8)).await?;
+        Ok(())
+    }
+
+    /// Keeps the crystal oscillator running in SLEEP.
+    pub async fn set_xosc_force_on(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM0, |r| r.xosc_force_on(enable as u8)).await?;
+        Ok(())
+    }
+
+    /// Set Modem deviation setting, returning the deviation achieved.
+    ///
+    /// Fails with [`crate::ConfigError::Deviation`] outside of 1.587–380.859 kHz.
+    pub async fn set_deviation_hz(&mut self, deviation: u64) -> Result<Rounded, Error<SpiE>> {
+        check_deviation(deviation, self.0.fxosc)?;
+        let (mantissa, exponent) = from_deviation(deviation, self.0.fxosc);
+        self.0
+            .write_register(config::DEVIATN, |w| w.deviation_m(mantissa).deviation_e(exponent))
+            .await?;
+        Ok(Rounded::new(deviation, to_deviation(mantissa, exponent, self.0.fxosc)))
+    }
+
+    /// Gets the frequency deviation (in Hertz).
+    pub async fn get_deviation_hz(&mut self) -> Result<u64, Error<SpiE>> {
+        let deviatn = self.0.read_register(config::DEVIATN).await?;
+        Ok(to_deviation(deviatn.deviation_m(), deviatn.deviation_e(), self.0.fxosc))
+    }
+
+    /// Sets the data rate (in bits per second), returning the data rate achieved.
+    ///
+    /// Fails with [`crate::ConfigError::DataRate`] outside of 0.6–600 kBaud.
+    pub async fn set_data_rate(&mut self, baud: u64) -> Result<Rounded, Error<SpiE>> {
+        check_data_rate(baud)?;
+        let (mantissa, exponent) = from_drate(baud, self.0.fxosc);
+        self.0.modify_register(config::MDMCFG4, |r| r.drate_e(exponent)).await?;
+        self.0.write_register(config::MDMCFG3, |w| w.drate_m(mantissa)).await?;
+        Ok(Rounded::new(baud, to_drate(mantissa, exponent, self.0.fxosc)))
+    }
+
+    /// Gets the data rate (in bits per second).
+    pub async fn get_data_rate(&mut self) -> Result<u64, Error<SpiE>> {
+        let exponent = self.0.read_register(config::MDMCFG4).await?.drate_e();
+        let mantissa = self.0.read_register(config::MDMCFG3).await?.drate_m();
+        Ok(to_drate(mantissa, exponent, self.0.fxosc))
+    }
+
+    /// Enable Forward Error Correction (FEC) with interleaving for packet payload
+    pub async fn fec_enable(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MDMCFG1, |r| r.fec_en(enable as u8)).await?;
+        Ok(())
+    }
+
+    /// Sets the minimum number of preamble bytes to be transmitted
+    pub async fn set_num_preamble(&mut self, num_preamble: NumPreamble) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MDMCFG1, |r| r.num_preamble(num_preamble.into())).await?;
+        Ok(())
+    }
+
+    /// Sets the preamble quality threshold, `PKTCTRL1.PQT`.
+    ///
+    /// A sync word is only accepted once the preamble quality estimator has counted enough
+    /// more good bits than bad ones.
+    pub async fn set_preamble_quality_threshold(
+        &mut self,
+        threshold: PreambleQualityThreshold,
+    ) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::PKTCTRL1, |r| r.pqt(threshold.into())).await?;
+        Ok(())
+    }
+
+    /// Sets the threshold for the TX FIFO and RX FIFO.
+    pub async fn set_fifo_threshold(
+        &mut self,
+        threshold: FifoThreshold,
+    ) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::FIFOTHR, |r| r.fifo_thr(threshold.into())).await?;
+        Ok(())
+    }
+
+    /// Selects CCA_MODE; Reflected in CCA signal.
+    pub async fn set_cca_mode(&mut self, cca_mode: CcaMode) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM1, |r| r.cca_mode(cca_mode.into())).await?;
+        Ok(())
+    }
+
+    /// Selects the state to enter when a packet has been received.
+    pub async fn set_rxoff_mode(&mut self, mode: RxOffMode) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM1, |r| r.rxoff_mode(mode.into())).await?;
+        Ok(())
+    }
+
+    /// Selects the state to enter when a packet has been sent.
+    pub async fn set_txoff_mode(&mut self, mode: TxOffMode) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM1, |r| r.txoff_mode(mode.into())).await?;
+        Ok(())
+    }
+
+    /// Sets the timeout for the sync word search in RX, `MCSM2.RX_TIME`.
+    ///
+    /// The timeout is relative to the Event 0 period, see [`RxTime`].
+    pub async fn set_rx_time(&mut self, rx_time: RxTime) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM2, |r| r.rx_time(rx_time.into())).await?;
+        Ok(())
+    }
+
+    /// Leave RX as soon as carrier sense is not asserted, without waiting for the RX timeout.
+    pub async fn set_rx_time_rssi(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM2, |r| r.rx_time_rssi(enable as u8)).await?;
+        Ok(())
+    }
+
+    /// Selects what to look for when the RX timeout expires.
+    pub async fn set_rx_time_qualifier(
+        &mut self,
+        qualifier: RxTimeQualifier,
+    ) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM2, |r| r.rx_time_qual(qualifier.into())).await?;
+        Ok(())
+    }
+
+    /// Sets the narrowest channel bandwidth of at least `bandwidth_hz` (in Hertz), returning
+    /// the bandwidth achieved.
+    ///
+    /// Fails with [`crate::ConfigError::ChannelBandwidth`] above 812.5 kHz.
+    pub async fn set_channel_bandwidth(
+        &mut self,
+        bandwidth_hz: u64,
+    ) -> Result<Rounded, Error<SpiE>> {
+        check_channel_bandwidth(bandwidth_hz, self.0.fxosc)?;
+        let (mantissa, exponent) = from_chanbw(bandwidth_hz, self.0.fxosc);
+        self.0
+            .modify_register(config::MDMCFG4, |r| r.chanbw_m(mantissa).chanbw_e(exponent))
+            .await?;
+        Ok(Rounded::new(bandwidth_hz, to_chanbw(mantissa, exponent, self.0.fxosc)))
+    }
+
+    /// Gets the channel bandwidth (in Hertz).
+    pub async fn get_channel_bandwidth(&mut self) -> Result<u64, Error<SpiE>> {
+        let mdmcfg4 = self.0.read_register(config::MDMCFG4).await?;
+        Ok(to_chanbw(mdmcfg4.chanbw_m(), mdmcfg4.chanbw_e(), self.0.fxosc))
+    }
+
+    /// Configure the sync word to use, and at what level it should be verified.
+    pub async fn set_sync_mode(&mut self, sync_mode: SyncMode) -> Result<(), Error<SpiE>> {
+        let (mode, word) = sync_mode.fields();
+
+        self.0.modify_register(config::MDMCFG2, |r| r.sync_mode(mode.into())).await?;
+        self.0.write_register(config::SYNC1, |w| w.sync(((word >> 8) & 0xff) as u8)).await?;
+        self.0.write_register(config::SYNC0, |w| w.sync((word & 0xff) as u8)).await?;
+        Ok(())
+    }
+
+    /// Sets the Manchester encoding mode.
+    pub async fn set_manchester_encoding(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MDMCFG2, |r| r.manchester_en(enable as u8)).await?;
+        Ok(())
+    }
+
+    /// Set the modulation format of the radio signal.
+    pub async fn set_modulation_format(
+        &mut self,
+        mod_format: ModulationFormat,
+    ) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MDMCFG2, |r| r.mod_format(mod_format.into())).await?;
+        Ok(())
+    }
+
+    /// Enable automatic flush of RX FIFO when CRC is not OK.
+    /// This requires that only one packet is in the RX FIFO and that packet length is limited to the RX FIFO size.
+    pub async fn crc_autoflush_enable(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::PKTCTRL1, |r| r.crc_autoflush(enable as u8)).await?;
+        Ok(())
+    }
+
+    /// When enabled, two status bytes will be appended to the payload of the packet.
+    /// The status bytes contain RSSI and LQI values, as well as CRC OK.
+    pub async fn append_status_enable(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.rx_status_fields = enable;
+        self.0.modify_register(config::PKTCTRL1, |r| r.append_status(enable as u8)).await?;
+        Ok(())
+    }
+
+    /// Configure device address, and address filtering.
+    pub async fn set_address_filter(&mut self, filter: AddressFilter) -> Result<(), Error<SpiE>> {
+        let (mode, addr) = filter.fields();
+        self.0.address_field = mode != AddressCheck::DISABLED;
+        self.0.modify_register(config::PKTCTRL1, |r| r.adr_chk(mode.into())).await?;
+        self.0.write_register(config::ADDR, |w| w.device_addr(addr)).await?;
+        Ok(())
+    }
+
+    /// Turn data whitening on / off.
+    pub async fn white_data_enable(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::PKTCTRL0, |r| r.white_data(enable as u8)).await?;
+        Ok(())
+    }
+
+    /// Enable CRC calculation in TX and CRC check in RX
+    pub async fn crc_enable(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::PKTCTRL0, |r| r.crc_en(enable as u8)).await?;
+        Ok(())
+    }
+
+    /// Configure packet mode, and length.
+    pub async fn set_packet_length(&mut self, length: PacketLength) -> Result<(), Error<SpiE>> {
+        let (format, pktlen) = length.fields();
+        if format == LengthConfig::VARIABLE {
+            self.0.length_field = true;
+        }
+        self.0.modify_register(config::PKTCTRL0, |r| r.length_config(format.into())).await?;
+        self.0.write_register(config::PKTLEN, |w| w.packet_length(pktlen)).await?;
+        Ok(())
+    }
+
+    /// Set the format of RX and TX data.
+    pub async fn set_packet_format(&mut self, format: PacketFormat) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::PKTCTRL0, |r| r.pkt_format(format.into())).await?;
+        Ok(())
+    }
+
+    /// Program the base frequency and channel spacing of `plan`, and select channel 0.
+    ///
+    /// The radio must be in IDLE, e.g. after [`Self::set_radio_mode_within`]. Fails with
+    /// [`UserError::NotIdle`] otherwise, or with [`crate::ConfigError::Crystal`] if `plan` is
+    /// for another crystal frequency.
+    pub async fn set_channel_plan(&mut self, plan: &ChannelPlan) -> Result<(), Error<SpiE>> {
+        self.check_idle().await?;
+        plan.check_crystal(self.0.fxosc)?;
+        let ((freq0, freq1, freq2), chanspc_m, chanspc_e) = plan.registers();
+        self.0.write_register(config::FREQ0, |w| w.freq(freq0)).await?;
+        self.0.write_register(config::FREQ1, |w| w.freq(freq1)).await?;
+        self.0.write_register(config::FREQ2, |w| w.freq(freq2)).await?;
+        self.0.write_register(config::MDMCFG0, |w| w.chanspc_m(chanspc_m)).await?;
+        self.0.modify_register(config::MDMCFG1, |r| r.chanspc_e(chanspc_e)).await?;
+        self.0.write_register(config::CHANNR, |w| w.chan(0)).await?;
+        Ok(())
+    }
+
+    /// Select a channel of the programmed channel plan, with a single register write.
+    ///
+    /// The radio should be in IDLE. Neither that nor `channel` is checked, see
+    /// [`Self::set_plan_channel`] for the checked version.
+    pub async fn set_channel(&mut self, channel: u8) -> Result<(), Error<SpiE>> {
+        self.0.write_register(config::CHANNR, |w| w.chan(channel)).await?;
+        Ok(())
+    }
+
+    /// Select `channel` of `plan`, which must be the programmed channel plan.
+    ///
+    /// Fails with [`UserError::InvalidChannel`] if `channel` is outside of `plan`, with
+    /// [`crate::ConfigError::Crystal`] if `plan` is for another crystal frequency, or with
+    /// [`UserError::NotIdle`] if the radio is not in IDLE.
+    pub async fn set_plan_channel(
+        &mut self,
+        plan: &ChannelPlan,
+        channel: u8,
+    ) -> Result<(), Error<SpiE>> {
+        plan.check_crystal(self.0.fxosc)?;
+        plan.check_channel(channel).map_err(Error::UserInputError)?;
+        self.check_idle().await?;
+        self.set_channel(channel).await
+    }
+
+    async fn check_idle(&mut self) -> Result<(), Error<SpiE>> {
+        match self.get_machine_state().await? {
+            MachineState::IDLE => Ok(()),
+            state => Err(Error::UserInputError(UserError::NotIdle(state))),
+        }
+    }
+
+    /// Currently selected channel.
+    pub async fn get_channel(&mut self) -> Result<u8, Error<SpiE>> {
+        Ok(self.0.read_register(config::CHANNR).await?.chan())
+    }
+
+    /// Set the output power, using the lookup table for the currently programmed frequency.
+    ///
+    /// Selects the highest output power not above `dbm`, and returns it. In ASK/OOK a '0' is
+    /// sent with the PA off. Set the frequency and modulation format first.
+    pub async fn set_output_power_dbm(&mut self, dbm: i8) -> Result<i8, Error<SpiE>> {
+        let band = PowerBand::from_frequency(self.get_frequency().await?);
+        let (setting, actual) = band.pa_setting(dbm);
+
+        let mod_format = self.0.read_register(config::MDMCFG2).await?.mod_format();
+        if mod_format == ModulationFormat::AmplitudeShiftOnOffKeying as u8 {
+            self.set_patable(&[0x00, setting]).await?;
+        } else {
+            self.set_patable(&[setting]).await?;
+        }
+        Ok(actual)
+    }
+
+    /// Write the first `table.len()` (1 to 8) PATABLE entries, and use the last one for
+    /// sending a '1' (`FREND0.PA_POWER`).
+    pub async fn set_patable(&mut self, table: &[u8]) -> Result<(), Error<SpiE>> {
+        let pa_power = pa_power_index(table).map_err(Error::UserInputError)?;
+        self.0.write_burst(multi::PATABLE, table).await?;
+        self.0.modify_register(config::FREND0, |r| r.pa_power(pa_power)).await?;
+        Ok(())
+    }
+
+    /// Read all PATABLE entries.
+    pub async fn get_patable(&mut self) -> Result<[u8; PATABLE_SIZE], Error<SpiE>> {
+        let mut table = [0; PATABLE_SIZE];
+        self.0.read_burst(multi::PATABLE, &mut table).await?;
+        Ok(table)
+    }
+
+    /// Configure Wake-on-Radio, returning the register values and achieved timing.
+    ///
+    /// Also powers up the RC oscillator. WOR is started with [`Self::start_wake_on_radio`].
+    pub async fn set_wake_on_radio(
+        &mut self,
+        wor: &WakeOnRadioConfig,
+    ) -> Result<WakeOnRadioSettings, Error<SpiE>> {
+        let settings = wor.crystal(self.0.fxosc).settings();
+        self.0
+            .modify_register(config::MCSM2, |r| {
+                r.rx_time_rssi(settings.rx_time_rssi as u8)
+                    .rx_time_qual(settings.rx_time_qual.into())
+                    .rx_time(settings.rx_time)
+            })
+            .await?;
+        self.0.write_register(config::WOREVT1, |w| w.event((settings.event0 >> 8) as u8)).await?;
+        self.0.write_register(config::WOREVT0, |w| w.event((settings.event0 & 0xff) as u8)).await?;
+        self.0
+            .modify_register(config::WORCTRL, |r| {
+                r.rc_pd(0)
+                    .event(settings.event1)
+                    .rc_cal((settings.rc_calibration == RcCalibration::Automatic) as u8)
+                    .wor_res(settings.wor_res)
+            })
+            .await?;
+        if let RcCalibration::Manual {
+            rcctrl1,
+            rcctrl0,
+        } = settings.rc_calibration
+        {
+            self.0.write_register(config::RCCTRL1, |w| w.rcctrl1(rcctrl1)).await?;
+            self.0.write_register(config::RCCTRL0, |w| w.rcctrl0(rcctrl0)).await?;
+        }
+        Ok(settings)
+    }
+
+    /// Result of the last RC oscillator calibration, for use with [`RcCalibration::Manual`].
+    pub async fn get_rc_calibration(&mut self) -> Result<RcCalibration, Error<SpiE>> {
+        let rcctrl1 = self.0.read_register(status::RCCTRL1_STATUS).await?.rcctrl1_status();
+        let rcctrl0 = self.0.read_register(status::RCCTRL0_STATUS).await?.rcctrl0_status();
+        Ok(RcCalibration::Manual {
+            rcctrl1,
+            rcctrl0,
+        })
+    }
+
+    /// Write all configuration registers in a single burst access.
+    ///
+    /// The radio should be in IDLE, as frequency programming registers must not be altered
+    /// while the frequency synthesizer is running.
+    pub async fn apply_config(&mut self, config: &RadioConfig) -> Result<(), Error<SpiE>> {
+        self.0.write_config_burst(config::IOCFG2, config.as_bytes()).await?;
+
+        let pktctrl1 = config.read(config::PKTCTRL1);
+        self.0.length_field =
+            config.read(config::PKTCTRL0).length_config() == LengthConfig::VARIABLE as u8;
+        self.0.address_field = pktctrl1.adr_chk() != AddressCheck::DISABLED as u8;
+        self.0.rx_status_fields = pktctrl1.append_status() != 0;
+        Ok(())
+    }
+
+    /// Read all configuration registers in a single burst access.
+    pub async fn read_config(&mut self) -> Result<RadioConfig, Error<SpiE>> {
+        let mut regs = [0; CONFIG_REGISTERS];
+        self.0.read_config_burst(config::IOCFG2, &mut regs).await?;
+        Ok(RadioConfig::from_bytes(regs).crystal(self.0.fxosc))
+    }
+
+    /// Read hardware information: part number for CC1101 and current version number
+    pub async fn get_hw_info(&mut self) -> Result<(u8, u8), Error<SpiE>> {
+        let partnum = self.0.read_register(status::PARTNUM).await?.partnum();
+        let version = self.0.read_register(status::VERSION).await?.version();
+        Ok((partnum, version))
+    }
+
+    /// Configure every field of the AGC.
+    pub async fn set_agc_profile(&mut self, profile: &AgcProfile) -> Result<(), Error<SpiE>> {
+        self.0
+            .write_register(config::AGCCTRL2, |w| {
+                w.max_dvga_gain(profile.max_dvga_gain.into())
+                    .max_lna_gain(profile.max_lna_gain.into())
+                    .magn_target(profile.magn_target.into())
+            })
+            .await?;
+        self.0
+            .write_register(config::AGCCTRL1, |w| {
+                w.agc_lna_priority(profile.lna_priority.into())
+                    .carrier_sense_rel_thr(profile.carrier_sense_relative_threshold.into())
+                    .carrier_sense_abs_thr(profile.carrier_sense_threshold.into())
+            })
+            .await?;
+        self.0
+            .write_register(config::AGCCTRL0, |w| {
+                w.hyst_level(profile.hysteresis.into())
+                    .wait_time(profile.wait_time.into())
+                    .agc_freeze(profile.freeze.into())
+                    .filter_length(profile.filter_length.into())
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Freeze both the analogue and the digital gain at their current setting, or let the AGC
+    /// adjust them again. See [`crate::Cc1101::freeze_agc`].
+    pub async fn freeze_agc(&mut self, freeze: bool) -> Result<(), Error<SpiE>> {
+        let mode = if freeze {
+            AgcFreeze::All
+        } else {
+            AgcFreeze::Normal
+        };
+        self.set_agc_freeze(mode).await
+    }
+
+    /// Configure the clock recovery loop.
+    pub async fn set_bit_sync_config(
+        &mut self,
+        bit_sync: &BitSyncConfig,
+    ) -> Result<(), Error<SpiE>> {
+        self.0
+            .write_register(config::BSCFG, |w| {
+                w.bs_pre_ki(bit_sync.pre_ki.into())
+                    .bs_pre_kp(bit_sync.pre_kp.into())
+                    .bs_post_ki(bit_sync.post_ki.into())
+                    .bs_post_kp(bit_sync.post_kp.into())
+                    .bs_limit(bit_sync.limit.into())
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Configure the currents of the RX front end.
+    pub async fn set_front_end_config(
+        &mut self,
+        front_end: &FrontEndConfig,
+    ) -> Result<(), Error<SpiE>> {
+        self.0
+            .write_register(config::FREND1, |w| {
+                w.lna_current(front_end.lna_current.into())
+                    .lna2mix_current(front_end.lna2mix_current.into())
+                    .lodiv_buf_current_rx(front_end.lodiv_buf_current.into())
+                    .mix_current(front_end.mix_current.into())
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Sets the current of the TX LO buffer (input to the PA), `FREND0.LODIV_BUF_CURRENT_TX`.
+    pub async fn set_tx_lo_buffer_current(
+        &mut self,
+        current: FrontEndCurrent,
+    ) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::FREND0, |r| r.lodiv_buf_current_tx(current.into())).await?;
+        Ok(())
+    }
+
+    /// Sets the RX attenuation, for links where the other side can be very close.
+    pub async fn set_rx_attenuation(
+        &mut self,
+        attenuation: RxAttenuation,
+    ) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::FIFOTHR, |r| r.close_in_rx(attenuation.into())).await?;
+        Ok(())
+    }
+
+    /// Apply all settings of a receiver profile.
+    pub async fn set_receiver_profile(
+        &mut self,
+        profile: &ReceiverProfile,
+    ) -> Result<(), Error<SpiE>> {
+        self.set_front_end_config(&profile.front_end).await?;
+        self.0.write_register(config::TEST2, |w| w.test2(profile.test2)).await?;
+        self.0.write_register(config::TEST1, |w| w.test1(profile.test1)).await?;
+        self.0
+            .modify_register(config::FIFOTHR, |r| {
+                r.adc_retention(profile.adc_retention as u8)
+                    .close_in_rx(profile.rx_attenuation.into())
+            })
+            .await?;
+        self.set_agc_profile(&profile.agc).await
+    }
+
+    /// Configure the frequency offset compensation loop.
+    pub async fn set_foc_config(&mut self, foc: &FocConfig) -> Result<(), Error<SpiE>> {
+        self.0
+            .modify_register(config::FOCCFG, |r| {
+                r.foc_bs_cs_gate(foc.cs_gate as u8)
+                    .foc_pre_k(foc.pre_k.into())
+                    .foc_post_k(foc.post_k.into())
+                    .foc_limit(foc.limit.into())
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Sets the frequency offset added to the carrier frequency (in Hertz), returning the
+    /// achieved offset.
+    ///
+    /// Fails with [`crate::ConfigError::FrequencyOffset`] outside of -203.125–201.538 kHz
+    /// (with a 26 MHz crystal).
+    pub async fn set_frequency_offset(&mut self, hz: i32) -> Result<i32, Error<SpiE>> {
+        check_frequency_offset(hz, self.0.fxosc)?;
+        let freqoff = from_frequency_offset(hz, self.0.fxosc);
+        self.0.write_register(config::FSCTRL0, |w| w.freqoff(freqoff)).await?;
+        Ok(to_frequency_offset(freqoff, self.0.fxosc))
+    }
+
+    /// Frequency offset added to the carrier frequency (in Hertz).
+    pub async fn get_frequency_offset(&mut self) -> Result<i32, Error<SpiE>> {
+        let freqoff = self.0.read_register(config::FSCTRL0).await?.freqoff();
+        Ok(to_frequency_offset(freqoff, self.0.fxosc))
+    }
+
+    /// Feed the offset estimate of the last received packet to `tracker` and write the new
+    /// learned offset to `FSCTRL0`, returning it in Hertz.
+    ///
+    /// Call in IDLE, after a packet with a good CRC.
+    pub async fn compensate_frequency_offset(
+        &mut self,
+        tracker: &mut FrequencyOffsetTracker,
+    ) -> Result<i32, Error<SpiE>> {
+        let freqest = self.0.read_register(status::FREQEST).await?.freqoff_est();
+        tracker.update(freqest as i8);
+        self.apply_frequency_offset(tracker).await
+    }
+
+    /// Write the offset learned by `tracker` to `FSCTRL0`, returning it in Hertz.
+    pub async fn apply_frequency_offset(
+        &mut self,
+        tracker: &FrequencyOffsetTracker,
+    ) -> Result<i32, Error<SpiE>> {
+        let freqoff = tracker.offset() as u8;
+        self.0.write_register(config::FSCTRL0, |w| w.freqoff(freqoff)).await?;
+        Ok(to_frequency_offset(freqoff, self.0.fxosc))
+    }
+
+    /// Configure the receive window, returning the RX timeout achieved (in microseconds).
+    ///
+    /// Set the Event 0 period first, e.g. with [`Self::set_wake_on_radio`]; the reset value
+    /// gives RX timeouts of 1.95 ms to 125 ms.
+    pub async fn set_rx_window(&mut self, window: &RxWindowConfig) -> Result<Rounded, Error<SpiE>> {
+        let event1 = self.0.read_register(config::WOREVT1).await?.event() as u16;
+        let event0 = event1 << 8 | self.0.read_register(config::WOREVT0).await?.event() as u16;
+        let wor_res = self.0.read_register(config::WORCTRL).await?.wor_res();
+        let rx_time = rx_time_for(event0, wor_res, window.timeout_us, self.0.fxosc);
+
+        self.0
+            .modify_register(config::MCSM2, |r| {
+                r.rx_time_rssi(window.rssi_termination as u8)
+                    .rx_time_qual(window.qualifier.into())
+                    .rx_time(rx_time)
+            })
+            .await?;
+        self.set_preamble_quality_threshold(window.preamble_quality_threshold).await?;
+
+        let timeout_ns = rx_timeout_ns(event0, wor_res, rx_time, self.0.fxosc).unwrap_or(0);
+        Ok(Rounded::new(window.timeout_us as u64, timeout_ns / 1_000))
+    }
+
+    /// Open a receive window as configured with [`Self::set_rx_window`], and wait for it to end.
+    ///
+    /// The RX FIFO is flushed first. The radio is polled every `budget.interval_us` until it
+    /// leaves RX, or until a packet is in the RX FIFO with `MCSM1.RXOFF_MODE` staying in RX. A
+    /// window still open after `budget.polls` polls is closed by going to IDLE, aborting any
+    /// packet being received, and reported as [`RxWindowEnd::Timeout`].
+    pub async fn receive_window<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<RxWindowEnd, Error<SpiE>> {
+        let mcsm2 = self.0.read_register(config::MCSM2).await?;
+        self.set_radio_mode_within(RadioMode::Idle, delay, budget).await?;
+        self.flush_rx_fifo_buffer().await?;
+        self.enable_rx().await?;
+
+        let mut carrier_sense = false;
+        for _ in 0..budget.polls {
+            delay.delay_us(budget.interval_us).await;
+            let status = self.get_packet_status().await?;
+            carrier_sense |= status.carrier_sense;
+            match self.get_machine_state().await? {
+                MachineState::RX | MachineState::RX_END | MachineState::RX_RST => {
+                    if !status.sof_delimiter && self.get_rx_bytes().await? > 0 {
+                        return Ok(RxWindowEnd::Packet);
+                    }
+                }
+                MachineState::RXFIFO_OVERFLOW => {
+                    self.flush_rx_fifo_buffer().await?;
+                    return Err(Error::RxOverflow);
+                }
+                _ if self.get_rx_bytes().await? > 0 => return Ok(RxWindowEnd::Packet),
+                _ if mcsm2.rx_time_rssi() != 0 && !carrier_sense => {
+                    return Ok(RxWindowEnd::NoCarrierSense)
+                }
+                _ if mcsm2.rx_time_qual() == RxTimeQualifier::SyncWordOrPqi.into() => {
+                    return Ok(RxWindowEnd::NoPreambleQuality)
+                }
+                _ => return Ok(RxWindowEnd::Timeout),
+            }
+        }
+
+        self.set_radio_mode_within(RadioMode::Idle, delay, budget).await?;
+        Ok(RxWindowEnd::Timeout)
+    }
+
+    /// Transmit `data` as one packet, refilling the TX FIFO by polling `TXBYTES`.
+    ///
+    /// See [`crate::Cc1101::transmit_stream`].
+    pub async fn transmit_stream<D: DelayNs>(
+        &mut self,
+        data: &[u8],
+        delay: &mut D,
+        config: StreamConfig,
+    ) -> Result<(), Error<SpiE>> {
+        self.stream_tx(data, delay, config, &mut || true).await
+    }
+
+    /// Transmit `data` as one packet, refilling the TX FIFO when `pin` signals room.
+    ///
+    /// See [`crate::Cc1101::transmit_stream_gdo`].
+    pub async fn transmit_stream_gdo<D: DelayNs, P: InputPin>(
+        &mut self,
+        data: &[u8],
+        gdo: Gdo,
+        pin: &mut P,
+        delay: &mut D,
+        config: StreamConfig,
+    ) -> Result<(), Error<SpiE>> {
+        let saved = self.configure_gdo(gdo, GdoCfg::TX_FIFO_FILLED).await?;
+        // Deasserted below the TX FIFO threshold. On pin errors, fall back to polling.
+        let result =
+            self.stream_tx(data, delay, config, &mut || pin.is_low().unwrap_or(true)).await;
+        let restored = self.restore_gdo(saved).await;
+        result?;
+        restored
+    }
+
+    /// Receive one packet into `buf`, draining the RX FIFO by polling `RXBYTES`.
+    ///
+    /// See [`crate::Cc1101::receive_stream`].
+    pub async fn receive_stream<D: DelayNs>(
+        &mut self,
+        buf: &mut [u8],
+        length: StreamLength,
+        delay: &mut D,
+        config: StreamConfig,
+    ) -> Result<usize, Error<SpiE>> {
+        self.stream_rx(buf, length, delay, config, &mut || true).await
+    }
+
+    /// Receive one packet into `buf`, draining the RX FIFO when `pin` signals data.
+    ///
+    /// See [`crate::Cc1101::receive_stream_gdo`].
+    pub async fn receive_stream_gdo<D: DelayNs, P: InputPin>(
+        &mut self,
+        buf: &mut [u8],
+        length: StreamLength,
+        gdo: Gdo,
+        pin: &mut P,
+        delay: &mut D,
+        config: StreamConfig,
+    ) -> Result<usize, Error<SpiE>> {
+        let saved = self.configure_gdo(gdo, GdoCfg::RX_FIFO_FILLED_END_OF_PKT).await?;
+        // Asserted at the RX FIFO threshold or end of packet. On pin errors, fall back to polling.
+        let result =
+            self.stream_rx(buf, length, delay, config, &mut || pin.is_high().unwrap_or(true)).await;
+        let restored = self.restore_gdo(saved).await;
+        let read = result?;
+        restored?;
+        Ok(read)
+    }
+
+    async fn stream_tx<D: DelayNs>(
+        &mut self,
+        data: &[u8],
+        delay: &mut D,
+        config: StreamConfig,
+        ready: &mut impl FnMut() -> bool,
+    ) -> Result<(), Error<SpiE>> {
+        if data.is_empty() {
+            return Err(Error::UserInputError(UserError::BufferTooSmall(1)));
+        }
+        let saved = self.save_stream_registers().await?;
+        let result = self.stream_tx_inner(data, delay, config, ready).await;
+        if let Err(Error::TxUnderflow) = result {
+            // Acknowledge the underflow, the original error takes precedence.
+            let _ = self.flush_tx_fifo_buffer().await;
+        }
+        let restored = self.restore_stream_registers(saved).await;
+        result?;
+        restored
+    }
+
+    async fn stream_tx_inner<D: DelayNs>(
+        &mut self,
+        data: &[u8],
+        delay: &mut D,
+        config: StreamConfig,
+        ready: &mut impl FnMut() -> bool,
+    ) -> Result<(), Error<SpiE>> {
+        let total = data.len();
+
+        self.set_radio_mode_within(RadioMode::Idle, delay, config.budget).await?;
+        self.flush_tx_fifo_buffer().await?;
+        self.set_fifo_threshold(config.threshold).await?;
+        let mut fixed = self.set_stream_length(total).await?;
+
+        let mut written = total.min(FIFO_SIZE_MAX as usize);
+        self.0.write_burst(multi::FIFO, &data[..written]).await?;
+        self.enable_tx().await?;
+
+        let mut polls = 0;
+        while written < total || !fixed {
+            if ready() {
+                let in_fifo = self.get_tx_bytes().await? as usize;
+
+                if !fixed && total - (written - in_fifo) < BYTE_COUNTER_SPAN {
+                    self.0
+                        .modify_register(config::PKTCTRL0, |r| {
+                            r.length_config(LengthConfig::FIXED.into())
+                        })
+                        .await?;
+                    fixed = true;
+                }
+
+                let chunk = tx_chunk(total - written, in_fifo, config.threshold);
+                if chunk > 0 {
+                    self.0.write_burst(multi::FIFO, &data[written..written + chunk]).await?;
+                    written += chunk;
+                    polls = 0;
+                    continue;
+                }
+            }
+
+            self.poll_wait(MachineState::TX, &mut polls, delay, config.budget).await?;
+        }
+
+        // Wait for the remaining bytes to go on air, and the radio to enter the TX off state.
+        let txoff = off_state(self.0.read_register(config::MCSM1).await?.txoff_mode());
+        let mut polls = 0;
+        loop {
+            match self.get_machine_state().await? {
+                MachineState::TXFIFO_UNDERFLOW => return Err(Error::TxUnderflow),
+                // Staying in TX, the radio sends preamble once the TX FIFO has drained.
+                MachineState::TX
+                    if txoff == MachineState::TX && self.get_tx_bytes().await? == 0 =>
+                {
+                    return Ok(())
+                }
+                MachineState::TX | MachineState::TX_END => {
+                    self.poll_wait(txoff, &mut polls, delay, config.budget).await?
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    async fn stream_rx<D: DelayNs>(
+        &mut self,
+        buf: &mut [u8],
+        length: StreamLength,
+        delay: &mut D,
+        config: StreamConfig,
+        ready: &mut impl FnMut() -> bool,
+    ) -> Result<usize, Error<SpiE>> {
+        let saved = self.save_stream_registers().await?;
+        let result = self.stream_rx_inner(buf, length, delay, config, ready).await;
+        if result.is_err() {
+            // Drop the partial packet, the original error takes precedence.
+            if self.set_radio_mode_within(RadioMode::Idle, delay, config.budget).await.is_ok() {
+                let _ = self.flush_rx_fifo_buffer().await;
+            }
+        }
+        let restored = self.restore_stream_registers(saved).await;
+        let read = result?;
+        restored?;
+        Ok(read)
+    }
+
+    async fn stream_rx_inner<D: DelayNs>(
+        &mut self,
+        buf: &mut [u8],
+        length: StreamLength,
+        delay: &mut D,
+        config: StreamConfig,
+        ready: &mut impl FnMut() -> bool,
+    ) -> Result<usize, Error<SpiE>> {
+        let status_len = rx_status_len(self.0.rx_status_fields);
+        let mut expected =
+            rx_expected(length, buf.len(), status_len).map_err(Error::UserInputError)?;
+        let mut fixed = true;
+        let total = match length {
+            StreamLength::Fixed(total) => total,
+            StreamLength::Variable => 0,
+        };
+
+        self.set_radio_mode_within(RadioMode::Idle, delay, config.budget).await?;
+        self.flush_rx_fifo_buffer().await?;
+        self.set_fifo_threshold(config.threshold).await?;
+        match length {
+            StreamLength::Fixed(total) => fixed = self.set_stream_length(total).await?,
+            StreamLength::Variable => {
+                let max_len = (buf.len() - status_len - 1).min(u8::MAX as usize) as u8;
+                self.0
+                    .modify_register(config::PKTCTRL0, |r| {
+                        r.length_config(LengthConfig::VARIABLE.into())
+                    })
+                    .await?;
+                self.0.write_register(config::PKTLEN, |w| w.packet_length(max_len)).await?;
+            }
+        }
+        self.enable_rx().await?;
+
+        let mut read = 0;
+        let mut polls = 0;
+        loop {
+            if expected == Some(read) {
+                return Ok(read);
+            }
+
+            if ready() {
+                let in_fifo = self.rx_bytes_stable().await? as usize;
+
+                if !fixed && total - (read + in_fifo).min(total) < BYTE_COUNTER_SPAN {
+                    self.0
+                        .modify_register(config::PKTCTRL0, |r| {
+                            r.length_config(LengthConfig::FIXED.into())
+                        })
+                        .await?;
+                    fixed = true;
+                }
+
+                let chunk = rx_chunk(expected, read, in_fifo, config.threshold);
+                if chunk > 0 {
+                    self.0.read_burst(multi::FIFO, &mut buf[read..read + chunk]).await?;
+                    if expected.is_none() {
+                        expected = Some(
+                            rx_expected_variable(buf[0], buf.len(), status_len)
+                                .map_err(Error::UserInputError)?,
+                        );
+                    }
+                    read += chunk;
+                    polls = 0;
+                    continue;
+                }
+            }
+
+            self.poll_wait(MachineState::RX, &mut polls, delay, config.budget).await?;
+        }
+    }
+
+    /// Program `PKTCTRL0.LENGTH_CONFIG` and `PKTLEN` for a streamed packet of `total` bytes.
+    /// Returns whether fixed packet length mode is in effect.
+    async fn set_stream_length(&mut self, total: usize) -> Result<bool, Error<SpiE>> {
+        let fixed = total < BYTE_COUNTER_SPAN;
+        let mode = if fixed {
+            LengthConfig::FIXED
+        } else {
+            LengthConfig::INFINITE
+        };
+        self.0.modify_register(config::PKTCTRL0, |r| r.length_config(mode.into())).await?;
+        self.0
+            .write_register(config::PKTLEN, |w| w.packet_length((total % BYTE_COUNTER_SPAN) as u8))
+            .await?;
+        Ok(fixed)
+    }
+
+    async fn save_stream_registers(&mut self) -> Result<SavedRegisters, Error<SpiE>> {
+        Ok(SavedRegisters {
+            fifo_thr: self.0.read_register(config::FIFOTHR).await?.fifo_thr(),
+            length_config: self.0.read_register(config::PKTCTRL0).await?.length_config(),
+            packet_length: self.0.read_register(config::PKTLEN).await?.packet_length(),
+        })
+    }
+
+    async fn restore_stream_registers(&mut self, saved: SavedRegisters) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::FIFOTHR, |r| r.fifo_thr(saved.fifo_thr)).await?;
+        self.0.modify_register(config::PKTCTRL0, |r| r.length_config(saved.length_config)).await?;
+        self.0.write_register(config::PKTLEN, |w| w.packet_length(saved.packet_length)).await?;
+        Ok(())
+    }
+
+    /// Configure `gdo` as active high `cfg`, returning the previous configuration.
+    async fn configure_gdo(&mut self, gdo: Gdo, cfg: GdoCfg) -> Result<SavedGdo, Error<SpiE>> {
+        let cfg = cfg.into();
+        let saved = match gdo {
+            Gdo::Gdo0 => {
+                let r = self.0.read_register(config::IOCFG0).await?;
+                self.0.modify_register(config::IOCFG0, |r| r.gdo0_inv(0).gdo0_cfg(cfg)).await?;
+                SavedGdo {
+                    gdo,
+                    cfg: r.gdo0_cfg(),
+                    inv: r.gdo0_inv(),
+                }
+            }
+            Gdo::Gdo1 => {
+                let r = self.0.read_register(config::IOCFG1).await?;
+                self.0.modify_register(config::IOCFG1, |r| r.gdo1_inv(0).gdo1_cfg(cfg)).await?;
+                SavedGdo {
+                    gdo,
+                    cfg: r.gdo1_cfg(),
+                    inv: r.gdo1_inv(),
+                }
+            }
+            Gdo::Gdo2 => {
+                let r = self.0.read_register(config::IOCFG2).await?;
+                self.0.modify_register(config::IOCFG2, |r| r.gdo2_inv(0).gdo2_cfg(cfg)).await?;
+                SavedGdo {
+                    gdo,
+                    cfg: r.gdo2_cfg(),
+                    inv: r.gdo2_inv(),
+                }
+            }
+        };
+        Ok(saved)
+    }
+
+    async fn restore_gdo(&mut self, saved: SavedGdo) -> Result<(), Error<SpiE>> {
+        let SavedGdo {
+            gdo,
+            cfg,
+            inv,
+        } = saved;
+        match gdo {
+            Gdo::Gdo0 => {
+                self.0.modify_register(config::IOCFG0, |r| r.gdo0_inv(inv).gdo0_cfg(cfg)).await?
+            }
+            Gdo::Gdo1 => {
+                self.0.modify_register(config::IOCFG1, |r| r.gdo1_inv(inv).gdo1_cfg(cfg)).await?
+            }
+            Gdo::Gdo2 => {
+                self.0.modify_register(config::IOCFG2, |r| r.gdo2_inv(inv).gdo2_cfg(cfg)).await?
+            }
+        }
+        Ok(())
+    }
+
+    /// Read `RXBYTES` until two consecutive reads agree (CC1101 errata, SWRZ020E).
+    async fn rx_bytes_stable(&mut self) -> Result<u8, Error<SpiE>> {
+        let mut last = self.get_rx_bytes().await?;
+        loop {
+            let num_rxbytes = self.get_rx_bytes().await?;
+            if num_rxbytes == last {
+                return Ok(num_rxbytes);
+            }
+            last = num_rxbytes;
+        }
+    }
+
+    /// Wait one poll interval, failing with [`Error::Timeout`] once the budget is spent.
+    async fn poll_wait<D: DelayNs>(
+        &mut self,
+        expected: MachineState,
+        polls: &mut u32,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<(), Error<SpiE>> {
+        if *polls >= budget.polls {
+            let last_seen = self.get_machine_state().await?;
+            return Err(Error::Timeout {
+                expected,
+                last_seen,
+            });
+        }
+        *polls += 1;
+        delay.delay_us(budget.interval_us).await;
+        Ok(())
+    }
+
+    /// Sweep `range`, storing the RSSI of each point in `bins` in order, and return the number
+    /// of points.
+    ///
+    /// See [`crate::Cc1101::scan_rssi`].
+    pub async fn scan_rssi<D: DelayNs>(
+        &mut self,
+        range: ScanRange<'_>,
+        bins: &mut [RssiBin],
+        config: ScanConfig,
+        delay: &mut D,
+    ) -> Result<usize, Error<SpiE>> {
+        if let ScanRange::Frequencies {
+            step_hz: 0,
+            ..
+        } = range
+        {
+            return Err(Error::InvalidConfig(ConfigError::FrequencyStep(0)));
+        }
+        let points = range.len();
+        if bins.len() < points {
+            return Err(Error::UserInputError(UserError::BufferTooSmall(points)));
+        }
+
+        let mode = match self.get_machine_state().await? {
+            MachineState::RX => RadioMode::Receive,
+            _ => RadioMode::Idle,
+        };
+        let mut freq = [0; 3];
+        self.0.read_config_burst(config::FREQ2, &mut freq).await?;
+        let channel = self.get_channel().await?;
+        let mut fscal = [0; 3];
+        self.0.read_config_burst(config::FSCAL3, &mut fscal).await?;
+
+        let result = self.sweep(range, &mut bins[..points], config, delay).await;
+        // Restore even if the sweep failed, but report the sweep's error first.
+        let restored = self.restore_scan(mode, &freq, channel, &fscal, delay, config.budget).await;
+        result?;
+        restored?;
+        Ok(points)
+    }
+
+    async fn restore_scan<D: DelayNs>(
+        &mut self,
+        mode: RadioMode,
+        freq: &[u8; 3],
+        channel: u8,
+        fscal: &[u8; 3],
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<(), Error<SpiE>> {
+        self.set_radio_mode_within(RadioMode::Idle, delay, budget).await?;
+        self.0.write_config_burst(config::FREQ2, freq).await?;
+        self.set_channel(channel).await?;
+        self.0.write_config_burst(config::FSCAL3, fscal).await?;
+        if mode == RadioMode::Receive {
+            self.set_radio_mode_within(RadioMode::Receive, delay, budget).await?;
+        }
+        Ok(())
+    }
+
+    /// RSSI settling time after entering RX and RSSI update period, in microseconds, for the
+    /// current configuration.
+    async fn rssi_timing(&mut self) -> Result<(u32, u32), Error<SpiE>> {
+        let mdmcfg4 = self.0.read_register(config::MDMCFG4).await?;
+        let chanbw = (mdmcfg4.chanbw_m(), mdmcfg4.chanbw_e());
+        let drate = (self.0.read_register(config::MDMCFG3).await?.drate_m(), mdmcfg4.drate_e());
+        let filter_length = self.0.read_register(config::AGCCTRL0).await?.filter_length();
+        Ok(rssi_timing(chanbw, drate, filter_length, self.0.fxosc))
+    }
+
+    async fn sweep<D: DelayNs>(
+        &mut self,
+        range: ScanRange<'_>,
+        bins: &mut [RssiBin],
+        config: ScanConfig,
+        delay: &mut D,
+    ) -> Result<(), Error<SpiE>> {
+        let (settle_us, update_us) = self.rssi_timing().await?;
+        let manual_cal = self.0.read_register(config::MCSM0).await?.fs_autocal()
+            != AutoCalibration::FromIdle as u8;
+
+        let freq = (
+            self.0.read_register(config::FREQ0).await?.freq(),
+            self.0.read_register(config::FREQ1).await?.freq(),
+            self.0.read_register(config::FREQ2).await?.freq(),
+        );
+        let chanspc_m = self.0.read_register(config::MDMCFG0).await?.chanspc_m();
+        let chanspc_e = self.0.read_register(config::MDMCFG1).await?.chanspc_e();
+
+        for (i, bin) in bins.iter_mut().enumerate() {
+            let frequency_hz = match range {
+                ScanRange::Frequencies {
+                    start_hz,
+                    step_hz,
+                    ..
+                } => {
+                    let hz = start_hz + i as u64 * step_hz;
+                    let actual = self.set_frequency_within(hz, delay, config.budget).await?.actual;
+                    self.set_channel(0).await?;
+                    actual
+                }
+                ScanRange::Channels(channels) => {
+                    self.set_radio_mode_within(RadioMode::Idle, delay, config.budget).await?;
+                    self.set_channel(channels[i]).await?;
+                    carrier_hz(freq, chanspc_m, chanspc_e, channels[i], self.0.fxosc)
+                }
+            };
+            if manual_cal {
+                self.set_radio_mode_within(RadioMode::Calibrate, delay, config.budget).await?;
+            }
+            self.set_radio_mode_within(RadioMode::Receive, delay, config.budget).await?;
+            delay.delay_us(settle_us).await;
+
+            let (mut min_dbm, mut peak_dbm, mut sum) = (i16::MAX, i16::MIN, 0i32);
+            let samples = config.samples.max(1);
+            for sample in 0..samples {
+                if sample > 0 {
+                    delay.delay_us(update_us).await;
+                }
+                let rssi_dbm = self.get_rssi_dbm().await?;
+                min_dbm = min_dbm.min(rssi_dbm);
+                peak_dbm = peak_dbm.max(rssi_dbm);
+                sum += rssi_dbm as i32;
+            }
+            *bin = RssiBin {
+                frequency_hz,
+                min_dbm,
+                avg_dbm: (sum / samples as i32) as i16,
+                peak_dbm,
+            };
+        }
+        Ok(())
+    }
+
+    /// Transmit the packet in the TX FIFO once clear channel assessment allows it, returning
+    /// the number of attempts it took.
+    ///
+    /// See [`crate::Cc1101::transmit_with_cca`].
+    pub async fn transmit_with_cca<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        config: LbtConfig,
+    ) -> Result<u8, Error<SpiE>> {
+        if self.get_machine_state().await? != MachineState::RX {
+            self.set_radio_mode_within(RadioMode::Receive, delay, config.budget).await?;
+        }
+        let (settle_us, _) = self.rssi_timing().await?;
+        delay.delay_us(settle_us.max(config.listen_us)).await;
+
+        let mut random = config.seed;
+        let mut window_us = config.backoff_us;
+        for attempt in 1..=config.attempts {
+            self.enable_tx().await?;
+            if self.await_cca(delay, config.budget).await? {
+                return Ok(attempt);
+            }
+
+            if attempt < config.attempts {
+                random ^= self.get_rssi_dbm().await? as u32;
+                delay.delay_us(next_random(&mut random) % window_us.max(1)).await;
+                window_us = window_us.saturating_mul(2).min(config.max_backoff_us);
+            }
+        }
+        Err(Error::ChannelBusy)
+    }
+
+    /// Wait for the radio to settle after `STX` in RX: `true` once in TX, `false` if it stayed
+    /// in RX as the channel is busy.
+    async fn await_cca<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<bool, Error<SpiE>> {
+        let mut last_seen = MachineState::RX;
+        for _ in 0..budget.polls {
+            delay.delay_us(budget.interval_us).await;
+            last_seen = self.get_machine_state().await?;
+            match last_seen {
+                MachineState::TX => return Ok(true),
+                MachineState::RX => return Ok(false),
+                MachineState::TXFIFO_UNDERFLOW => {
+                    self.flush_tx_fifo_buffer().await?;
+                    return Err(Error::TxUnderflow);
+                }
+                _ => {}
+            }
+        }
+        Err(Error::Timeout {
+            expected: MachineState::TX,
+            last_seen,
+        })
+    }
+
+    /// Calibrate each of `channels` at `temperature_c`, storing the results in `cache`.
+    ///
+    /// See [`crate::Cc1101::calibrate_channels`].
+    pub async fn calibrate_channels<D: DelayNs>(
+        &mut self,
+        cache: &mut FscalCache<'_>,
+        channels: &[u8],
+        temperature_c: i8,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<(), Error<SpiE>> {
+        self.set_autocalibration(AutoCalibration::Disabled).await?;
+        for &channel in channels {
+            self.calibrate_channel(cache, channel, temperature_c, delay, budget).await?;
+        }
+        Ok(())
+    }
+
+    /// Switch to `channel` in IDLE, restoring its calibration from `cache`.
+    ///
+    /// See [`crate::Cc1101::hop_to_channel`].
+    pub async fn hop_to_channel<D: DelayNs>(
+        &mut self,
+        cache: &mut FscalCache<'_>,
+        channel: u8,
+        temperature_c: i8,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<bool, Error<SpiE>> {
+        self.set_radio_mode_within(RadioMode::Idle, delay, budget).await?;
+
+        match cache.get(channel) {
+            Some(entry) if !cache.is_stale(channel, temperature_c) => {
+                self.0.write_config_burst(config::FSCAL3, &entry.fscal).await?;
+                self.set_channel(channel).await?;
+                Ok(false)
+            }
+            _ => {
+                self.calibrate_channel(cache, channel, temperature_c, delay, budget).await?;
+                Ok(true)
+            }
+        }
+    }
+
+    async fn calibrate_channel<D: DelayNs>(
+        &mut self,
+        cache: &mut FscalCache<'_>,
+        channel: u8,
+        temperature_c: i8,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<(), Error<SpiE>> {
+        let slot = cache.slot(channel).map_err(Error::UserInputError)?;
+
+        self.set_channel(channel).await?;
+        self.set_radio_mode_within(RadioMode::Calibrate, delay, budget).await?;
+
+        let mut fscal = [0; 3];
+        self.0.read_config_burst(config::FSCAL3, &mut fscal).await?;
+        *slot = Some(FscalEntry {
+            fscal,
+            temperature_c,
+        });
+        Ok(())
+    }
+
+    /// Read Frequency Offset Estimate from Demodulator
+    /// Frequency offset compensation is only supported for 2-FSK, GFSK, 4-FSK, and MSK modulation.
+    /// This register will read 0 when using ASK or OOK modulation.
+    pub async fn get_est_freq_offset(&mut self) -> Result<i32, Error<SpiE>> {
+        Ok(to_frequency_offset(
+            self.0.read_register(status::FREQEST).await?.freqoff_est(),
+            self.0.fxosc,
+        ))
+    }
+
+    /// Read Link Quality Indicator.
+    /// Note: Register field LQI.CRC_OK is ignored in this read because it's available also in the PKTSTATUS.CRC_OK register field.
+    pub async fn get_lqi(&mut self) -> Result<u8, Error<SpiE>> {
+        Ok(self.0.read_register(status::LQI).await?.lqi())
+    }
+
+    /// Received Signal Strength Indicator is an estimate of the signal power level in the chosen channel.
+    pub async fn get_rssi_dbm(&mut self) -> Result<i16, Error<SpiE>> {
+        Ok(from_rssi_to_rssi_dbm(self.0.read_register(status::RSSI).await?.rssi()))
+    }
+
+    /// Read the Machine State
+    pub async fn get_machine_state(&mut self) -> Result<MachineState, Error<SpiE>> {
+        machine_state(self.0.read_register(status::MARCSTATE).await?.marc_state())
+    }
+
+    /// Read the Current GDOx Status and Packet Status
+    pub async fn get_packet_status(&mut self) -> Result<PacketStatus, Error<SpiE>> {
+        Ok(self.0.read_register(status::PKTSTATUS).await?.into())
+    }
+
+    /// Read number of bytes in TX FIFO
+    pub async fn get_tx_bytes(&mut self) -> Result<u8, Error<SpiE>> {
+        let txbytes = self.0.read_register(status::TXBYTES).await?;
+        let num_txbytes: u8 = txbytes.num_txbytes();
+
+        if txbytes.txfifo_underflow() != 0 {
+            return Err(Error::TxUnderflow);
+        }
+
+        Ok(num_txbytes)
+    }
+
+    /// Read number of bytes in RX FIFO
+    pub async fn get_rx_bytes(&mut self) -> Result<u8, Error<SpiE>> {
+        let rxbytes = self.0.read_register(status::RXBYTES).await?;
+        let num_rxbytes: u8 = rxbytes.num_rxbytes();
+
+        if rxbytes.rxfifo_overflow() != 0 {
+            return Err(Error::RxOverflow);
+        }
+
+        Ok(num_rxbytes)
+    }
+
+    /// Read one complete packet from the RX FIFO, copying its payload into `buf`.
+    ///
+    /// The packet framing (length byte, address byte and appended status bytes) is read back
+    /// from `PKTCTRL0`, `PKTCTRL1` and `PKTLEN`, so no knowledge of the current configuration
+    /// is needed. The packet must be fully received, e.g. once a GDO configured as
+    /// [`GdoCfg::SYNC_WORD`] has deasserted. Use streaming for infinite packet length mode.
+    ///
+    /// Fails with [`UserError::BufferTooSmall`] if the payload does not fit in `buf`. The
+    /// packet is then left in the RX FIFO in fixed packet length mode, and dropped from it in
+    /// variable packet length mode, as its length byte has been read.
+    pub async fn receive_packet<'a>(
+        &mut self,
+        buf: &'a mut [u8],
+    ) -> Result<RxPacket<'a>, Error<SpiE>> {
+        let pktctrl1 = self.0.read_register(config::PKTCTRL1).await?;
+        let framing = PacketFraming {
+            length_config: self.0.read_register(config::PKTCTRL0).await?.length_config(),
+            packet_length: self.0.read_register(config::PKTLEN).await?.packet_length(),
+            address: pktctrl1.adr_chk() != AddressCheck::DISABLED as u8,
+            status: pktctrl1.append_status() != 0,
+        };
+
+        if framing.infinite() {
+            return Err(Error::UserInputError(UserError::InfinitePacketLength));
+        }
+
+        let mut field = [0];
+        let length = if framing.variable() {
+            self.0.read_burst(multi::FIFO, &mut field).await?;
+            Some(field[0])
+        } else {
+            None
+        };
+
+        let len = framing.payload_len(length);
+        if len > buf.len() {
+            // Only the length byte has been read, drop the rest of the packet so that the
+            // next one is read from its start.
+            if let Some(length) = length {
+                self.discard_rx(length as usize + framing.status as usize * 2).await?;
+            }
+            return Err(Error::UserInputError(UserError::BufferTooSmall(len)));
+        }
+
+        let address = if framing.address {
+            self.0.read_burst(multi::FIFO, &mut field).await?;
+            Some(field[0])
+        } else {
+            None
+        };
+
+        self.0.read_burst(multi::FIFO, &mut buf[..len]).await?;
+
+        let status = if framing.status {
+            let mut status = [0; 2];
+            self.0.read_burst(multi::FIFO, &mut status).await?;
+            Some(RxStatus::from_bytes(status))
+        } else {
+            None
+        };
+
+        Ok(RxPacket {
+            payload: &buf[..len],
+            length,
+            address,
+            status,
+        })
+    }
+
+    /// Read and drop `len` bytes from the RX FIFO.
+    async fn discard_rx(&mut self, mut len: usize) -> Result<(), Error<SpiE>> {
+        let mut scratch = [0; 16];
+        while len > 0 {
+            let chunk = len.min(scratch.len());
+            self.0.read_burst(multi::FIFO, &mut scratch[..chunk]).await?;
+            len -= chunk;
+        }
+        Ok(())
+    }
+
+    /// Read data from FIFO
+    ///
+    /// Prefer [`Self::receive_packet`], which does not depend on the optional fields
+    /// tracked by the driver.
+    pub async fn read_data(
+        &mut self,
+        length: &mut Option<u8>,
+        address: &mut Option<u8>,
+        rssi: &mut Option<i16>,
+        lqi: &mut Option<u8>,
+        data: &mut [u8],
+    ) -> Result<(), Error<SpiE>> {
+        let fields = FifoFields::of_async(&self.0);
+        let mut optional_fields = [0, 0];
+        let num = fields.check_read(length, address, rssi, lqi, data)?;
+        self.0.access_fifo(Access::Read, &mut optional_fields[..num], data).await?;
+        fields.unpack(&optional_fields, length, address, rssi, lqi, data);
+        Ok(())
+    }
+
+    /// Write data into FIFO
+    pub async fn write_data(
+        &mut self,
+        length: &mut Option<u8>,
+        address: &mut Option<u8>,
+        data: &mut [u8],
+    ) -> Result<(), Error<SpiE>> {
+        let fields = FifoFields::of_async(&self.0);
+        let mut optional_fields = [0, 0];
+        let num = fields.pack(length, address, data, &mut optional_fields)?;
+        self.0.access_fifo(Access::Write, &mut optional_fields[..num], data).await?;
+        Ok(())
+    }
+
+    /// Set radio in Idle/Sleep/Calibrate/Transmit/Receive mode, giving up after `budget`.
+    ///
+    /// See [`crate::Cc1101::set_radio_mode_within`].
+    pub async fn set_radio_mode_within<D: DelayNs>(
+        &mut self,
+        radio_mode: RadioMode,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<(), Error<SpiE>> {
+        let idle = self.enter_radio_mode(RadioMode::Idle).await?;
+        self.await_machine_state_within(RadioMode::Idle, idle, delay, budget).await?;
+        if radio_mode == RadioMode::Idle {
+            return Ok(());
+        }
+
+        let target = self.enter_radio_mode(radio_mode).await?;
+        match radio_mode {
+            RadioMode::Sleep => Ok(()),
+            _ => self.await_machine_state_within(radio_mode, target, delay, budget).await,
+        }
+    }
+
+    /// Issue the command strobe for `radio_mode`, returning the state it settles in.
+    async fn enter_radio_mode(
+        &mut self,
+        radio_mode: RadioMode,
+    ) -> Result<MachineState, Error<SpiE>> {
+        Ok(match radio_mode {
+            RadioMode::Idle => {
+                self.exit_rx_tx().await?;
+                MachineState::IDLE
+            }
+            RadioMode::Sleep => {
+                self.enter_power_down_mode().await?;
+                MachineState::SLEEP
+            }
+            RadioMode::Calibrate => {
+                self.cal_freq_synth_and_turn_off().await?;
+                MachineState::IDLE
+            }
+            RadioMode::Transmit => {
+                self.enable_tx().await?;
+                MachineState::TX
+            }
+            RadioMode::Receive => {
+                self.enable_rx().await?;
+                MachineState::RX
+            }
+        })
+    }
+
+    async fn await_machine_state_within<D: DelayNs>(
+        &mut self,
+        radio_mode: RadioMode,
+        target: MachineState,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<(), Error<SpiE>> {
+        let reached = |state| state == target;
+        self.poll_machine_state_within(target, reached, Some(radio_mode), delay, budget).await?;
+        Ok(())
+    }
+
+    /// Poll `MARCSTATE` with `delay` between reads until `reached` accepts the state, and
+    /// return it, or fail with [`Error::Timeout`] for `expected` after `budget`.
+    ///
+    /// With `recover`, the FIFO error states are left by flushing the FIFO and re-issuing the
+    /// command strobe for that radio mode.
+    async fn poll_machine_state_within<D: DelayNs>(
+        &mut self,
+        expected: MachineState,
+        reached: impl Fn(MachineState) -> bool,
+        recover: Option<RadioMode>,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<MachineState, Error<SpiE>> {
+        let mut last_seen = self.get_machine_state().await?;
+
+        for _ in 0..budget.polls {
+            match (last_seen, recover) {
+                (state, _) if reached(state) => return Ok(state),
+                (MachineState::RXFIFO_OVERFLOW, Some(radio_mode)) => {
+                    self.flush_rx_fifo_buffer().await?;
+                    self.enter_radio_mode(radio_mode).await?;
+                }
+                (MachineState::TXFIFO_UNDERFLOW, Some(radio_mode)) => {
+                    self.flush_tx_fifo_buffer().await?;
+                    self.enter_radio_mode(radio_mode).await?;
+                }
+                _ => {}
+            }
+            delay.delay_us(budget.interval_us).await;
+            last_seen = self.get_machine_state().await?;
+        }
+
+        if reached(last_seen) {
+            Ok(last_seen)
+        } else {
+            Err(Error::Timeout {
+                expected,
+                last_seen,
+            })
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // Mirrors the blocking helpers slated for discontinuation, see `crate::Cc1101`.
+    // ------------------------------------------------------------------------
+
+    async fn await_machine_state(&mut self, target_state: MachineState) -> Result<(), Error<SpiE>> {
+        loop {
+            let machine_state = self.get_machine_state().await?;
+            if target_state == machine_state {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait for the radio to leave RX after a packet, returning the state it went to, or fail
+    /// with [`Error::Timeout`] after `budget`.
+    async fn await_rx_off<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<MachineState, Error<SpiE>> {
+        let rxoff = off_state(self.0.read_register(config::MCSM1).await?.rxoff_mode());
+        if rxoff == MachineState::RX {
+            return Ok(rxoff);
+        }
+        let reached = |state| !in_rx(state);
+        self.poll_machine_state_within(rxoff, reached, None, delay, budget).await
+    }
+
+    async fn enter_idle(&mut self) -> Result<(), Error<SpiE>> {
+        self.exit_rx_tx().await?;
+        self.await_machine_state(MachineState::IDLE).await
+    }
+
+    /// Configure some default settings, to be removed in the future.
+    #[rustfmt::skip]
+    pub async fn set_defaults(&mut self) -> Result<(), Error<SpiE>> {
+        self.reset_chip().await?;
+
+        self.white_data_enable(false).await?;
+
+        self.set_freq_if(203_125).await?;
+
+        self.0.write_register(config::MDMCFG2, |w| w.dem_dcfilt_off(1)).await?;
+
+        self.set_autocalibration(AutoCalibration::FromIdle).await?;
+
+        self.set_max_lna_gain(MaxLnaGain::BelowMax9_2).await?;
+
+        Ok(())
+    }
+
+    /// Set radio in Idle/Sleep/Calibrate/Transmit/Receive mode.
+    ///
+    /// Waits for the radio without a bound, prefer [`Self::set_radio_mode_within`].
+    pub async fn set_radio_mode(&mut self, radio_mode: RadioMode) -> Result<(), Error<SpiE>> {
+        let target = match radio_mode {
+            RadioMode::Idle => {
+                self.exit_rx_tx().await?;
+                MachineState::IDLE
+            }
+            RadioMode::Sleep => {
+                self.enter_idle().await?;
+                self.enter_power_down_mode().await?;
+                MachineState::SLEEP
+            }
+            RadioMode::Calibrate => {
+                self.enter_idle().await?;
+                self.cal_freq_synth_and_turn_off().await?;
+                MachineState::MANCAL
+            }
+            RadioMode::Transmit => {
+                self.enter_idle().await?;
+                self.enable_tx().await?;
+                MachineState::TX
+            }
+            RadioMode::Receive => {
+                self.enter_idle().await?;
+                self.enable_rx().await?;
+                MachineState::RX
+            }
+        };
+        self.await_machine_state(target).await
+    }
+
+    /// Wait for the number of bytes in the RX FIFO to be non-zero and stable over one poll
+    /// interval, or fail with [`Error::Timeout`] after `budget`.
+    async fn rx_bytes_available<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<u8, Error<SpiE>> {
+        let mut last = 0;
+
+        for _ in 0..budget.polls {
+            let num_rxbytes = self.get_rx_bytes().await?;
+
+            if (num_rxbytes > 0) && (num_rxbytes == last) {
+                return Ok(last);
+            }
+
+            last = num_rxbytes;
+            delay.delay_us(budget.interval_us).await;
+        }
+        let rxoff = off_state(self.0.read_register(config::MCSM1).await?.rxoff_mode());
+        Err(Error::Timeout {
+            expected: rxoff,
+            last_seen: self.get_machine_state().await?,
+        })
+    }
+
+    /// Read a received packet, waiting for the radio to enter the state configured with
+    /// [`Self::set_rxoff_mode`]. The RX FIFO is flushed if that is IDLE.
+    ///
+    /// See [`crate::Cc1101::receive`].
+    pub async fn receive<D: DelayNs>(
+        &mut self,
+        addr: &mut u8,
+        buf: &mut [u8],
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<u8, Error<SpiE>> {
+        match self.rx_bytes_available(delay, budget).await {
+            Ok(_nbytes) => {
+                let mut length: Option<u8> = Some(0);
+                let mut address: Option<u8> = Some(0);
+                let mut rssi: Option<i16> = Some(0);
+                let mut lqi: Option<u8> = Some(0);
+                self.read_data(&mut length, &mut address, &mut rssi, &mut lqi, buf).await?;
+                *addr = address.unwrap();
+                let lqi = self.0.read_register(status::LQI).await?;
+                if self.await_rx_off(delay, budget).await? == MachineState::IDLE {
+                    self.flush_rx_fifo_buffer().await?;
+                }
+                if lqi.crc_ok() != 1 {
+                    Err(Error::CrcMismatch)
+                } else {
+                    Ok(length.unwrap())
+                }
+            }
+            Err(err) => {
+                self.flush_rx_fifo_buffer().await?;
+                Err(err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChip, SimDelay, SimGdo, SimPacket, SimSpi, CALIBRATION_US};
+    use core::cell::RefCell;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+
+    const BUDGET: PollBudget = PollBudget::new(100, 1_000);
+
+    /// Run `future` to completion. The simulator completes every access at once, so the
+    /// future never has to wait.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut cx = Context::from_waker(Waker::noop());
+        match pin!(future).poll(&mut cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("simulated access did not complete"),
+        }
+    }
+
+    #[test]
+    fn test_radio_mode() {
+        let chip = RefCell::new(SimChip::new());
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        block_on(cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET)).unwrap();
+        assert_eq!(chip.borrow().state(), MachineState::RX);
+
+        let frequency = block_on(cc1101.set_frequency_within(868_000_000, &mut delay, BUDGET));
+        assert_eq!(frequency.unwrap().actual, 867_999_938);
+        assert_eq!(chip.borrow().state(), MachineState::IDLE);
+
+        assert_eq!(
+            block_on(cc1101.set_radio_mode_within(
+                RadioMode::Calibrate,
+                &mut delay,
+                PollBudget::new(10, 2)
+            )),
+            Err(Error::Timeout {
+                expected: MachineState::IDLE,
+                last_seen: MachineState::MANCAL,
+            })
+        );
+
+        // The unbounded helpers mirror the blocking driver.
+        block_on(cc1101.set_radio_mode(RadioMode::Receive)).unwrap();
+        assert_eq!(chip.borrow().state(), MachineState::RX);
+        let frequency = block_on(cc1101.set_frequency(433_920_000)).unwrap();
+        assert_eq!(frequency.actual, chip.borrow().carrier_hz());
+        assert_eq!(chip.borrow().state(), MachineState::IDLE);
+    }
+
+    #[test]
+    fn test_stream() {
+        let data: [u8; 300] = core::array::from_fn(|i| i as u8);
+        let config = StreamConfig {
+            threshold: FifoThreshold::TX_33_RX_32,
+            budget: BUDGET,
+        };
+
+        let chip = RefCell::new(SimChip::new());
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        let before = chip.borrow().config();
+        let mut gdo = SimGdo::new(&chip, Gdo::Gdo0);
+        block_on(cc1101.transmit_stream_gdo(&data, Gdo::Gdo0, &mut gdo, &mut delay, config))
+            .unwrap();
+        let sent = chip.borrow_mut().take_transmitted().unwrap();
+        assert_eq!(sent.data(), &data);
+        assert_eq!(chip.borrow().config(), before);
+        assert_eq!(
+            block_on(cc1101.transmit_stream(&[], &mut delay, config)),
+            Err(Error::UserInputError(UserError::BufferTooSmall(1)))
+        );
+
+        chip.borrow_mut().inject(sent);
+        let mut buf = [0; 302];
+        let length = StreamLength::Fixed(300);
+        let read = block_on(cc1101.receive_stream(&mut buf, length, &mut delay, config));
+        assert_eq!(read.unwrap(), 302);
+        assert_eq!(&buf[..300], &data);
+        assert_eq!(chip.borrow().config(), before);
+    }
+
+    #[test]
+    fn test_scan_and_transmit_with_cca() {
+        let chip = RefCell::new(SimChip::new());
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        chip.borrow_mut().set_noise_dbm(-95);
+        chip.borrow_mut().set_interferer(Some((868_300_000, -40)));
+        let range = ScanRange::Frequencies {
+            start_hz: 868_000_000,
+            stop_hz: 868_600_000,
+            step_hz: 100_000,
+        };
+        let config = ScanConfig {
+            samples: 4,
+            budget: BUDGET,
+        };
+        let mut bins = [RssiBin::default(); 8];
+        assert_eq!(block_on(cc1101.scan_rssi(range, &mut bins, config, &mut delay)), Ok(7));
+        assert_eq!((bins[3].peak_dbm, bins[0].peak_dbm), (-40, -95));
+        assert_eq!(chip.borrow().state(), MachineState::IDLE);
+
+        block_on(cc1101.set_cca_mode(CcaMode::CciRssiBelowThreshold)).unwrap();
+        block_on(cc1101.write_data(&mut Some(3), &mut None, &mut [1, 2, 3])).unwrap();
+        let lbt = LbtConfig::etsi(0x5EED);
+        chip.borrow_mut().set_channel_busy(true);
+        assert_eq!(block_on(cc1101.transmit_with_cca(&mut delay, lbt)), Err(Error::ChannelBusy));
+        assert_eq!(chip.borrow().state(), MachineState::RX);
+        chip.borrow_mut().set_channel_busy(false);
+        assert_eq!(block_on(cc1101.transmit_with_cca(&mut delay, lbt)), Ok(1));
+        assert_eq!(chip.borrow().state(), MachineState::TX);
+    }
+
+    #[test]
+    fn test_hop_to_channel() {
+        let chip = RefCell::new(SimChip::new());
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        let plan = ChannelPlan::new(902_200_000, 200_000, 64).unwrap();
+        block_on(cc1101.set_channel_plan(&plan)).unwrap();
+        let mut entries = [None; 64];
+        let mut cache = FscalCache::new(&mut entries, 10);
+        block_on(cc1101.calibrate_channels(&mut cache, &[0, 20], 25, &mut delay, BUDGET)).unwrap();
+        assert_eq!(chip.borrow().config().read(config::MCSM0).fs_autocal(), 0);
+
+        let start = chip.borrow().now_us();
+        let hop = block_on(cc1101.hop_to_channel(&mut cache, 0, 30, &mut delay, BUDGET));
+        assert_eq!(hop, Ok(false));
+        assert!(chip.borrow().now_us() - start < CALIBRATION_US as u64);
+        assert_eq!(chip.borrow().config().as_bytes()[0x23..0x26], cache.get(0).unwrap().fscal);
+        let hop = block_on(cc1101.hop_to_channel(&mut cache, 10, 25, &mut delay, BUDGET));
+        assert_eq!(hop, Ok(true));
+    }
+
+    #[test]
+    fn test_receive() {
+        let chip = RefCell::new(SimChip::new());
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        block_on(cc1101.set_packet_length(PacketLength::Variable(32))).unwrap();
+        block_on(cc1101.set_address_filter(AddressFilter::Device(0x42))).unwrap();
+        chip.borrow_mut().inject(SimPacket::new(&[3, 0x42, 1, 2]).with_lqi(5));
+        block_on(cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET)).unwrap();
+
+        let (mut addr, mut buf) = (0, [0; 4]);
+        let length = block_on(cc1101.receive(&mut addr, &mut buf, &mut delay, BUDGET));
+        assert_eq!(length.unwrap(), 3);
+        assert_eq!((addr, buf), (0x42, [1, 2, 0, 0]));
+        assert_eq!(chip.borrow().state(), MachineState::IDLE);
+
+        chip.borrow_mut().inject(SimPacket::new(&[2, 0x42, 7]).with_lqi(9));
+        block_on(cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET)).unwrap();
+        block_on(delay.delay_ms(10));
+        let mut buf = [0; 8];
+        let packet = block_on(cc1101.receive_packet(&mut buf)).unwrap();
+        assert_eq!((packet.payload, packet.address), (&[7][..], Some(0x42)));
+        assert_eq!(packet.status.unwrap().lqi, 9);
+
+        // Without a packet, receive gives up once the budget is spent.
+        block_on(cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET)).unwrap();
+        let budget = PollBudget::new(100, 5);
+        assert_eq!(
+            block_on(cc1101.receive(&mut addr, &mut buf, &mut delay, budget)),
+            Err(Error::Timeout {
+                expected: MachineState::IDLE,
+                last_seen: MachineState::RX,
+            })
+        );
+    }
+
+    #[test]
+    fn test_wake_on_radio() {
+        let chip = RefCell::new(SimChip::new());
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        let wor = WakeOnRadioConfig::from_period_ms(100).rx_timeout_us(5_000);
+        let settings = block_on(cc1101.set_wake_on_radio(&wor)).unwrap();
+        assert_eq!(settings.rx_timeout_us, Some(6_250));
+        assert_eq!(chip.borrow().config(), RadioConfig::default().wake_on_radio(&settings));
+
+        // The RX timeout of the WOR settings also bounds a receive window.
+        let window = RxWindowConfig::new(5_000);
+        let achieved = block_on(cc1101.set_rx_window(&window)).unwrap();
+        assert_eq!(achieved, Rounded::new(5_000, 6_250));
+        let end = block_on(cc1101.receive_window(&mut delay, BUDGET)).unwrap();
+        assert_eq!(end, RxWindowEnd::Timeout);
+        chip.borrow_mut().inject(SimPacket::new(&[1, 5]));
+        let end = block_on(cc1101.receive_window(&mut delay, BUDGET)).unwrap();
+        assert_eq!(end, RxWindowEnd::Packet);
+
+        block_on(cc1101.start_wake_on_radio()).unwrap();
+        assert_eq!(chip.borrow().state(), MachineState::SLEEP);
+    }
+}
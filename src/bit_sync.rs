@@ -0,0 +1,83 @@
+//! Bit synchronization (clock recovery) tuning.
+//!
+//! The demodulator recovers the symbol clock of the received signal with a loop configured in
+//! `BSCFG`, and can compensate for a data rate offset between transmitter and receiver up to
+//! `BS_LIMIT`, see "Bit Synchronization" in the data sheet (TI document SWRS061I).
+
+use hal::spi::SpiDevice;
+
+use crate::lowlevel::registers::*;
+use crate::{BsLimit, BsPostKi, BsPostKp, BsPreKi, BsPreKp, Cc1101, Error};
+
+/// Clock recovery loop, `BSCFG`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BitSyncConfig {
+    /// Integral gain before a sync word is detected.
+    pub pre_ki: BsPreKi,
+    /// Proportional gain before a sync word is detected.
+    pub pre_kp: BsPreKp,
+    /// Integral gain after a sync word is detected.
+    pub post_ki: BsPostKi,
+    /// Proportional gain after a sync word is detected.
+    pub post_kp: BsPostKp,
+    /// Largest data rate offset compensated for.
+    pub limit: BsLimit,
+}
+
+impl Default for BitSyncConfig {
+    /// Reset value of `BSCFG`.
+    fn default() -> Self {
+        BitSyncConfig {
+            pre_ki: BsPreKi::Ki2,
+            pre_kp: BsPreKp::Kp3,
+            post_ki: BsPostKi::HalfKi,
+            post_kp: BsPostKp::Kp,
+            limit: BsLimit::Disabled,
+        }
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Configure the clock recovery loop.
+    pub fn set_bit_sync_config(&mut self, bit_sync: &BitSyncConfig) -> Result<(), Error<SpiE>> {
+        self.0.write_register(config::BSCFG, |w| {
+            w.bs_pre_ki(bit_sync.pre_ki.into())
+                .bs_pre_kp(bit_sync.pre_kp.into())
+                .bs_post_ki(bit_sync.post_ki.into())
+                .bs_post_kp(bit_sync.post_kp.into())
+                .bs_limit(bit_sync.limit.into())
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChip, SimSpi};
+    use crate::RadioConfig;
+    use core::cell::RefCell;
+
+    #[test]
+    fn test_settings() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+        assert_eq!(
+            RadioConfig::default().bit_sync(&BitSyncConfig::default()),
+            RadioConfig::default()
+        );
+
+        // SmartRF Studio setting for 250 kBaud.
+        let bit_sync = BitSyncConfig {
+            pre_ki: BsPreKi::Ki1,
+            pre_kp: BsPreKp::Kp2,
+            ..BitSyncConfig::default()
+        };
+        cc1101.set_bit_sync_config(&bit_sync).unwrap();
+        assert_eq!(chip.borrow().config().as_bytes()[0x1A], 0x1C);
+        assert_eq!(chip.borrow().config(), RadioConfig::default().bit_sync(&bit_sync));
+    }
+}
@@ -0,0 +1,263 @@
+//! Channel plans: evenly spaced channels above a base frequency.
+//!
+//! The carrier frequency is `FREQ2/1/0` plus `CHANNR` times the channel spacing programmed in
+//! `MDMCFG1.CHANSPC_E`/`MDMCFG0.CHANSPC_M`, see section 21 "Frequency Programming" of the data
+//! sheet (TI document SWRS061I). Once a plan is programmed, changing channels is a single
+//! register write.
+
+use hal::spi::SpiDevice;
+
+use crate::lowlevel::convert::*;
+use crate::lowlevel::registers::*;
+use crate::lowlevel::FXOSC;
+use crate::{Cc1101, ConfigError, Error, MachineState, UserError};
+
+/// Evenly spaced channels above a base frequency.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ChannelPlan {
+    freq: (u8, u8, u8),
+    chanspc_m: u8,
+    chanspc_e: u8,
+    channels: u16,
+    fxosc: u64,
+}
+
+impl ChannelPlan {
+    /// `channels` (1 to 256) channels, `spacing_hz` apart, starting at `base_hz` for channel 0.
+    ///
+    /// The base frequency and spacing are rounded to what the radio can be programmed to,
+    /// see [`Self::base_hz`] and [`Self::spacing_hz`]. Fails with
+    /// [`ConfigError::ChannelSpacing`] for a spacing outside of 25.4–405.5 kHz (with a 26 MHz
+    /// crystal), with [`ConfigError::ChannelCount`] for a channel count outside of 1–256, or
+    /// with [`ConfigError::Frequency`] unless all channels are within one frequency band.
+    pub fn new(base_hz: u64, spacing_hz: u64, channels: u16) -> Result<Self, ConfigError> {
+        Self::with_crystal(base_hz, spacing_hz, channels, FXOSC)
+    }
+
+    /// Channel plan for a radio clocked by a `fxosc` Hertz crystal, see
+    /// [`Cc1101::with_crystal`].
+    ///
+    /// Also fails with [`ConfigError::Crystal`] for a crystal outside of 26–27 MHz.
+    pub fn with_crystal(
+        base_hz: u64,
+        spacing_hz: u64,
+        channels: u16,
+        fxosc: u64,
+    ) -> Result<Self, ConfigError> {
+        if !(26_000_000..=27_000_000).contains(&fxosc) {
+            return Err(ConfigError::Crystal(fxosc));
+        }
+        if !(to_chanspc(0, 0, fxosc)..=to_chanspc(255, 3, fxosc)).contains(&spacing_hz) {
+            return Err(ConfigError::ChannelSpacing(spacing_hz));
+        }
+        if !(1..=256).contains(&channels) {
+            return Err(ConfigError::ChannelCount(channels));
+        }
+        let (low, high) = FREQUENCY_BANDS_HZ
+            .into_iter()
+            .find(|&(low, high)| (low..=high).contains(&base_hz))
+            .ok_or(ConfigError::Frequency(base_hz))?;
+
+        let (chanspc_m, chanspc_e) = from_chanspc(spacing_hz, fxosc);
+        let plan = ChannelPlan {
+            freq: from_frequency(base_hz, fxosc),
+            chanspc_m,
+            chanspc_e,
+            channels,
+            fxosc,
+        };
+        // The programmed frequencies are rounded, so check those rather than the requested ones.
+        let top_hz = carrier_hz(plan.freq, chanspc_m, chanspc_e, (channels - 1) as u8, fxosc);
+        if !(low..=high).contains(&plan.base_hz()) || top_hz > high {
+            return Err(ConfigError::Frequency(top_hz));
+        }
+        Ok(plan)
+    }
+
+    /// Frequency of channel 0, as programmed.
+    pub fn base_hz(&self) -> u64 {
+        let (freq0, freq1, freq2) = self.freq;
+        to_frequency(freq0, freq1, freq2, self.fxosc)
+    }
+
+    /// Channel spacing, as programmed.
+    pub fn spacing_hz(&self) -> u64 {
+        to_chanspc(self.chanspc_m, self.chanspc_e, self.fxosc)
+    }
+
+    /// Number of channels in the plan.
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Check that `channel` is part of the plan.
+    pub fn check_channel(&self, channel: u8) -> Result<u8, UserError> {
+        if (channel as u16) < self.channels {
+            Ok(channel)
+        } else {
+            Err(UserError::InvalidChannel(channel))
+        }
+    }
+
+    /// Carrier frequency of `channel`, or `None` if it is not part of the plan.
+    pub fn frequency(&self, channel: u8) -> Option<u64> {
+        self.check_channel(channel).ok()?;
+        Some(carrier_hz(self.freq, self.chanspc_m, self.chanspc_e, channel, self.fxosc))
+    }
+
+    pub(crate) fn check_crystal<SpiE>(&self, fxosc: u64) -> Result<(), Error<SpiE>> {
+        if self.fxosc == fxosc {
+            Ok(())
+        } else {
+            Err(Error::InvalidConfig(ConfigError::Crystal(self.fxosc)))
+        }
+    }
+
+    pub(crate) fn registers(&self) -> ((u8, u8, u8), u8, u8) {
+        (self.freq, self.chanspc_m, self.chanspc_e)
+    }
+}
+
+/// Carrier frequency of `channel` above the base frequency `FREQ0/1/2`, with channel spacing
+/// `CHANSPC_M`/`CHANSPC_E` and a `fxosc` Hertz crystal.
+pub(crate) fn carrier_hz(
+    freq: (u8, u8, u8),
+    chanspc_m: u8,
+    chanspc_e: u8,
+    channel: u8,
+    fxosc: u64,
+) -> u64 {
+    let (freq0, freq1, freq2) = freq;
+    let freq = (freq2 as u64) << 16 | (freq1 as u64) << 8 | freq0 as u64;
+    // Both terms in units of FXOSC / 2^18, to avoid compounding rounding errors.
+    let steps = 4 * freq + ((256 + chanspc_m as u64) << chanspc_e) * channel as u64;
+    steps * fxosc / (1 << 18)
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Program the base frequency and channel spacing of `plan`, and select channel 0.
+    ///
+    /// The radio must be in IDLE, e.g. after [`Self::set_radio_mode_within`], as the frequency
+    /// registers are not applied consistently while it is active. Fails with
+    /// [`UserError::NotIdle`] otherwise, or with [`ConfigError::Crystal`] if `plan` is for
+    /// another crystal frequency.
+    pub fn set_channel_plan(&mut self, plan: &ChannelPlan) -> Result<(), Error<SpiE>> {
+        self.check_idle()?;
+        plan.check_crystal(self.0.fxosc)?;
+        let ((freq0, freq1, freq2), chanspc_m, chanspc_e) = plan.registers();
+        self.0.write_register(config::FREQ0, |w| w.freq(freq0))?;
+        self.0.write_register(config::FREQ1, |w| w.freq(freq1))?;
+        self.0.write_register(config::FREQ2, |w| w.freq(freq2))?;
+        self.0.write_register(config::MDMCFG0, |w| w.chanspc_m(chanspc_m))?;
+        self.0.modify_register(config::MDMCFG1, |r| r.chanspc_e(chanspc_e))?;
+        self.0.write_register(config::CHANNR, |w| w.chan(0))?;
+        Ok(())
+    }
+
+    /// Select a channel of the programmed channel plan, with a single register write.
+    ///
+    /// The radio should be in IDLE. Neither that nor `channel` is checked, see
+    /// [`Self::set_plan_channel`] for the checked version.
+    pub fn set_channel(&mut self, channel: u8) -> Result<(), Error<SpiE>> {
+        self.0.write_register(config::CHANNR, |w| w.chan(channel))?;
+        Ok(())
+    }
+
+    /// Select `channel` of `plan`, which must be the programmed channel plan.
+    ///
+    /// Fails with [`UserError::InvalidChannel`] if `channel` is outside of `plan`, with
+    /// [`ConfigError::Crystal`] if `plan` is for another crystal frequency, or with
+    /// [`UserError::NotIdle`] if the radio is not in IDLE.
+    pub fn set_plan_channel(&mut self, plan: &ChannelPlan, channel: u8) -> Result<(), Error<SpiE>> {
+        plan.check_crystal(self.0.fxosc)?;
+        plan.check_channel(channel).map_err(Error::UserInputError)?;
+        self.check_idle()?;
+        self.set_channel(channel)
+    }
+
+    fn check_idle(&mut self) -> Result<(), Error<SpiE>> {
+        match self.get_machine_state()? {
+            MachineState::IDLE => Ok(()),
+            state => Err(Error::UserInputError(UserError::NotIdle(state))),
+        }
+    }
+
+    /// Currently selected channel.
+    pub fn get_channel(&mut self) -> Result<u8, Error<SpiE>> {
+        Ok(self.0.read_register(config::CHANNR)?.chan())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChip, SimSpi};
+    use crate::RadioConfig;
+    use core::cell::RefCell;
+
+    #[test]
+    fn test_channel_plan() {
+        let plan = ChannelPlan::new(902_200_000, 200_000, 64).unwrap();
+        assert_eq!(plan.spacing_hz(), 199_951);
+        assert_eq!(plan.base_hz(), 902_199_920);
+        assert_eq!(plan.frequency(1), Some(902_399_871));
+        assert_eq!(plan.frequency(63), Some(914_796_844));
+        assert_eq!(plan.frequency(64), None);
+        assert_eq!(plan.check_channel(64), Err(UserError::InvalidChannel(64)));
+    }
+
+    #[test]
+    fn test_invalid_channel_plan() {
+        assert_eq!(
+            ChannelPlan::new(868_000_000, 25_000, 10),
+            Err(ConfigError::ChannelSpacing(25_000))
+        );
+        assert_eq!(
+            ChannelPlan::new(868_000_000, 406_000, 10),
+            Err(ConfigError::ChannelSpacing(406_000))
+        );
+        assert_eq!(ChannelPlan::new(868_000_000, 100_000, 0), Err(ConfigError::ChannelCount(0)));
+        assert_eq!(
+            ChannelPlan::new(868_000_000, 100_000, 257),
+            Err(ConfigError::ChannelCount(257))
+        );
+        assert_eq!(
+            ChannelPlan::new(350_000_000, 100_000, 10),
+            Err(ConfigError::Frequency(350_000_000))
+        );
+        // The top channel leaves the 779–928 MHz band.
+        assert_eq!(
+            ChannelPlan::new(927_000_000, 100_000, 20),
+            Err(ConfigError::Frequency(928_899_353))
+        );
+        assert!(ChannelPlan::new(927_000_000, 100_000, 10).is_ok());
+        assert_eq!(
+            ChannelPlan::with_crystal(868_000_000, 100_000, 10, 30_000_000),
+            Err(ConfigError::Crystal(30_000_000))
+        );
+    }
+
+    #[test]
+    fn test_set_channel() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        let plan = ChannelPlan::new(868_000_000, 100_000, 10).unwrap();
+        cc1101.set_channel_plan(&plan).unwrap();
+        cc1101.set_plan_channel(&plan, 7).unwrap();
+        assert_eq!(cc1101.get_channel().unwrap(), 7);
+        assert_eq!(
+            cc1101.set_plan_channel(&plan, 10),
+            Err(Error::UserInputError(UserError::InvalidChannel(10)))
+        );
+        let other = ChannelPlan::with_crystal(868_000_000, 100_000, 10, 27_000_000).unwrap();
+        assert_eq!(
+            cc1101.set_plan_channel(&other, 1),
+            Err(Error::InvalidConfig(ConfigError::Crystal(27_000_000)))
+        );
+        assert_eq!(chip.borrow().config(), RadioConfig::default().channel_plan(&plan).channel(7));
+    }
+}
@@ -0,0 +1,275 @@
+//! Duty-cycle accounting for regulated sub-bands.
+//!
+//! In the EU 863–870 MHz band, ERC Recommendation 70-03 and ETSI EN 300 220 limit the time
+//! on air of a transmitter to 0.1%, 1% or 10% per sub-band, over an observation period of one
+//! hour. The [`DutyCycleLimiter`] keeps the airtime used in each sub-band over a sliding hour,
+//! in one-minute buckets. A bucket only leaves the window once all of it is older than an
+//! hour, so the usage is never underestimated.
+
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+use crate::channel::carrier_hz;
+use crate::lowlevel::registers::*;
+use crate::{Airtime, Cc1101, Clock, Error};
+
+/// Number of buckets in the sliding window.
+const BUCKETS: usize = 60;
+
+/// Time covered by one bucket, in microseconds.
+const BUCKET_US: u64 = 60_000_000;
+
+/// Observation period, in microseconds.
+const WINDOW_US: u64 = BUCKETS as u64 * BUCKET_US;
+
+/// Buckets kept: the oldest one still holds airtime less than an hour old until the bucket
+/// after the current one starts.
+const SLOTS: usize = BUCKETS + 1;
+
+/// A frequency range with a duty-cycle limit.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SubBand {
+    /// Lower edge, in Hertz.
+    pub start_hz: u64,
+    /// Upper edge, in Hertz.
+    pub end_hz: u64,
+    /// Allowed time on air, in per mille of the observation period.
+    pub limit_permille: u16,
+}
+
+impl SubBand {
+    pub const fn new(start_hz: u64, end_hz: u64, limit_permille: u16) -> Self {
+        SubBand {
+            start_hz,
+            end_hz,
+            limit_permille,
+        }
+    }
+
+    /// Whether the carrier frequency `hz` falls within the sub-band.
+    pub fn contains(&self, hz: u64) -> bool {
+        (self.start_hz..=self.end_hz).contains(&hz)
+    }
+
+    /// Airtime allowed per observation period, in microseconds.
+    pub fn limit_us(&self) -> u64 {
+        WINDOW_US / 1000 * self.limit_permille as u64
+    }
+}
+
+/// Duty-cycle limited sub-bands for non-specific short range devices in the 863–870 MHz band,
+/// per ERC Recommendation 70-03 Annex 1.
+pub const ETSI_SUB_BANDS: [SubBand; 6] = [
+    SubBand::new(863_000_000, 865_000_000, 1),
+    SubBand::new(865_000_000, 868_000_000, 10),
+    SubBand::new(868_000_000, 868_600_000, 10),
+    SubBand::new(868_700_000, 869_200_000, 1),
+    SubBand::new(869_400_000, 869_650_000, 100),
+    SubBand::new(869_700_000, 870_000_000, 10),
+];
+
+/// Airtime used in one sub-band over the sliding window.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SubBandUsage {
+    band: SubBand,
+    /// Airtime per bucket in microseconds, `newest` being the current bucket.
+    buckets: [u32; SLOTS],
+    newest: usize,
+    /// Start of the current bucket.
+    newest_start_us: u64,
+}
+
+impl SubBandUsage {
+    pub const fn new(band: SubBand) -> Self {
+        SubBandUsage {
+            band,
+            buckets: [0; SLOTS],
+            newest: 0,
+            newest_start_us: 0,
+        }
+    }
+
+    pub fn band(&self) -> SubBand {
+        self.band
+    }
+
+    /// Airtime used within the window, in microseconds.
+    pub fn used_us(&self) -> u64 {
+        self.buckets.iter().map(|&us| us as u64).sum()
+    }
+
+    /// Move the window forward to `now_us`.
+    fn advance(&mut self, now_us: u64) {
+        let elapsed = now_us.saturating_sub(self.newest_start_us) / BUCKET_US;
+        for _ in 0..elapsed.min(SLOTS as u64) {
+            self.newest = (self.newest + 1) % SLOTS;
+            self.buckets[self.newest] = 0;
+        }
+        self.newest_start_us += elapsed * BUCKET_US;
+    }
+
+    /// Time from `now_us` until `airtime_us` fits within the limit, `u64::MAX` if it never
+    /// does. The window must have been advanced to `now_us`.
+    fn wait_us(&self, now_us: u64, airtime_us: u64) -> u64 {
+        let limit_us = self.band.limit_us();
+        if airtime_us > limit_us {
+            return u64::MAX;
+        }
+
+        let mut used_us = self.used_us();
+        // The oldest bucket leaves the window when the one after the current bucket starts.
+        for age in 0..SLOTS as u64 {
+            if used_us + airtime_us <= limit_us {
+                return (self.newest_start_us + age * BUCKET_US).saturating_sub(now_us);
+            }
+            used_us -= self.buckets[(self.newest + 1 + age as usize) % SLOTS] as u64;
+        }
+        self.newest_start_us + WINDOW_US + BUCKET_US - now_us
+    }
+
+    fn record(&mut self, airtime_us: u64) {
+        let bucket = &mut self.buckets[self.newest];
+        *bucket = bucket.saturating_add(airtime_us.min(u32::MAX as u64) as u32);
+    }
+}
+
+/// Duty-cycle limiter over a set of sub-bands, with the usage kept in a caller-provided table.
+pub struct DutyCycleLimiter<'a, C> {
+    usage: &'a mut [SubBandUsage],
+    clock: C,
+}
+
+impl<'a, C: Clock> DutyCycleLimiter<'a, C> {
+    /// Limiter over the sub-bands in `usage`, starting with no airtime used.
+    pub fn new(usage: &'a mut [SubBandUsage], mut clock: C) -> Self {
+        let now_us = clock.now_us();
+        for band in usage.iter_mut() {
+            *band = SubBandUsage::new(band.band);
+            band.newest_start_us = now_us;
+        }
+        DutyCycleLimiter {
+            usage,
+            clock,
+        }
+    }
+
+    /// Usage of the sub-band containing carrier frequency `hz`, if any.
+    pub fn usage(&mut self, hz: u64) -> Option<&SubBandUsage> {
+        let now_us = self.clock.now_us();
+        let band = self.usage.iter_mut().find(|usage| usage.band.contains(hz))?;
+        band.advance(now_us);
+        Some(band)
+    }
+
+    /// Time until `airtime_us` may be spent on carrier frequency `hz`, in microseconds: 0 if
+    /// allowed now, `u64::MAX` if it exceeds the limit on its own. Frequencies outside all
+    /// sub-bands are not limited.
+    pub fn wait_us(&mut self, hz: u64, airtime_us: u64) -> u64 {
+        let now_us = self.clock.now_us();
+        match self.usage.iter_mut().find(|usage| usage.band.contains(hz)) {
+            Some(band) => {
+                band.advance(now_us);
+                band.wait_us(now_us, airtime_us)
+            }
+            None => 0,
+        }
+    }
+
+    /// Account `airtime_us` spent on carrier frequency `hz` now.
+    pub fn record(&mut self, hz: u64, airtime_us: u64) {
+        let now_us = self.clock.now_us();
+        if let Some(band) = self.usage.iter_mut().find(|usage| usage.band.contains(hz)) {
+            band.advance(now_us);
+            band.record(airtime_us);
+        }
+    }
+
+    /// Reserve the airtime of a packet with `payload_len` bytes of payload, at the carrier
+    /// frequency and packet format currently configured in `radio`, and return it in
+    /// microseconds.
+    ///
+    /// Waits with `delay` if the airtime becomes available within `max_wait_us`, otherwise
+    /// fails with [`Error::DutyCycleExceeded`]. Transmit the packet right after.
+    pub fn acquire<SPI, SpiE, D: DelayNs>(
+        &mut self,
+        radio: &mut Cc1101<SPI>,
+        payload_len: usize,
+        delay: &mut D,
+        max_wait_us: u64,
+    ) -> Result<u64, Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+    {
+        let config = radio.read_config()?;
+        let hz = carrier_hz(
+            (
+                config.read(config::FREQ0).freq(),
+                config.read(config::FREQ1).freq(),
+                config.read(config::FREQ2).freq(),
+            ),
+            config.read(config::MDMCFG0).chanspc_m(),
+            config.read(config::MDMCFG1).chanspc_e(),
+            config.read(config::CHANNR).chan(),
+            config.crystal_hz(),
+        );
+        let airtime_us = Airtime::from_config(&config).time_on_air_us(payload_len);
+
+        let mut wait_us = self.wait_us(hz, airtime_us);
+        if wait_us > max_wait_us {
+            return Err(Error::DutyCycleExceeded {
+                wait_us,
+            });
+        }
+        while wait_us > 0 {
+            let step_us = wait_us.min(u32::MAX as u64);
+            delay.delay_us(step_us as u32);
+            wait_us -= step_us;
+        }
+        self.record(hz, airtime_us);
+        Ok(airtime_us)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChip, SimDelay, SimSpi};
+    use core::cell::RefCell;
+
+    #[test]
+    fn test_limiter() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+        let mut delay = SimDelay::new(&chip);
+
+        let mut usage = ETSI_SUB_BANDS.map(SubBandUsage::new);
+        let mut limiter = DutyCycleLimiter::new(&mut usage, SimDelay::new(&chip));
+
+        // 0.1%, 3.6 s per hour.
+        assert_eq!(limiter.wait_us(868_800_000, 3_600_001), u64::MAX);
+        limiter.record(868_800_000, 2_000_000);
+        delay.delay_ms(30 * 60_000);
+        assert_eq!(limiter.wait_us(868_800_000, 1_600_000), 0);
+        limiter.record(868_800_000, 1_000_000);
+        // The first transmission leaves the window once it is an hour old, rounded up to the
+        // next bucket.
+        let wait_us = limiter.wait_us(868_800_000, 1_000_000);
+        assert!((30 * 60_000_000..=31 * 60_000_000).contains(&wait_us), "{wait_us}");
+        assert_eq!(limiter.usage(868_800_000).unwrap().used_us(), 3_000_000);
+        assert_eq!(limiter.wait_us(433_920_000, 1_000_000), 0);
+
+        // 1% in 868.0–868.6 MHz, 36 s per hour.
+        cc1101.set_frequency(868_300_000).unwrap();
+        limiter.record(868_300_000, 35_999_000);
+        let Err(Error::DutyCycleExceeded {
+            wait_us,
+        }) = limiter.acquire(&mut cc1101, 10, &mut delay, 0)
+        else {
+            panic!("expected the duty cycle to be exceeded");
+        };
+        assert!(wait_us > 59 * 60_000_000);
+        let start = chip.borrow().now_us();
+        assert_eq!(limiter.acquire(&mut cc1101, 10, &mut delay, wait_us), Ok(1322));
+        assert!(chip.borrow().now_us() - start >= wait_us);
+    }
+}
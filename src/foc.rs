@@ -0,0 +1,229 @@
+//! Frequency offset compensation.
+//!
+//! Within a packet, the demodulator tracks the carrier frequency of the received signal with
+//! the loop configured in `FOCCFG`, see section 14.1 "Frequency Offset Compensation" of the data
+//! sheet (TI document SWRS061I). The offset it settled on is left in `FREQEST`. Adding that
+//! estimate to `FSCTRL0` after each good packet moves the synthesizer onto the carrier of the
+//! other side, which corrects for the ppm error of both crystals in TX as well as in RX.
+//!
+//! A single estimate is noisy, so [`FrequencyOffsetTracker`] averages them, and can be saved
+//! and restored so that a learned offset survives a reboot.
+
+use hal::spi::SpiDevice;
+
+use crate::lowlevel::{convert::*, registers::*};
+use crate::{check_frequency_offset, Cc1101, Error, FocLimit, FocPostK, FocPreK};
+
+/// Fractional bits of the offset accumulated by [`FrequencyOffsetTracker`].
+const FRACTION_BITS: u32 = 8;
+
+/// Frequency offset compensation loop, `FOCCFG`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FocConfig {
+    /// Freeze the compensation and clock recovery loops until carrier sense goes high.
+    pub cs_gate: bool,
+    /// Loop gain before a sync word is detected.
+    pub pre_k: FocPreK,
+    /// Loop gain after a sync word is detected.
+    pub post_k: FocPostK,
+    /// Largest offset the loop compensates for.
+    pub limit: FocLimit,
+}
+
+impl Default for FocConfig {
+    /// Reset value of `FOCCFG`.
+    fn default() -> Self {
+        FocConfig {
+            cs_gate: true,
+            pre_k: FocPreK::K3,
+            post_k: FocPostK::HalfK,
+            limit: FocLimit::ChanBwDiv4,
+        }
+    }
+}
+
+/// Frequency offset learned from `FREQEST` readings, in `FSCTRL0` steps of f_XOSC/2^14.
+///
+/// Each reading moves the learned offset `1/2^smoothing` of the way towards the offset it
+/// indicates, so a smoothing of 0 follows the last reading exactly.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FrequencyOffsetTracker {
+    /// Learned offset, with [`FRACTION_BITS`] fractional bits.
+    accumulated: i32,
+    smoothing: u8,
+}
+
+impl FrequencyOffsetTracker {
+    /// No learned offset.
+    pub const fn new(smoothing: u8) -> Self {
+        FrequencyOffsetTracker {
+            accumulated: 0,
+            smoothing,
+        }
+    }
+
+    /// Continue from an offset saved with [`Self::export`].
+    pub const fn import(self, exported: i16) -> Self {
+        FrequencyOffsetTracker {
+            accumulated: exported as i32,
+            ..self
+        }
+    }
+
+    /// Learned offset including its fractional part, to be saved for [`Self::import`].
+    pub const fn export(&self) -> i16 {
+        self.accumulated as i16
+    }
+
+    /// Learned offset, as written to `FSCTRL0`.
+    pub const fn offset(&self) -> i8 {
+        let rounded = (self.accumulated + (1 << (FRACTION_BITS - 1))) >> FRACTION_BITS;
+        rounded as i8
+    }
+
+    /// Account for a `FREQEST` reading taken with [`Self::offset`] in `FSCTRL0`, returning
+    /// the new offset.
+    pub fn update(&mut self, freqest: i8) -> i8 {
+        let target = (self.offset() as i32 + freqest as i32) << FRACTION_BITS;
+        let step = (target - self.accumulated) / (1 << self.smoothing.min(15));
+        self.accumulated = (self.accumulated + step)
+            .clamp((i8::MIN as i32) << FRACTION_BITS, (i8::MAX as i32) << FRACTION_BITS);
+        self.offset()
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Configure the frequency offset compensation loop.
+    pub fn set_foc_config(&mut self, foc: &FocConfig) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::FOCCFG, |r| {
+            r.foc_bs_cs_gate(foc.cs_gate as u8)
+                .foc_pre_k(foc.pre_k.into())
+                .foc_post_k(foc.post_k.into())
+                .foc_limit(foc.limit.into())
+        })?;
+        Ok(())
+    }
+
+    /// Sets the frequency offset added to the carrier frequency (in Hertz), returning the
+    /// achieved offset.
+    ///
+    /// Fails with [`crate::ConfigError::FrequencyOffset`] outside of -203.125–201.538 kHz
+    /// (with a 26 MHz crystal).
+    pub fn set_frequency_offset(&mut self, hz: i32) -> Result<i32, Error<SpiE>> {
+        check_frequency_offset(hz, self.0.fxosc)?;
+        let freqoff = from_frequency_offset(hz, self.0.fxosc);
+        self.0.write_register(config::FSCTRL0, |w| w.freqoff(freqoff))?;
+        Ok(to_frequency_offset(freqoff, self.0.fxosc))
+    }
+
+    /// Frequency offset added to the carrier frequency (in Hertz).
+    pub fn get_frequency_offset(&mut self) -> Result<i32, Error<SpiE>> {
+        let freqoff = self.0.read_register(config::FSCTRL0)?.freqoff();
+        Ok(to_frequency_offset(freqoff, self.0.fxosc))
+    }
+
+    /// Feed the offset estimate of the last received packet to `tracker` and write the new
+    /// learned offset to `FSCTRL0`, returning it in Hertz.
+    ///
+    /// Call in IDLE, after a packet with a good CRC; the estimate of a corrupted packet or of
+    /// noise says nothing about the other side's carrier.
+    pub fn compensate_frequency_offset(
+        &mut self,
+        tracker: &mut FrequencyOffsetTracker,
+    ) -> Result<i32, Error<SpiE>> {
+        let freqest = self.0.read_register(status::FREQEST)?.freqoff_est();
+        tracker.update(freqest as i8);
+        self.apply_frequency_offset(tracker)
+    }
+
+    /// Write the offset learned by `tracker` to `FSCTRL0`, e.g. after a reboot, returning it in
+    /// Hertz.
+    pub fn apply_frequency_offset(
+        &mut self,
+        tracker: &FrequencyOffsetTracker,
+    ) -> Result<i32, Error<SpiE>> {
+        let freqoff = tracker.offset() as u8;
+        self.0.write_register(config::FSCTRL0, |w| w.freqoff(freqoff))?;
+        Ok(to_frequency_offset(freqoff, self.0.fxosc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChip, SimSpi};
+    use crate::{ConfigError, RadioConfig};
+    use core::cell::RefCell;
+
+    #[test]
+    fn test_tracker() {
+        let mut tracker = FrequencyOffsetTracker::new(0);
+        assert_eq!(tracker.update(5), 5);
+        assert_eq!(tracker.update(-2), 3);
+        assert_eq!(tracker.update(-128), -125);
+        assert_eq!(tracker.update(-128), -128);
+
+        let mut tracker = FrequencyOffsetTracker::new(2);
+        assert_eq!(tracker.update(8), 2);
+        assert_eq!(tracker.update(6), 4);
+        assert_eq!(tracker.export(), 0x380);
+
+        let restored = FrequencyOffsetTracker::new(2).import(tracker.export());
+        assert_eq!(restored, tracker);
+        assert_eq!(FrequencyOffsetTracker::new(2).import(-0x180).offset(), -1);
+    }
+
+    #[test]
+    fn test_compensation() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        // 20 ppm at 433.92 MHz is about 5.5 steps of 1587 Hz.
+        chip.borrow_mut().set_frequency_error(8_678);
+        assert_eq!(cc1101.get_est_freq_offset().unwrap(), 7_934);
+
+        let mut tracker = FrequencyOffsetTracker::new(1);
+        for _ in 0..8 {
+            cc1101.compensate_frequency_offset(&mut tracker).unwrap();
+        }
+        assert_eq!(tracker.offset(), 5);
+        assert_eq!(cc1101.get_frequency_offset().unwrap(), 7_934);
+        assert_eq!(cc1101.get_est_freq_offset().unwrap(), 0);
+
+        let exported = tracker.export();
+        cc1101.reset_chip().unwrap();
+        assert_eq!(cc1101.get_frequency_offset().unwrap(), 0);
+        let tracker = FrequencyOffsetTracker::new(1).import(exported);
+        assert_eq!(cc1101.apply_frequency_offset(&tracker).unwrap(), 7_934);
+        assert_eq!(chip.borrow().config().read(config::FSCTRL0).freqoff(), 5);
+    }
+
+    #[test]
+    fn test_settings() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        let foc = FocConfig {
+            cs_gate: false,
+            pre_k: FocPreK::K2,
+            post_k: FocPostK::SameAsPreK,
+            limit: FocLimit::ChanBwDiv8,
+        };
+        cc1101.set_foc_config(&foc).unwrap();
+        assert_eq!(cc1101.set_frequency_offset(-20_000).unwrap(), -19_042);
+        assert_eq!(
+            cc1101.set_frequency_offset(250_000),
+            Err(Error::InvalidConfig(ConfigError::FrequencyOffset(250_000)))
+        );
+
+        let expected = RadioConfig::default().foc_config(&foc).frequency_offset(-20_000);
+        assert_eq!(chip.borrow().config(), expected);
+        assert_eq!(
+            RadioConfig::default().foc_config(&FocConfig::default()),
+            RadioConfig::default()
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! Analog front end currents and receiver profiles.
+//!
+//! `FREND1` trims the currents of the LNA, mixer and LO buffer in RX, and `FREND0` the LO
+//! buffer in TX. Together with `TEST2`/`TEST1` and the AGC they decide the balance between
+//! sensitivity, selectivity and current draw. The data sheet (TI document SWRS061I) only gives
+//! these as SmartRF Studio values, which [`ReceiverProfile`] collects so that they need not be
+//! copied for each data rate.
+
+use hal::spi::SpiDevice;
+
+use crate::lowlevel::registers::*;
+use crate::{AgcProfile, Cc1101, Error, RxAttenuation};
+
+/// `TEST2`/`TEST1` values for improved sensitivity, with an RX filter bandwidth below 325 kHz.
+const TEST_SENSITIVITY: (u8, u8) = (0x81, 0x35);
+
+/// Reset values of `TEST2`/`TEST1`, needed with an RX filter bandwidth of 325 kHz or more.
+const TEST_DEFAULT: (u8, u8) = (0x88, 0x31);
+
+/// Trim of a front end current, one of the 2-bit fields of `FREND1` and `FREND0`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum FrontEndCurrent {
+    /// Lowest current.
+    Level0 = 0,
+    /// Second lowest current.
+    Level1 = 1,
+    /// Second highest current.
+    Level2 = 2,
+    /// Highest current.
+    Level3 = 3,
+}
+
+impl From<FrontEndCurrent> for u8 {
+    fn from(value: FrontEndCurrent) -> Self {
+        value as Self
+    }
+}
+
+/// Current trims of the RX front end, `FREND1`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FrontEndConfig {
+    /// `LNA_CURRENT`, the LNA PTAT current.
+    pub lna_current: FrontEndCurrent,
+    /// `LNA2MIX_CURRENT`, the front-end PTAT outputs.
+    pub lna2mix_current: FrontEndCurrent,
+    /// `LODIV_BUF_CURRENT_RX`, the RX LO buffer (LO input to the mixer).
+    pub lodiv_buf_current: FrontEndCurrent,
+    /// `MIX_CURRENT`, the mixer.
+    pub mix_current: FrontEndCurrent,
+}
+
+impl Default for FrontEndConfig {
+    /// Reset value of `FREND1`, as recommended up to 100 kBaud.
+    fn default() -> Self {
+        FrontEndConfig {
+            lna_current: FrontEndCurrent::Level1,
+            lna2mix_current: FrontEndCurrent::Level1,
+            lodiv_buf_current: FrontEndCurrent::Level1,
+            mix_current: FrontEndCurrent::Level2,
+        }
+    }
+}
+
+impl FrontEndConfig {
+    /// SmartRF Studio value of `FREND1` for 250 kBaud and above.
+    pub const fn high_data_rate() -> Self {
+        FrontEndConfig {
+            lna_current: FrontEndCurrent::Level2,
+            lna2mix_current: FrontEndCurrent::Level3,
+            lodiv_buf_current: FrontEndCurrent::Level1,
+            mix_current: FrontEndCurrent::Level2,
+        }
+    }
+}
+
+/// Combined receiver settings of `FREND1`, `TEST2`, `TEST1`, `FIFOTHR` and the AGC.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ReceiverProfile {
+    /// `FREND1`.
+    pub front_end: FrontEndConfig,
+    /// `TEST2`.
+    pub test2: u8,
+    /// `TEST1`.
+    pub test1: u8,
+    /// `FIFOTHR.ADC_RETENTION`, which keeps the `TEST2`/`TEST1` sensitivity values through
+    /// SLEEP. They read back as their reset values after SLEEP either way.
+    pub adc_retention: bool,
+    /// `FIFOTHR.CLOSE_IN_RX`.
+    pub rx_attenuation: RxAttenuation,
+    /// `AGCCTRL2`, `AGCCTRL1` and `AGCCTRL0`.
+    pub agc: AgcProfile,
+}
+
+impl ReceiverProfile {
+    /// Best sensitivity for 2-FSK/GFSK with an RX filter bandwidth below 325 kHz, at a higher
+    /// RX current than [`Self::low_current`].
+    pub const fn sensitivity() -> Self {
+        ReceiverProfile {
+            front_end: FrontEndConfig {
+                lna_current: FrontEndCurrent::Level1,
+                lna2mix_current: FrontEndCurrent::Level1,
+                lodiv_buf_current: FrontEndCurrent::Level1,
+                mix_current: FrontEndCurrent::Level2,
+            },
+            test2: TEST_SENSITIVITY.0,
+            test1: TEST_SENSITIVITY.1,
+            adc_retention: true,
+            rx_attenuation: RxAttenuation::Db0,
+            agc: AgcProfile::narrowband_fsk(),
+        }
+    }
+
+    /// Lower RX current for 2-FSK/GFSK at low data rates, with the reset `TEST2`/`TEST1`
+    /// values, at the cost of some sensitivity.
+    pub const fn low_current() -> Self {
+        ReceiverProfile {
+            test2: TEST_DEFAULT.0,
+            test1: TEST_DEFAULT.1,
+            adc_retention: false,
+            ..Self::sensitivity()
+        }
+    }
+
+    /// GFSK/MSK at 250 kBaud and above, with an RX filter bandwidth of 325 kHz or more.
+    pub const fn high_data_rate() -> Self {
+        ReceiverProfile {
+            front_end: FrontEndConfig::high_data_rate(),
+            test2: TEST_DEFAULT.0,
+            test1: TEST_DEFAULT.1,
+            adc_retention: false,
+            rx_attenuation: RxAttenuation::Db0,
+            agc: AgcProfile::high_data_rate_fsk(),
+        }
+    }
+
+    /// OOK/ASK with an RX filter bandwidth below 325 kHz (DN022).
+    pub const fn ook() -> Self {
+        ReceiverProfile {
+            agc: AgcProfile::ook(),
+            ..Self::sensitivity()
+        }
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Configure the currents of the RX front end.
+    pub fn set_front_end_config(&mut self, front_end: &FrontEndConfig) -> Result<(), Error<SpiE>> {
+        self.0.write_register(config::FREND1, |w| {
+            w.lna_current(front_end.lna_current.into())
+                .lna2mix_current(front_end.lna2mix_current.into())
+                .lodiv_buf_current_rx(front_end.lodiv_buf_current.into())
+                .mix_current(front_end.mix_current.into())
+        })?;
+        Ok(())
+    }
+
+    /// Sets the current of the TX LO buffer (input to the PA), `FREND0.LODIV_BUF_CURRENT_TX`.
+    pub fn set_tx_lo_buffer_current(
+        &mut self,
+        current: FrontEndCurrent,
+    ) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::FREND0, |r| r.lodiv_buf_current_tx(current.into()))?;
+        Ok(())
+    }
+
+    /// Sets the RX attenuation, for links where the other side can be very close.
+    pub fn set_rx_attenuation(&mut self, attenuation: RxAttenuation) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::FIFOTHR, |r| r.close_in_rx(attenuation.into()))?;
+        Ok(())
+    }
+
+    /// Apply all settings of a receiver profile.
+    pub fn set_receiver_profile(&mut self, profile: &ReceiverProfile) -> Result<(), Error<SpiE>> {
+        self.set_front_end_config(&profile.front_end)?;
+        self.0.write_register(config::TEST2, |w| w.test2(profile.test2))?;
+        self.0.write_register(config::TEST1, |w| w.test1(profile.test1))?;
+        self.0.modify_register(config::FIFOTHR, |r| {
+            r.adc_retention(profile.adc_retention as u8).close_in_rx(profile.rx_attenuation.into())
+        })?;
+        self.set_agc_profile(&profile.agc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChip, SimSpi};
+    use crate::RadioConfig;
+    use core::cell::RefCell;
+
+    fn registers(config: &RadioConfig) -> [u8; 4] {
+        let bytes = config.as_bytes();
+        [bytes[0x21], bytes[0x2C], bytes[0x2D], bytes[0x03]]
+    }
+
+    #[test]
+    fn test_presets() {
+        let reset = RadioConfig::default();
+        assert_eq!(reset.front_end(&FrontEndConfig::default()), reset);
+        assert_eq!(
+            registers(&reset.receiver_profile(&ReceiverProfile::sensitivity())),
+            [0x56, 0x81, 0x35, 0x47]
+        );
+        assert_eq!(
+            registers(&reset.receiver_profile(&ReceiverProfile::low_current())),
+            [0x56, 0x88, 0x31, 0x07]
+        );
+        assert_eq!(
+            registers(&reset.receiver_profile(&ReceiverProfile::high_data_rate())),
+            [0xB6, 0x88, 0x31, 0x07]
+        );
+    }
+
+    #[test]
+    fn test_settings() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        let profile = ReceiverProfile {
+            rx_attenuation: RxAttenuation::Db12,
+            ..ReceiverProfile::ook()
+        };
+        cc1101.set_receiver_profile(&profile).unwrap();
+        cc1101.set_tx_lo_buffer_current(FrontEndCurrent::Level3).unwrap();
+
+        let expected = RadioConfig::default()
+            .receiver_profile(&profile)
+            .tx_lo_buffer_current(FrontEndCurrent::Level3);
+        assert_eq!(chip.borrow().config(), expected);
+        assert_eq!(registers(&expected), [0x56, 0x81, 0x35, 0x67]);
+        assert_eq!(expected.as_bytes()[0x22], 0x30);
+
+        cc1101.set_rx_attenuation(RxAttenuation::Db0).unwrap();
+        cc1101.set_front_end_config(&FrontEndConfig::high_data_rate()).unwrap();
+        let expected = expected
+            .rx_attenuation(RxAttenuation::Db0)
+            .front_end(&FrontEndConfig::high_data_rate());
+        assert_eq!(chip.borrow().config(), expected);
+
+        let front_end = FrontEndConfig {
+            mix_current: FrontEndCurrent::Level3,
+            ..FrontEndConfig::default()
+        };
+        cc1101.set_front_end_config(&front_end).unwrap();
+        assert_eq!(chip.borrow().config(), expected.front_end(&front_end));
+        assert_eq!(chip.borrow().config().as_bytes()[0x21], 0x57);
+    }
+}
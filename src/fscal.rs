@@ -0,0 +1,212 @@
+//! Frequency synthesizer calibration cache for fast frequency hopping.
+//!
+//! Calibrating the frequency synthesizer takes about 720 µs, see section 28.2 "Frequency
+//! Hopping and Multi-Channel Systems" of the data sheet (TI document SWRS061I). Instead of
+//! calibrating on every hop, each channel can be calibrated once and its `FSCAL3`, `FSCAL2` and
+//! `FSCAL1` values written back when hopping to it, with automatic calibration disabled.
+//!
+//! The calibration drifts with temperature, so each result remembers the temperature it was
+//! taken at and is redone once the temperature has moved too far. Measuring the temperature is
+//! left to the caller, e.g. with the analog sensor output on GDO0, see
+//! [`Cc1101::temperature_sensor_enable`].
+
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+use crate::lowlevel::registers::*;
+use crate::{AutoCalibration, Cc1101, Error, PollBudget, RadioMode, UserError};
+
+/// Frequency synthesizer calibration result of one channel.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FscalEntry {
+    /// `FSCAL3`, `FSCAL2` and `FSCAL1`.
+    pub fscal: [u8; 3],
+    /// Temperature at calibration, in degrees Celsius.
+    pub temperature_c: i8,
+}
+
+/// Calibration results of a set of channels, stored in a caller-provided table indexed by
+/// channel number.
+#[derive(Debug)]
+pub struct FscalCache<'a> {
+    entries: &'a mut [Option<FscalEntry>],
+    max_drift_c: u8,
+}
+
+impl<'a> FscalCache<'a> {
+    /// Cache for channels `0..entries.len()`, recalibrating a channel once the temperature
+    /// is more than `max_drift_c` degrees away from its calibration.
+    ///
+    /// `entries` is used as it is, so that results kept across a reset can be reused. Start
+    /// from `[None; N]`, or call [`Self::clear`], for an empty cache.
+    pub fn new(entries: &'a mut [Option<FscalEntry>], max_drift_c: u8) -> Self {
+        FscalCache {
+            entries,
+            max_drift_c,
+        }
+    }
+
+    /// Calibration result of `channel`, if any.
+    pub fn get(&self, channel: u8) -> Option<FscalEntry> {
+        self.entries.get(channel as usize).copied().flatten()
+    }
+
+    /// Whether `channel` needs to be calibrated at `temperature_c`.
+    pub fn is_stale(&self, channel: u8, temperature_c: i8) -> bool {
+        match self.get(channel) {
+            Some(entry) => entry.temperature_c.abs_diff(temperature_c) > self.max_drift_c,
+            None => true,
+        }
+    }
+
+    /// Forget all calibration results, e.g. after changing the base frequency.
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+
+    pub(crate) fn slot(&mut self, channel: u8) -> Result<&mut Option<FscalEntry>, UserError> {
+        self.entries.get_mut(channel as usize).ok_or(UserError::InvalidChannel(channel))
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Calibrate each of `channels` at `temperature_c`, storing the results in `cache`.
+    ///
+    /// Disables automatic calibration, as it would overwrite the restored values, and leaves
+    /// the radio in IDLE on the last channel. Each calibration is awaited within `budget`.
+    pub fn calibrate_channels<D: DelayNs>(
+        &mut self,
+        cache: &mut FscalCache,
+        channels: &[u8],
+        temperature_c: i8,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<(), Error<SpiE>> {
+        self.set_autocalibration(AutoCalibration::Disabled)?;
+        for &channel in channels {
+            self.calibrate_channel(cache, channel, temperature_c, delay, budget)?;
+        }
+        Ok(())
+    }
+
+    /// Switch to `channel` in IDLE, restoring its calibration from `cache`.
+    ///
+    /// The channel is calibrated first if it has no result in `cache` yet, or if its result
+    /// is stale at `temperature_c`. Returns whether it was. The radio is left in IDLE, ready
+    /// to be started in RX or TX without calibrating; automatic calibration must be disabled,
+    /// see [`Self::calibrate_channels`].
+    pub fn hop_to_channel<D: DelayNs>(
+        &mut self,
+        cache: &mut FscalCache,
+        channel: u8,
+        temperature_c: i8,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<bool, Error<SpiE>> {
+        self.set_radio_mode_within(RadioMode::Idle, delay, budget)?;
+
+        match cache.get(channel) {
+            Some(entry) if !cache.is_stale(channel, temperature_c) => {
+                self.0.write_config_burst(config::FSCAL3, &entry.fscal)?;
+                self.set_channel(channel)?;
+                Ok(false)
+            }
+            _ => {
+                self.calibrate_channel(cache, channel, temperature_c, delay, budget)?;
+                Ok(true)
+            }
+        }
+    }
+
+    fn calibrate_channel<D: DelayNs>(
+        &mut self,
+        cache: &mut FscalCache,
+        channel: u8,
+        temperature_c: i8,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<(), Error<SpiE>> {
+        let slot = cache.slot(channel).map_err(Error::UserInputError)?;
+
+        self.set_channel(channel)?;
+        self.set_radio_mode_within(RadioMode::Calibrate, delay, budget)?;
+
+        let mut fscal = [0; 3];
+        self.0.read_config_burst(config::FSCAL3, &mut fscal)?;
+        *slot = Some(FscalEntry {
+            fscal,
+            temperature_c,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChip, SimDelay, SimSpi, CALIBRATION_US};
+    use crate::ChannelPlan;
+    use core::cell::RefCell;
+
+    const BUDGET: PollBudget = PollBudget::new(100, 20);
+
+    #[test]
+    fn test_cache() {
+        let mut entries = [None; 4];
+        let mut cache = FscalCache::new(&mut entries, 10);
+        assert!(cache.is_stale(0, 20));
+
+        *cache.slot(1).unwrap() = Some(FscalEntry {
+            fscal: [0xA9, 0x2A, 0x11],
+            temperature_c: 20,
+        });
+        assert!(!cache.is_stale(1, 30));
+        assert!(cache.is_stale(1, 31));
+        assert!(cache.is_stale(1, 9));
+        assert_eq!(cache.slot(4), Err(UserError::InvalidChannel(4)));
+        assert!(cache.is_stale(4, 20));
+
+        // Results kept across a reset are reused.
+        let mut cache = FscalCache::new(&mut entries, 10);
+        assert!(!cache.is_stale(1, 20));
+        cache.clear();
+        assert!(cache.is_stale(1, 20));
+    }
+
+    #[test]
+    fn test_hop_to_channel() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+        let mut delay = SimDelay::new(&chip);
+
+        cc1101.set_autocalibration(AutoCalibration::FromIdle).unwrap();
+        cc1101.set_channel_plan(&ChannelPlan::new(902_200_000, 200_000, 64).unwrap()).unwrap();
+
+        let mut entries = [None; 64];
+        let mut cache = FscalCache::new(&mut entries, 10);
+        cc1101.calibrate_channels(&mut cache, &[0, 10, 20], 25, &mut delay, BUDGET).unwrap();
+        assert_eq!(chip.borrow().config().read(config::MCSM0).fs_autocal(), 0);
+        assert_ne!(cache.get(0).unwrap().fscal, cache.get(20).unwrap().fscal);
+
+        // Restoring a cached channel takes no calibration.
+        let start = chip.borrow().now_us();
+        assert!(!cc1101.hop_to_channel(&mut cache, 0, 30, &mut delay, BUDGET).unwrap());
+        assert!(chip.borrow().now_us() - start < CALIBRATION_US as u64);
+        cc1101.enable_rx().unwrap();
+        let config = chip.borrow().config();
+        assert_eq!(config.read(config::CHANNR).chan(), 0);
+        assert_eq!(config.as_bytes()[0x23..0x26], cache.get(0).unwrap().fscal);
+
+        // Unknown and stale channels are calibrated.
+        assert!(cc1101.hop_to_channel(&mut cache, 30, 25, &mut delay, BUDGET).unwrap());
+        assert!(cc1101.hop_to_channel(&mut cache, 10, 40, &mut delay, BUDGET).unwrap());
+        assert_eq!(cache.get(10).unwrap().temperature_c, 40);
+        assert_eq!(
+            cc1101.hop_to_channel(&mut cache, 64, 25, &mut delay, BUDGET),
+            Err(Error::UserInputError(UserError::InvalidChannel(64)))
+        );
+    }
+}
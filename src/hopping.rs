@@ -0,0 +1,379 @@
+//! Frequency hopping scheduler.
+//!
+//! Hops through a list of channels in a pseudo-random order, spending a fixed dwell time on
+//! each. Time is divided into hop slots counted from a shared epoch, and the channel of a slot
+//! only depends on the slot number and a seed, so a transmitter and a receiver with the same
+//! channel list and seed hop in step once they agree on the epoch.
+//!
+//! Each cycle through the list visits every channel exactly once, which gives the equal
+//! average channel use required by FCC 15.247(a)(1). Time spent on each channel is tracked
+//! over an observation period, for checking the average occupancy limit, e.g. 0.4 s within
+//! 20 s in the 902–928 MHz band with a 20 dB bandwidth below 250 kHz.
+
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+use crate::{Cc1101, Error, PollBudget, RadioMode, UserError};
+
+/// Source of monotonic time for the [`FrequencyHopper`].
+pub trait Clock {
+    /// Current time, in microseconds.
+    fn now_us(&mut self) -> u64;
+}
+
+/// A channel to hop to.
+///
+/// A hop list is either all channel numbers or all frequencies: a frequency overwrites the
+/// base frequency of the channel plan.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum HopChannel {
+    /// Channel number of the programmed channel plan (`CHANNR`), see [`crate::ChannelPlan`].
+    Channel(u8),
+    /// Carrier frequency in Hertz (`FREQ2/1/0`), for plans that are not evenly spaced.
+    Frequency(u64),
+}
+
+/// Pseudo-random order of `len` channels, as a function of the hop number.
+///
+/// Each cycle of `len` hops is an affine permutation `(i * stride + offset) % len` of the
+/// channel indices, with `stride` and `offset` drawn from the seed and the cycle number.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HopSequence {
+    len: usize,
+    seed: u32,
+}
+
+impl HopSequence {
+    pub const fn new(len: usize, seed: u32) -> Self {
+        HopSequence {
+            len,
+            seed,
+        }
+    }
+
+    /// Index into the channel list of hop number `hop`.
+    pub fn index(&self, hop: u64) -> usize {
+        let len = self.len as u64;
+        if len <= 1 {
+            return 0;
+        }
+
+        let r = splitmix64(((self.seed as u64) << 32) ^ (hop / len));
+        let offset = r % len;
+        let mut stride = 1 + (r >> 32) % (len - 1);
+        while gcd(stride, len) != 1 {
+            stride = stride % (len - 1) + 1;
+        }
+        ((hop % len * stride + offset) % len) as usize
+    }
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Hopping timing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HoppingConfig {
+    /// Time spent on each channel, in microseconds.
+    pub dwell_us: u32,
+    /// Seed of the hop sequence, shared by all nodes of the network.
+    pub seed: u32,
+    /// Period over which channel occupancy is tracked, in microseconds.
+    pub period_us: u64,
+    /// Maximum time on one channel within `period_us`, in microseconds.
+    pub max_occupancy_us: u64,
+}
+
+impl HoppingConfig {
+    /// Hop every `dwell_us`, with the FCC 15.247(a)(1)(i) limit of 0.4 s occupancy within
+    /// 20 s.
+    pub const fn new(dwell_us: u32, seed: u32) -> Self {
+        HoppingConfig {
+            dwell_us,
+            seed,
+            period_us: 20_000_000,
+            max_occupancy_us: 400_000,
+        }
+    }
+
+    /// Track occupancy over `period_us`, allowing at most `max_occupancy_us` per channel.
+    pub const fn occupancy_limit(self, max_occupancy_us: u64, period_us: u64) -> Self {
+        HoppingConfig {
+            period_us,
+            max_occupancy_us,
+            ..self
+        }
+    }
+}
+
+/// Frequency hopping scheduler driving a [`Cc1101`].
+///
+/// The channel list and the per-channel occupancy table are provided by the caller.
+pub struct FrequencyHopper<'a, C> {
+    channels: &'a [HopChannel],
+    occupancy_us: &'a mut [u64],
+    sequence: HopSequence,
+    config: HoppingConfig,
+    clock: C,
+    epoch_us: u64,
+    /// Current hop number, and when the radio was tuned to it.
+    current: Option<(u64, u64)>,
+    period_start_us: u64,
+    limit_exceeded: bool,
+}
+
+impl<'a, C: Clock> FrequencyHopper<'a, C> {
+    /// Scheduler hopping over `channels`, with hop 0 starting now.
+    ///
+    /// `occupancy_us` needs an entry per channel. Fails with [`UserError::MixedHopChannels`] if
+    /// `channels` mixes [`HopChannel::Channel`] and [`HopChannel::Frequency`].
+    pub fn new(
+        channels: &'a [HopChannel],
+        occupancy_us: &'a mut [u64],
+        config: HoppingConfig,
+        mut clock: C,
+    ) -> Result<Self, UserError> {
+        if channels.is_empty() {
+            return Err(UserError::BufferTooSmall(1));
+        }
+        if occupancy_us.len() < channels.len() {
+            return Err(UserError::BufferTooSmall(channels.len()));
+        }
+        let by_channel = |c: &HopChannel| matches!(c, HopChannel::Channel(_));
+        if channels.iter().any(by_channel) && !channels.iter().all(by_channel) {
+            return Err(UserError::MixedHopChannels);
+        }
+        let config = HoppingConfig {
+            dwell_us: config.dwell_us.max(1),
+            ..config
+        };
+
+        let now = clock.now_us();
+        occupancy_us.fill(0);
+        Ok(FrequencyHopper {
+            channels,
+            occupancy_us,
+            sequence: HopSequence::new(channels.len(), config.seed),
+            config,
+            clock,
+            epoch_us: now,
+            current: None,
+            period_start_us: now,
+            limit_exceeded: false,
+        })
+    }
+
+    /// Align the hop slots so that hop number `hop` started at `started_at_us`.
+    ///
+    /// A receiver calls this with the hop number carried in a packet from the transmitter
+    /// and the time the packet was received, less its time on air and the transmitter's
+    /// offset into the slot.
+    pub fn synchronize(&mut self, hop: u64, started_at_us: u64) {
+        self.epoch_us = started_at_us.wrapping_sub(hop * self.config.dwell_us as u64);
+    }
+
+    /// Hop number of the slot at `now_us`.
+    pub fn hop_at(&self, now_us: u64) -> u64 {
+        now_us.wrapping_sub(self.epoch_us) / self.config.dwell_us as u64
+    }
+
+    /// Channel of hop number `hop`.
+    pub fn channel(&self, hop: u64) -> HopChannel {
+        self.channels[self.sequence.index(hop)]
+    }
+
+    /// Time left until the next hop, in microseconds.
+    pub fn time_to_next_hop_us(&mut self) -> u64 {
+        let dwell_us = self.config.dwell_us as u64;
+        let elapsed = self.clock.now_us().wrapping_sub(self.epoch_us);
+        dwell_us - elapsed % dwell_us
+    }
+
+    /// Current hop number and channel, if tuned.
+    pub fn current(&self) -> Option<(u64, HopChannel)> {
+        self.current.map(|(hop, _)| (hop, self.channel(hop)))
+    }
+
+    /// Time spent on channel `index` of the list in the current occupancy period, up to the
+    /// last hop.
+    pub fn occupancy_us(&self, index: usize) -> u64 {
+        self.occupancy_us[index]
+    }
+
+    /// Whether any channel exceeded `max_occupancy_us` within one occupancy period.
+    pub fn limit_exceeded(&self) -> bool {
+        self.limit_exceeded
+    }
+
+    /// Retune the radio if the current hop slot has changed, returning the new channel.
+    ///
+    /// Call this at least once per dwell time. Frequency programming registers must only be
+    /// altered in IDLE, so the radio is left in IDLE after a hop; put it back in RX or TX.
+    /// IDLE is awaited within `budget`. Frequency hops select channel 0, so that the carrier
+    /// is the frequency itself.
+    pub fn poll<SPI, SpiE, D>(
+        &mut self,
+        radio: &mut Cc1101<SPI>,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<Option<HopChannel>, Error<SpiE>>
+    where
+        SPI: SpiDevice<u8, Error = SpiE>,
+        D: DelayNs,
+    {
+        let now = self.clock.now_us();
+        let hop = self.hop_at(now);
+        if matches!(self.current, Some((current, _)) if current == hop) {
+            return Ok(None);
+        }
+
+        self.account(now);
+        let channel = self.channel(hop);
+        radio.set_radio_mode_within(RadioMode::Idle, delay, budget)?;
+        match channel {
+            HopChannel::Channel(channel) => radio.set_channel(channel)?,
+            HopChannel::Frequency(hz) => {
+                radio.set_frequency_within(hz, delay, budget)?;
+                radio.set_channel(0)?;
+            }
+        }
+        self.current = Some((hop, now));
+        Ok(Some(channel))
+    }
+
+    /// Add the time spent on the current channel to its occupancy.
+    fn account(&mut self, now: u64) {
+        if let Some((hop, tuned_at)) = self.current {
+            let index = self.sequence.index(hop);
+            self.occupancy_us[index] += now - tuned_at;
+            if self.occupancy_us[index] > self.config.max_occupancy_us {
+                self.limit_exceeded = true;
+            }
+        }
+        if now - self.period_start_us >= self.config.period_us {
+            self.occupancy_us.fill(0);
+            self.period_start_us = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChip, SimDelay, SimSpi};
+    use crate::{ChannelPlan, MachineState};
+    use core::cell::RefCell;
+
+    const BUDGET: PollBudget = PollBudget::new(100, 1_000);
+
+    #[test]
+    fn test_hop_sequence() {
+        let sequence = HopSequence::new(50, 0x1234);
+        for cycle in 0..4 {
+            let mut seen = [false; 50];
+            for hop in cycle * 50..(cycle + 1) * 50 {
+                seen[sequence.index(hop)] = true;
+            }
+            assert!(seen.iter().all(|&s| s));
+        }
+        assert!((0..50).any(|hop| sequence.index(hop) != sequence.index(hop + 50)));
+        assert!((0..50).any(|hop| sequence.index(hop) != HopSequence::new(50, 0x4321).index(hop)));
+        assert_eq!(HopSequence::new(1, 0x1234).index(7), 0);
+    }
+
+    #[test]
+    fn test_hopper() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+        let mut delay = SimDelay::new(&chip);
+        let plan = ChannelPlan::new(902_200_000, 200_000, 64).unwrap();
+        cc1101.set_channel_plan(&plan).unwrap();
+
+        let channels = [HopChannel::Channel(3), HopChannel::Channel(7), HopChannel::Channel(11)];
+        let config = HoppingConfig::new(10_000, 42).occupancy_limit(15_000, 100_000);
+        let mut occupancy = [0; 3];
+        let mut hopper =
+            FrequencyHopper::new(&channels, &mut occupancy, config, SimDelay::new(&chip)).unwrap();
+
+        let first = hopper.poll(&mut cc1101, &mut delay, BUDGET).unwrap().unwrap();
+        assert_eq!(first, hopper.channel(0));
+        assert_eq!(hopper.poll(&mut cc1101, &mut delay, BUDGET).unwrap(), None);
+        let HopChannel::Channel(channel) = first else {
+            unreachable!()
+        };
+        assert_eq!(chip.borrow().carrier_hz(), plan.frequency(channel).unwrap());
+
+        delay.delay_us(hopper.time_to_next_hop_us() as u32);
+        let next = hopper.poll(&mut cc1101, &mut delay, BUDGET).unwrap();
+        assert_eq!(next, Some(hopper.channel(1)));
+        assert_eq!(hopper.current().unwrap().0, 1);
+        assert!(hopper.occupancy_us(channels.iter().position(|&c| c == first).unwrap()) >= 10_000);
+        assert!(!hopper.limit_exceeded());
+
+        // A receiver that joins later follows the same sequence once synchronized.
+        let mut occupancy = [0; 3];
+        let mut receiver =
+            FrequencyHopper::new(&channels, &mut occupancy, config, SimDelay::new(&chip)).unwrap();
+        let now = chip.borrow().now_us();
+        receiver.synchronize(hopper.hop_at(now), now - (10_000 - hopper.time_to_next_hop_us()));
+        for _ in 0..10 {
+            let now = chip.borrow().now_us();
+            assert_eq!(receiver.hop_at(now), hopper.hop_at(now));
+            delay.delay_us(3_000);
+        }
+
+        // Missed hops keep the radio on one channel beyond the limit.
+        delay.delay_us(20_000);
+        hopper.poll(&mut cc1101, &mut delay, BUDGET).unwrap();
+        assert!(hopper.limit_exceeded());
+
+        assert_eq!(
+            FrequencyHopper::new(&[], &mut [], config, SimDelay::new(&chip)).err(),
+            Some(UserError::BufferTooSmall(1))
+        );
+        let mixed = [HopChannel::Channel(3), HopChannel::Frequency(915_000_000)];
+        assert_eq!(
+            FrequencyHopper::new(&mixed, &mut [0; 2], config, SimDelay::new(&chip)).err(),
+            Some(UserError::MixedHopChannels)
+        );
+    }
+
+    #[test]
+    fn test_frequency_hops() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+        let mut delay = SimDelay::new(&chip);
+
+        // A channel selected before hopping does not offset the frequencies.
+        cc1101.set_channel_plan(&ChannelPlan::new(902_200_000, 200_000, 64).unwrap()).unwrap();
+        cc1101.set_channel(7).unwrap();
+        cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+
+        let channels = [HopChannel::Frequency(903_000_000), HopChannel::Frequency(915_000_000)];
+        let mut occupancy = [0; 2];
+        let config = HoppingConfig::new(10_000, 42);
+        let mut hopper =
+            FrequencyHopper::new(&channels, &mut occupancy, config, SimDelay::new(&chip)).unwrap();
+        for _ in 0..4 {
+            let channel = hopper.poll(&mut cc1101, &mut delay, BUDGET).unwrap().unwrap();
+            let HopChannel::Frequency(hz) = channel else {
+                unreachable!()
+            };
+            assert!(chip.borrow().carrier_hz().abs_diff(hz) < 400, "{hz}");
+            assert_eq!(chip.borrow().state(), MachineState::IDLE);
+            cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+            delay.delay_us(hopper.time_to_next_hop_us() as u32);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Listen-before-talk transmission.
+//!
+//! In RX, a `STX` command strobe only takes the radio to TX if the channel is clear according
+//! to `MCSM1.CCA_MODE`, see section 15.7 "Clear Channel Assessment" of the data sheet (TI
+//! document SWRS061I). Otherwise the radio stays in RX, which is seen in `MARCSTATE`. On a
+//! busy channel, the attempt is repeated after a random backoff whose window doubles with
+//! every attempt, as needed for the LBT requirements of ETSI EN 300 220.
+
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+use crate::{Cc1101, Error, MachineState, PollBudget, RadioMode};
+
+/// Listen-before-talk timing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LbtConfig {
+    /// Time to listen in RX before the first attempt. RSSI is always given time to become
+    /// valid first.
+    pub listen_us: u32,
+    /// Backoff window after the first busy attempt. A random time within the window is waited.
+    pub backoff_us: u32,
+    /// Largest backoff window, reached by doubling the window after every busy attempt.
+    pub max_backoff_us: u32,
+    /// Number of attempts before giving up with [`Error::ChannelBusy`].
+    pub attempts: u8,
+    /// Seed of the backoff generator. Mixed with RSSI readings, so that radios sharing a seed
+    /// do not back off in step.
+    pub seed: u32,
+    /// Bound on each radio state change.
+    pub budget: PollBudget,
+}
+
+impl LbtConfig {
+    /// ETSI EN 300 220-1 listen time of 5 ms, followed by random backoffs of up to 5 ms,
+    /// growing to 40 ms.
+    pub const fn etsi(seed: u32) -> Self {
+        LbtConfig {
+            listen_us: 5_000,
+            backoff_us: 5_000,
+            max_backoff_us: 40_000,
+            attempts: 8,
+            seed,
+            budget: PollBudget::new(10, 100),
+        }
+    }
+}
+
+/// xorshift32.
+pub(crate) fn next_random(state: &mut u32) -> u32 {
+    let mut x = (*state).max(1);
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    *state = x;
+    x
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Transmit the packet in the TX FIFO once clear channel assessment allows it, returning
+    /// the number of attempts it took.
+    ///
+    /// Enters RX, waits until RSSI is valid and `listen_us` has passed, and strobes `STX`.
+    /// Returns as soon as the radio is in TX; wait for the packet to be sent as after
+    /// [`Self::enable_tx`]. Set a CCA mode other than [`crate::CcaMode::CciAlways`] first, see
+    /// [`Self::set_cca_mode`]. On [`Error::ChannelBusy`] the radio is left in RX with the
+    /// packet still in the TX FIFO.
+    pub fn transmit_with_cca<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        config: LbtConfig,
+    ) -> Result<u8, Error<SpiE>> {
+        if self.get_machine_state()? != MachineState::RX {
+            self.set_radio_mode_within(RadioMode::Receive, delay, config.budget)?;
+        }
+        let (settle_us, _) = self.rssi_timing()?;
+        delay.delay_us(settle_us.max(config.listen_us));
+
+        let mut random = config.seed;
+        let mut window_us = config.backoff_us;
+        for attempt in 1..=config.attempts {
+            self.enable_tx()?;
+            if self.await_cca(delay, config.budget)? {
+                return Ok(attempt);
+            }
+
+            if attempt < config.attempts {
+                random ^= self.get_rssi_dbm()? as u32;
+                delay.delay_us(next_random(&mut random) % window_us.max(1));
+                window_us = window_us.saturating_mul(2).min(config.max_backoff_us);
+            }
+        }
+        Err(Error::ChannelBusy)
+    }
+
+    /// Wait for the radio to settle after `STX` in RX: `true` once in TX, `false` if it stayed
+    /// in RX as the channel is busy. Passes through RXTX_SWITCH, and the calibration states
+    /// with `MCSM0.FS_AUTOCAL`, on the way to TX.
+    fn await_cca<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<bool, Error<SpiE>> {
+        let mut last_seen = MachineState::RX;
+        for _ in 0..budget.polls {
+            delay.delay_us(budget.interval_us);
+            last_seen = self.get_machine_state()?;
+            match last_seen {
+                MachineState::TX => return Ok(true),
+                MachineState::RX => return Ok(false),
+                MachineState::TXFIFO_UNDERFLOW => {
+                    self.flush_tx_fifo_buffer()?;
+                    return Err(Error::TxUnderflow);
+                }
+                _ => {}
+            }
+        }
+        Err(Error::Timeout {
+            expected: MachineState::TX,
+            last_seen,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChip, SimSpi, RXTX_SWITCH_US};
+    use crate::CcaMode;
+    use core::cell::RefCell;
+
+    /// Delay that clears the channel once `clear_at_us` is reached.
+    struct ClearingDelay<'a> {
+        chip: &'a RefCell<SimChip>,
+        clear_at_us: u64,
+    }
+
+    impl DelayNs for ClearingDelay<'_> {
+        fn delay_ns(&mut self, ns: u32) {
+            let mut chip = self.chip.borrow_mut();
+            chip.advance_ns(ns as u64);
+            if chip.now_us() >= self.clear_at_us {
+                chip.set_channel_busy(false);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transmit_with_cca() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+        cc1101.set_cca_mode(CcaMode::CciRssiBelowThreshold).unwrap();
+        cc1101.write_data(&mut Some(3), &mut None, &mut [1, 2, 3]).unwrap();
+        let config = LbtConfig::etsi(0x5EED);
+        let queued = chip.borrow().tx_fifo_len();
+
+        // Busy channel, give up after all attempts.
+        chip.borrow_mut().set_channel_busy(true);
+        let mut delay = ClearingDelay {
+            chip: &chip,
+            clear_at_us: u64::MAX,
+        };
+        let start = chip.borrow().now_us();
+        assert_eq!(cc1101.transmit_with_cca(&mut delay, config), Err(Error::ChannelBusy));
+        assert!(chip.borrow().now_us() - start >= 5_000);
+        assert_eq!(chip.borrow().state(), MachineState::RX);
+        assert_eq!(chip.borrow().tx_fifo_len(), queued);
+
+        // Channel clears while backing off.
+        delay.clear_at_us = chip.borrow().now_us() + 20_000;
+        let attempts = cc1101.transmit_with_cca(&mut delay, config).unwrap();
+        assert!(attempts > 1);
+        assert_eq!(chip.borrow().state(), MachineState::TX);
+
+        // Clear channel, first attempt goes through RXTX_SWITCH, which outlasts a poll.
+        assert!(RXTX_SWITCH_US > config.budget.interval_us);
+        cc1101.set_radio_mode(RadioMode::Idle).unwrap();
+        cc1101.write_data(&mut Some(3), &mut None, &mut [1, 2, 3]).unwrap();
+        assert_eq!(cc1101.transmit_with_cca(&mut delay, config), Ok(1));
+
+        // Stuck between RX and TX, until the budget runs out.
+        cc1101.set_radio_mode(RadioMode::Idle).unwrap();
+        cc1101.write_data(&mut Some(3), &mut None, &mut [1, 2, 3]).unwrap();
+        let config = LbtConfig {
+            budget: PollBudget::new(10, 2),
+            ..config
+        };
+        assert_eq!(
+            cc1101.transmit_with_cca(&mut delay, config),
+            Err(Error::Timeout {
+                expected: MachineState::TX,
+                last_seen: MachineState::RXTX_SWITCH,
+            })
+        );
+    }
+}
@@ -2,19 +2,61 @@
 
 extern crate embedded_hal as hal;
 
-#[cfg(feature = "std")]
+#[cfg(any(test, feature = "std"))]
 extern crate std;
 
 use core::fmt::{self, Display, Formatter};
-use hal::{digital::PinState, spi::SpiDevice};
+use hal::{delay::DelayNs, digital::PinState, spi::SpiDevice};
 
 #[macro_use]
 pub mod lowlevel;
+mod agc;
+mod airtime;
+mod bit_sync;
+mod channel;
+mod duty_cycle;
+mod foc;
+mod front_end;
+mod fscal;
+mod hopping;
+mod lbt;
+mod output_power;
+mod radio_config;
+mod rx_window;
+mod scanner;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+#[cfg(any(test, feature = "std"))]
+mod smartrf;
+mod stream;
 mod types;
-
+mod wor;
+
+#[cfg(feature = "async")]
+pub mod asynch;
+
+pub use agc::*;
+pub use airtime::*;
+pub use bit_sync::*;
+pub use channel::*;
+pub use duty_cycle::*;
+pub use foc::*;
+pub use front_end::*;
+pub use fscal::*;
+pub use hopping::*;
+pub use lbt::*;
 use lowlevel::{access::*, convert::*, registers::*};
 pub use lowlevel::{types::*, FIFO_SIZE_MAX};
+pub use output_power::*;
+pub use radio_config::*;
+pub use rx_window::*;
+pub use scanner::*;
+#[cfg(any(test, feature = "std"))]
+pub use smartrf::*;
+pub use stream::*;
+use types::PacketFraming;
 pub use types::*;
+pub use wor::*;
 
 /// CC1101 errors.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -27,6 +69,22 @@ pub enum Error<SpiE> {
     CrcMismatch,
     /// Invalid state read from MARCSTATE register
     InvalidState(u8),
+    /// The radio did not reach the expected state within the polling budget.
+    Timeout {
+        /// State the radio was expected to reach.
+        expected: MachineState,
+        /// Last state read from MARCSTATE before giving up.
+        last_seen: MachineState,
+    },
+    /// Clear channel assessment found the channel busy on every attempt to transmit.
+    ChannelBusy,
+    /// Transmitting would exceed the duty-cycle limit of the sub-band.
+    DutyCycleExceeded {
+        /// Time until the transmission would be allowed, in microseconds, `u64::MAX` if never.
+        wait_us: u64,
+    },
+    /// Setting outside of what the radio supports.
+    InvalidConfig(ConfigError),
     /// User Input Error
     UserInputError(UserError),
     /// Platform-dependent SPI-errors, such as IO errors.
@@ -44,6 +102,41 @@ pub enum UserError {
     MissingRxStatusParameters,
     /// Array too long
     ArrayTooLong(usize),
+    /// Buffer too small, at least this many bytes are required
+    BufferTooSmall(usize),
+    /// Packets in infinite packet length mode have no framing, use streaming instead
+    InfinitePacketLength,
+    /// Channel number outside of the channel plan
+    InvalidChannel(u8),
+    /// Only allowed in IDLE, with the state the radio was in
+    NotIdle(MachineState),
+    /// Hop lists mixing channel numbers and frequencies
+    MixedHopChannels,
+}
+
+/// Settings outside of what the radio supports, with the requested value.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ConfigError {
+    /// Carrier frequency outside of the 300–348, 387–464 and 779–928 MHz bands, in Hertz
+    Frequency(u64),
+    /// Data rate outside of 0.6–600 kBaud, in baud
+    DataRate(u64),
+    /// Channel bandwidth wider than 812.5 kHz (with a 26 MHz crystal), in Hertz
+    ChannelBandwidth(u64),
+    /// Frequency deviation outside of 1.587–380.859 kHz (with a 26 MHz crystal), in Hertz
+    Deviation(u64),
+    /// Intermediate frequency above 787.109 kHz (with a 26 MHz crystal), in Hertz
+    IntermediateFrequency(u64),
+    /// Crystal frequency outside of 26–27 MHz, or not the one of the radio, in Hertz
+    Crystal(u64),
+    /// Frequency offset outside of -203.125–201.538 kHz (with a 26 MHz crystal), in Hertz
+    FrequencyOffset(i32),
+    /// Frequency step of a sweep of zero, in Hertz
+    FrequencyStep(u64),
+    /// Channel spacing outside of 25.390–405.457 kHz (with a 26 MHz crystal), in Hertz
+    ChannelSpacing(u64),
+    /// Channel count outside of 1–256
+    ChannelCount(u16),
 }
 
 impl<SpiE> From<SpiE> for Error<SpiE> {
@@ -59,6 +152,48 @@ impl<SpiE: Display> Display for Error<SpiE> {
             Self::RxOverflow => write!(f, "RX FIFO buffer overflowed"),
             Self::CrcMismatch => write!(f, "CRC mismatch"),
             Self::InvalidState(s) => write!(f, "Invalid state: {}", s),
+            Self::Timeout {
+                expected,
+                last_seen,
+            } => {
+                write!(f, "Timeout waiting for {:?}, last seen {:?}", expected, last_seen)
+            }
+            Self::ChannelBusy => write!(f, "Channel busy"),
+            Self::DutyCycleExceeded {
+                wait_us,
+            } => write!(f, "Duty cycle exceeded, wait {} us", wait_us),
+            Self::InvalidConfig(e) => match e {
+                ConfigError::Frequency(v) => {
+                    write!(f, "Invalid config: Frequency out of range: {} Hz", v)
+                }
+                ConfigError::DataRate(v) => {
+                    write!(f, "Invalid config: Data rate out of range: {} Baud", v)
+                }
+                ConfigError::ChannelBandwidth(v) => {
+                    write!(f, "Invalid config: Channel bandwidth out of range: {} Hz", v)
+                }
+                ConfigError::Deviation(v) => {
+                    write!(f, "Invalid config: Deviation out of range: {} Hz", v)
+                }
+                ConfigError::IntermediateFrequency(v) => {
+                    write!(f, "Invalid config: Intermediate frequency out of range: {} Hz", v)
+                }
+                ConfigError::Crystal(v) => {
+                    write!(f, "Invalid config: Crystal frequency out of range: {} Hz", v)
+                }
+                ConfigError::FrequencyOffset(v) => {
+                    write!(f, "Invalid config: Frequency offset out of range: {} Hz", v)
+                }
+                ConfigError::FrequencyStep(v) => {
+                    write!(f, "Invalid config: Frequency step out of range: {} Hz", v)
+                }
+                ConfigError::ChannelSpacing(v) => {
+                    write!(f, "Invalid config: Channel spacing out of range: {} Hz", v)
+                }
+                ConfigError::ChannelCount(v) => {
+                    write!(f, "Invalid config: Channel count out of range: {}", v)
+                }
+            },
             Self::UserInputError(e) => match e {
                 UserError::MissingLengthParameter => {
                     write!(f, "User error: Missing length parameter")
@@ -70,6 +205,17 @@ impl<SpiE: Display> Display for Error<SpiE> {
                     write!(f, "User error: Missing Rx status parameters")
                 }
                 UserError::ArrayTooLong(v) => write!(f, "User error: Array too long: {}", v),
+                UserError::BufferTooSmall(v) => {
+                    write!(f, "User error: Buffer too small, {} bytes required", v)
+                }
+                UserError::InfinitePacketLength => {
+                    write!(f, "User error: Infinite packet length, use streaming")
+                }
+                UserError::InvalidChannel(v) => write!(f, "User error: Invalid channel: {}", v),
+                UserError::NotIdle(v) => write!(f, "User error: Not in IDLE: {:?}", v),
+                UserError::MixedHopChannels => {
+                    write!(f, "User error: Hop list mixes channels and frequencies")
+                }
             },
             Self::Spi(e) => write!(f, "SPI error: {}", e),
         }
@@ -79,6 +225,64 @@ impl<SpiE: Display> Display for Error<SpiE> {
 #[cfg(feature = "std")]
 impl<SpiE: Display + core::fmt::Debug> std::error::Error for Error<SpiE> {}
 
+pub(crate) fn check_frequency<SpiE>(hz: u64) -> Result<(), Error<SpiE>> {
+    if FREQUENCY_BANDS_HZ.iter().any(|&(low, high)| (low..=high).contains(&hz)) {
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig(ConfigError::Frequency(hz)))
+    }
+}
+
+pub(crate) fn check_crystal<SpiE>(fxosc: u64) -> Result<(), Error<SpiE>> {
+    if (26_000_000..=27_000_000).contains(&fxosc) {
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig(ConfigError::Crystal(fxosc)))
+    }
+}
+
+pub(crate) fn check_data_rate<SpiE>(baud: u64) -> Result<(), Error<SpiE>> {
+    let (low, high) = DATA_RATE_RANGE;
+    if (low..=high).contains(&baud) {
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig(ConfigError::DataRate(baud)))
+    }
+}
+
+pub(crate) fn check_channel_bandwidth<SpiE>(hz: u64, fxosc: u64) -> Result<(), Error<SpiE>> {
+    if hz <= to_chanbw(0, 0, fxosc) {
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig(ConfigError::ChannelBandwidth(hz)))
+    }
+}
+
+pub(crate) fn check_deviation<SpiE>(hz: u64, fxosc: u64) -> Result<(), Error<SpiE>> {
+    if (to_deviation(0, 0, fxosc)..=to_deviation(7, 7, fxosc)).contains(&hz) {
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig(ConfigError::Deviation(hz)))
+    }
+}
+
+pub(crate) fn check_freq_if<SpiE>(hz: u64, fxosc: u64) -> Result<(), Error<SpiE>> {
+    if hz <= to_freq_if(0x1f, fxosc) {
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig(ConfigError::IntermediateFrequency(hz)))
+    }
+}
+
+pub(crate) fn check_frequency_offset<SpiE>(hz: i32, fxosc: u64) -> Result<(), Error<SpiE>> {
+    let (low, high) = (to_frequency_offset(0x80, fxosc), to_frequency_offset(0x7f, fxosc));
+    if (low..=high).contains(&hz) {
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig(ConfigError::FrequencyOffset(hz)))
+    }
+}
+
 /// High level API for interacting with the CC1101 radio chip.
 pub struct Cc1101<SPI>(lowlevel::Cc1101<SPI>);
 
@@ -90,6 +294,22 @@ where
         Ok(Cc1101(lowlevel::Cc1101::new(spi)?))
     }
 
+    /// Driver for a radio clocked by a `fxosc` Hertz crystal, rather than the usual 26 MHz.
+    ///
+    /// All frequency, data rate, bandwidth and timing conversions use it. Fails with
+    /// [`crate::ConfigError::Crystal`] outside of 26–27 MHz.
+    pub fn with_crystal(spi: SPI, fxosc: u64) -> Result<Self, Error<SpiE>> {
+        check_crystal(fxosc)?;
+        let mut cc1101 = lowlevel::Cc1101::new(spi)?;
+        cc1101.fxosc = fxosc;
+        Ok(Cc1101(cc1101))
+    }
+
+    /// Crystal frequency, in Hertz.
+    pub fn crystal_hz(&self) -> u64 {
+        self.0.fxosc
+    }
+
     /// Last Chip Status Byte
     pub fn get_chip_status(&mut self) -> Option<StatusByte> {
         self.0.status
@@ -193,37 +413,19 @@ where
 
     /// Set the GDO0 Active Output State
     pub fn set_gdo0_active_state(&mut self, state: PinState) -> Result<(), Error<SpiE>> {
-        let value = match state {
-            PinState::Low => 1,
-            PinState::High => 0,
-        };
-
-        // Invert output, i.e. select active low (1) / high (0)
-        self.0.modify_register(config::IOCFG0, |r| r.gdo0_inv(value))?;
+        self.0.modify_register(config::IOCFG0, |r| r.gdo0_inv(gdo_inv(state)))?;
         Ok(())
     }
 
     /// Set the GDO1 Active Output State
     pub fn set_gdo1_active_state(&mut self, state: PinState) -> Result<(), Error<SpiE>> {
-        let value = match state {
-            PinState::Low => 1,
-            PinState::High => 0,
-        };
-
-        // Invert output, i.e. select active low (1) / high (0)
-        self.0.modify_register(config::IOCFG1, |r| r.gdo1_inv(value))?;
+        self.0.modify_register(config::IOCFG1, |r| r.gdo1_inv(gdo_inv(state)))?;
         Ok(())
     }
 
     /// Set the GDO2 Active Output State
     pub fn set_gdo2_active_state(&mut self, state: PinState) -> Result<(), Error<SpiE>> {
-        let value = match state {
-            PinState::Low => 1,
-            PinState::High => 0,
-        };
-
-        // Invert output, i.e. select active low (1) / high (0)
-        self.0.modify_register(config::IOCFG2, |r| r.gdo2_inv(value))?;
+        self.0.modify_register(config::IOCFG2, |r| r.gdo2_inv(gdo_inv(state)))?;
         Ok(())
     }
 
@@ -251,25 +453,61 @@ where
         Ok(())
     }
 
-    /// Sets the carrier frequency (in Hertz).
-    pub fn set_frequency(&mut self, hz: u64) -> Result<(), Error<SpiE>> {
+    /// Sets the carrier frequency (in Hertz), returning the frequency achieved.
+    ///
+    /// Fails with [`ConfigError::Frequency`] outside of the 300–348, 387–464 and 779–928 MHz
+    /// bands. Waits for IDLE without a bound, prefer [`Self::set_frequency_within`].
+    pub fn set_frequency(&mut self, hz: u64) -> Result<Rounded, Error<SpiE>> {
+        check_frequency(hz)?;
+
         // Before altering any frequency programming register we
         // must stop the frequency synthesizer by going to Idle mode.
         // See section 21 "Frequency Programming" of the data sheet
         // (TI document SWRS061I).
         self.set_radio_mode(RadioMode::Idle)?;
+        self.write_frequency(hz)
+    }
 
-        let (freq0, freq1, freq2) = from_frequency(hz);
+    /// Sets the carrier frequency (in Hertz), returning the frequency achieved, going to IDLE
+    /// first with [`Self::set_radio_mode_within`].
+    ///
+    /// Fails with [`ConfigError::Frequency`] outside of the 300–348, 387–464 and 779–928 MHz
+    /// bands, and with [`Error::Timeout`] if IDLE is not reached within `budget`.
+    pub fn set_frequency_within<D: DelayNs>(
+        &mut self,
+        hz: u64,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<Rounded, Error<SpiE>> {
+        check_frequency(hz)?;
+        self.set_radio_mode_within(RadioMode::Idle, delay, budget)?;
+        self.write_frequency(hz)
+    }
+
+    /// Write `FREQ2/1/0`, with the frequency synthesizer stopped.
+    fn write_frequency(&mut self, hz: u64) -> Result<Rounded, Error<SpiE>> {
+        let (freq0, freq1, freq2) = from_frequency(hz, self.0.fxosc);
         self.0.write_register(config::FREQ0, |w| w.freq(freq0))?;
         self.0.write_register(config::FREQ1, |w| w.freq(freq1))?;
         self.0.write_register(config::FREQ2, |w| w.freq(freq2))?;
-        Ok(())
+        Ok(Rounded::new(hz, to_frequency(freq0, freq1, freq2, self.0.fxosc)))
     }
 
-    /// Sets the frequency synthesizer intermediate frequency (in Hertz).
-    pub fn set_freq_if(&mut self, hz: u64) -> Result<(), Error<SpiE>> {
-        self.0.write_register(config::FSCTRL1, |w| w.freq_if(from_freq_if(hz)))?;
-        Ok(())
+    /// Gets the carrier frequency (in Hertz).
+    pub fn get_frequency(&mut self) -> Result<u64, Error<SpiE>> {
+        let freq2 = self.0.read_register(config::FREQ2)?.freq();
+        let freq1 = self.0.read_register(config::FREQ1)?.freq();
+        let freq0 = self.0.read_register(config::FREQ0)?.freq();
+        Ok(to_frequency(freq0, freq1, freq2, self.0.fxosc))
+    }
+
+    /// Sets the frequency synthesizer intermediate frequency (in Hertz), returning the
+    /// intermediate frequency achieved.
+    pub fn set_freq_if(&mut self, hz: u64) -> Result<Rounded, Error<SpiE>> {
+        check_freq_if(hz, self.0.fxosc)?;
+        let value = from_freq_if(hz, self.0.fxosc);
+        self.0.write_register(config::FSCTRL1, |w| w.freq_if(value))?;
+        Ok(Rounded::new(hz, to_freq_if(value, self.0.fxosc)))
     }
 
     /// Sets the target value for the averaged amplitude from the digital channel filter.
@@ -308,16 +546,13 @@ where
         Ok(())
     }
 
-    /// Sets the absolute RSSI threshold for asserting carrier sense.
-    ///
-    /// The field value is written directly to `AGCCTRL1.CARRIER_SENSE_ABS_THR`.
-    /// It is a 4-bit two's complement value.
-    /// Its meaning is relative to the `MAGN_TARGET` setting in `AGCCTRL2`:
-    /// - `0b1000` (`-8`): absolute carrier sense threshold disabled
-    /// - `0b1001..=0b1111` (`-7..=-1`): 7 dB to 1 dB below `MAGN_TARGET`
-    /// - `0b0000..=0b0111` (`0..=7`): at `MAGN_TARGET` up to 7 dB above it
-    pub fn set_carrier_sense_threshold(&mut self, threshold: u8) -> Result<(), Error<SpiE>> {
-        self.0.modify_register(config::AGCCTRL1, |r| r.carrier_sense_abs_thr(threshold.min(15)))?;
+    /// Sets the absolute RSSI threshold for asserting carrier sense, relative to the
+    /// `MAGN_TARGET` setting in `AGCCTRL2`.
+    pub fn set_carrier_sense_threshold(
+        &mut self,
+        threshold: CarrierSenseAbsoluteThreshold,
+    ) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::AGCCTRL1, |r| r.carrier_sense_abs_thr(threshold.into()))?;
         Ok(())
     }
 
@@ -327,26 +562,83 @@ where
         Ok(())
     }
 
+    /// Sets the level of hysteresis on the magnitude deviation of the AGC.
+    pub fn set_agc_hysteresis(&mut self, level: HystLevel) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::AGCCTRL0, |r| r.hyst_level(level.into()))?;
+        Ok(())
+    }
+
+    /// Sets the number of channel filter samples the AGC waits after a gain adjustment.
+    pub fn set_agc_wait_time(&mut self, wait_time: WaitTime) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::AGCCTRL0, |r| r.wait_time(wait_time.into()))?;
+        Ok(())
+    }
+
+    /// Sets when the AGC gain is frozen, see also [`Self::freeze_agc`].
+    pub fn set_agc_freeze(&mut self, freeze: AgcFreeze) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::AGCCTRL0, |r| r.agc_freeze(freeze.into()))?;
+        Ok(())
+    }
+
     /// Configures when to run automatic calibration.
     pub fn set_autocalibration(&mut self, autocal: AutoCalibration) -> Result<(), Error<SpiE>> {
         self.0.modify_register(config::MCSM0, |r| r.fs_autocal(autocal.into()))?;
         Ok(())
     }
 
-    /// Set Modem deviation setting.
-    pub fn set_deviation_hz(&mut self, deviation: u64) -> Result<(), Error<SpiE>> {
-        let (mantissa, exponent) = from_deviation(deviation);
+    /// Sets how long to wait after the crystal oscillator has stabilized before `CHP_RDYn`
+    /// goes low, when leaving SLEEP.
+    pub fn set_po_timeout(&mut self, timeout: PoTimeout) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM0, |r| r.po_timeout(timeout.into()))?;
+        Ok(())
+    }
+
+    /// Enables the pin radio control option, where the radio state follows the `SI`, `SCLK`
+    /// and `SO` pins while `CSn` is high.
+    pub fn pin_control_enable(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM0, |r| r.pin_ctrl_en(enable as u8))?;
+        Ok(())
+    }
+
+    /// Keeps the crystal oscillator running in SLEEP.
+    pub fn set_xosc_force_on(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM0, |r| r.xosc_force_on(enable as u8))?;
+        Ok(())
+    }
+
+    /// Set Modem deviation setting, returning the deviation achieved.
+    ///
+    /// Fails with [`ConfigError::Deviation`] outside of 1.587–380.859 kHz.
+    pub fn set_deviation_hz(&mut self, deviation: u64) -> Result<Rounded, Error<SpiE>> {
+        check_deviation(deviation, self.0.fxosc)?;
+        let (mantissa, exponent) = from_deviation(deviation, self.0.fxosc);
         self.0
             .write_register(config::DEVIATN, |w| w.deviation_m(mantissa).deviation_e(exponent))?;
-        Ok(())
+        Ok(Rounded::new(deviation, to_deviation(mantissa, exponent, self.0.fxosc)))
     }
 
-    /// Sets the data rate (in bits per second).
-    pub fn set_data_rate(&mut self, baud: u64) -> Result<(), Error<SpiE>> {
-        let (mantissa, exponent) = from_drate(baud);
+    /// Gets the frequency deviation (in Hertz).
+    pub fn get_deviation_hz(&mut self) -> Result<u64, Error<SpiE>> {
+        let deviatn = self.0.read_register(config::DEVIATN)?;
+        Ok(to_deviation(deviatn.deviation_m(), deviatn.deviation_e(), self.0.fxosc))
+    }
+
+    /// Sets the data rate (in bits per second), returning the data rate achieved.
+    ///
+    /// Fails with [`ConfigError::DataRate`] outside of 0.6–600 kBaud.
+    pub fn set_data_rate(&mut self, baud: u64) -> Result<Rounded, Error<SpiE>> {
+        check_data_rate(baud)?;
+        let (mantissa, exponent) = from_drate(baud, self.0.fxosc);
         self.0.modify_register(config::MDMCFG4, |r| r.drate_e(exponent))?;
         self.0.write_register(config::MDMCFG3, |w| w.drate_m(mantissa))?;
-        Ok(())
+        Ok(Rounded::new(baud, to_drate(mantissa, exponent, self.0.fxosc)))
+    }
+
+    /// Gets the data rate (in bits per second).
+    pub fn get_data_rate(&mut self) -> Result<u64, Error<SpiE>> {
+        let exponent = self.0.read_register(config::MDMCFG4)?.drate_e();
+        let mantissa = self.0.read_register(config::MDMCFG3)?.drate_m();
+        Ok(to_drate(mantissa, exponent, self.0.fxosc))
     }
 
     /// Enable Forward Error Correction (FEC) with interleaving for packet payload
@@ -361,33 +653,82 @@ where
         Ok(())
     }
 
+    /// Sets the preamble quality threshold, `PKTCTRL1.PQT`.
+    ///
+    /// A sync word is only accepted once the preamble quality estimator has counted enough
+    /// more good bits than bad ones.
+    pub fn set_preamble_quality_threshold(
+        &mut self,
+        threshold: PreambleQualityThreshold,
+    ) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::PKTCTRL1, |r| r.pqt(threshold.into()))?;
+        Ok(())
+    }
+
+    /// Sets the threshold for the TX FIFO and RX FIFO.
+    pub fn set_fifo_threshold(&mut self, threshold: FifoThreshold) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::FIFOTHR, |r| r.fifo_thr(threshold.into()))?;
+        Ok(())
+    }
+
     /// Selects CCA_MODE; Reflected in CCA signal.
     pub fn set_cca_mode(&mut self, cca_mode: CcaMode) -> Result<(), Error<SpiE>> {
         self.0.modify_register(config::MCSM1, |r| r.cca_mode(cca_mode.into()))?;
         Ok(())
     }
 
-    /// Sets the channel bandwidth (in Hertz).
-    pub fn set_channel_bandwidth(&mut self, bandwidth_hz: u64) -> Result<(), Error<SpiE>> {
-        let (mantissa, exponent) = from_chanbw(bandwidth_hz);
-        self.0.modify_register(config::MDMCFG4, |r| r.chanbw_m(mantissa).chanbw_e(exponent))?;
+    /// Selects the state to enter when a packet has been received.
+    pub fn set_rxoff_mode(&mut self, mode: RxOffMode) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM1, |r| r.rxoff_mode(mode.into()))?;
+        Ok(())
+    }
+
+    /// Selects the state to enter when a packet has been sent.
+    pub fn set_txoff_mode(&mut self, mode: TxOffMode) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM1, |r| r.txoff_mode(mode.into()))?;
+        Ok(())
+    }
+
+    /// Sets the timeout for the sync word search in RX, `MCSM2.RX_TIME`.
+    ///
+    /// The timeout is relative to the Event 0 period, see [`RxTime`].
+    pub fn set_rx_time(&mut self, rx_time: RxTime) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM2, |r| r.rx_time(rx_time.into()))?;
+        Ok(())
+    }
+
+    /// Leave RX as soon as carrier sense is not asserted, without waiting for the RX timeout.
+    pub fn set_rx_time_rssi(&mut self, enable: bool) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM2, |r| r.rx_time_rssi(enable as u8))?;
+        Ok(())
+    }
+
+    /// Selects what to look for when the RX timeout expires.
+    pub fn set_rx_time_qualifier(&mut self, qualifier: RxTimeQualifier) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::MCSM2, |r| r.rx_time_qual(qualifier.into()))?;
         Ok(())
     }
 
+    /// Sets the narrowest channel bandwidth of at least `bandwidth_hz` (in Hertz), returning
+    /// the bandwidth achieved.
+    ///
+    /// Fails with [`ConfigError::ChannelBandwidth`] above 812.5 kHz.
+    pub fn set_channel_bandwidth(&mut self, bandwidth_hz: u64) -> Result<Rounded, Error<SpiE>> {
+        check_channel_bandwidth(bandwidth_hz, self.0.fxosc)?;
+        let (mantissa, exponent) = from_chanbw(bandwidth_hz, self.0.fxosc);
+        self.0.modify_register(config::MDMCFG4, |r| r.chanbw_m(mantissa).chanbw_e(exponent))?;
+        Ok(Rounded::new(bandwidth_hz, to_chanbw(mantissa, exponent, self.0.fxosc)))
+    }
+
+    /// Gets the channel bandwidth (in Hertz).
+    pub fn get_channel_bandwidth(&mut self) -> Result<u64, Error<SpiE>> {
+        let mdmcfg4 = self.0.read_register(config::MDMCFG4)?;
+        Ok(to_chanbw(mdmcfg4.chanbw_m(), mdmcfg4.chanbw_e(), self.0.fxosc))
+    }
+
     /// Configure the sync word to use, and at what level it should be verified.
     pub fn set_sync_mode(&mut self, sync_mode: SyncMode) -> Result<(), Error<SpiE>> {
-        let reset: u16 = 0xD391; // SYNC1/SYNC0 reset (default sync word)
-
-        let (mode, word) = match sync_mode {
-            SyncMode::Disabled => (SyncCheck::DISABLED, reset),
-            SyncMode::Match15of16(w) => (SyncCheck::CHECK_15_16, w),
-            SyncMode::Match16of16(w) => (SyncCheck::CHECK_16_16, w),
-            SyncMode::Match30of32(w) => (SyncCheck::CHECK_30_32, w),
-            SyncMode::CarrierSenseOnly => (SyncCheck::CHECK_0_0_CS, reset),
-            SyncMode::Match15of16Cs(w) => (SyncCheck::CHECK_15_16_CS, w),
-            SyncMode::Match16of16Cs(w) => (SyncCheck::CHECK_16_16_CS, w),
-            SyncMode::Match30of32Cs(w) => (SyncCheck::CHECK_30_32_CS, w),
-        };
+        let (mode, word) = sync_mode.fields();
 
         self.0.modify_register(config::MDMCFG2, |r| r.sync_mode(mode.into()))?;
         self.0.write_register(config::SYNC1, |w| w.sync(((word >> 8) & 0xff) as u8))?;
@@ -427,18 +768,8 @@ where
 
     /// Configure device address, and address filtering.
     pub fn set_address_filter(&mut self, filter: AddressFilter) -> Result<(), Error<SpiE>> {
-        self.0.address_field = true;
-        let (mode, addr) = match filter {
-            AddressFilter::Disabled => {
-                self.0.address_field = false;
-                (AddressCheck::DISABLED, 0)
-            }
-            AddressFilter::Device(addr) => (AddressCheck::SELF, addr),
-            AddressFilter::DeviceLowBroadcast(addr) => (AddressCheck::SELF_LOW_BROADCAST, addr),
-            AddressFilter::DeviceHighLowBroadcast(addr) => {
-                (AddressCheck::SELF_HIGH_LOW_BROADCAST, addr)
-            }
-        };
+        let (mode, addr) = filter.fields();
+        self.0.address_field = mode != AddressCheck::DISABLED;
         self.0.modify_register(config::PKTCTRL1, |r| r.adr_chk(mode.into()))?;
         self.0.write_register(config::ADDR, |w| w.device_addr(addr))?;
         Ok(())
@@ -458,14 +789,10 @@ where
 
     /// Configure packet mode, and length.
     pub fn set_packet_length(&mut self, length: PacketLength) -> Result<(), Error<SpiE>> {
-        let (format, pktlen) = match length {
-            PacketLength::Fixed(limit) => (LengthConfig::FIXED, limit),
-            PacketLength::Variable(max_limit) => {
-                self.0.length_field = true;
-                (LengthConfig::VARIABLE, max_limit)
-            }
-            PacketLength::Infinite => (LengthConfig::INFINITE, 0xFF), // PKTLEN reset (max)
-        };
+        let (format, pktlen) = length.fields();
+        if format == LengthConfig::VARIABLE {
+            self.0.length_field = true;
+        }
         self.0.modify_register(config::PKTCTRL0, |r| r.length_config(format.into()))?;
         self.0.write_register(config::PKTLEN, |w| w.packet_length(pktlen))?;
         Ok(())
@@ -477,6 +804,28 @@ where
         Ok(())
     }
 
+    /// Write all configuration registers in a single burst access.
+    ///
+    /// The radio should be in IDLE, as frequency programming registers must not be altered
+    /// while the frequency synthesizer is running.
+    pub fn apply_config(&mut self, config: &RadioConfig) -> Result<(), Error<SpiE>> {
+        self.0.write_config_burst(config::IOCFG2, config.as_bytes())?;
+
+        let pktctrl1 = config.read(config::PKTCTRL1);
+        self.0.length_field =
+            config.read(config::PKTCTRL0).length_config() == LengthConfig::VARIABLE as u8;
+        self.0.address_field = pktctrl1.adr_chk() != AddressCheck::DISABLED as u8;
+        self.0.rx_status_fields = pktctrl1.append_status() != 0;
+        Ok(())
+    }
+
+    /// Read all configuration registers in a single burst access.
+    pub fn read_config(&mut self) -> Result<RadioConfig, Error<SpiE>> {
+        let mut regs = [0; CONFIG_REGISTERS];
+        self.0.read_config_burst(config::IOCFG2, &mut regs)?;
+        Ok(RadioConfig::from_bytes(regs).crystal(self.0.fxosc))
+    }
+
     /// Read hardware information: part number for CC1101 and current version number
     pub fn get_hw_info(&mut self) -> Result<(u8, u8), Error<SpiE>> {
         let partnum = self.0.read_register(status::PARTNUM)?.partnum();
@@ -488,7 +837,7 @@ where
     /// Frequency offset compensation is only supported for 2-FSK, GFSK, 4-FSK, and MSK modulation.
     /// This register will read 0 when using ASK or OOK modulation.
     pub fn get_est_freq_offset(&mut self) -> Result<i32, Error<SpiE>> {
-        Ok(to_frequency_offset(self.0.read_register(status::FREQEST)?.freqoff_est()))
+        Ok(to_frequency_offset(self.0.read_register(status::FREQEST)?.freqoff_est(), self.0.fxosc))
     }
 
     /// Read Link Quality Indicator.
@@ -504,14 +853,7 @@ where
 
     /// Read the Machine State
     pub fn get_machine_state(&mut self) -> Result<MachineState, Error<SpiE>> {
-        let marc_state = self.0.read_register(status::MARCSTATE)?.marc_state();
-
-        match MachineState::try_from(marc_state) {
-            Ok(state) => Ok(state),
-            Err(e) => match e {
-                MachineStateError::InvalidState(value) => Err(Error::InvalidState(value)),
-            },
-        }
+        machine_state(self.0.read_register(status::MARCSTATE)?.marc_state())
     }
 
     /// Read the Current GDOx Status and Packet Status
@@ -543,65 +885,103 @@ where
         Ok(num_rxbytes)
     }
 
-    /// Read data from FIFO
-    pub fn read_data(
-        &mut self,
-        length: &mut Option<u8>,
-        address: &mut Option<u8>,
-        rssi: &mut Option<i16>,
-        lqi: &mut Option<u8>,
-        data: &mut [u8],
-    ) -> Result<(), Error<SpiE>> {
-        let num_of_optional_fields = self.0.length_field as usize + self.0.address_field as usize;
-        let data_len_max: usize = FIFO_SIZE_MAX as usize - num_of_optional_fields;
-        let mut optional_fields = [0, 0];
-        let mut index = 0;
-
-        // Validate Length parameter
-        if self.0.length_field && length.is_none() {
-            return Err(Error::UserInputError(UserError::MissingLengthParameter));
-        }
-
-        // Validate Address parameter
-        if self.0.address_field && address.is_none() {
-            return Err(Error::UserInputError(UserError::MissingAddressParameter));
-        }
+    /// Read one complete packet from the RX FIFO, copying its payload into `buf`.
+    ///
+    /// The packet framing (length byte, address byte and appended status bytes) is read back
+    /// from `PKTCTRL0`, `PKTCTRL1` and `PKTLEN`, so no knowledge of the current configuration
+    /// is needed. The packet must be fully received, e.g. once a GDO configured as
+    /// [`GdoCfg::SYNC_WORD`] has deasserted. Use streaming for infinite packet length mode.
+    ///
+    /// Fails with [`UserError::BufferTooSmall`] if the payload does not fit in `buf`. The
+    /// packet is then left in the RX FIFO in fixed packet length mode, and dropped from it in
+    /// variable packet length mode, as its length byte has been read.
+    pub fn receive_packet<'a>(&mut self, buf: &'a mut [u8]) -> Result<RxPacket<'a>, Error<SpiE>> {
+        let pktctrl1 = self.0.read_register(config::PKTCTRL1)?;
+        let framing = PacketFraming {
+            length_config: self.0.read_register(config::PKTCTRL0)?.length_config(),
+            packet_length: self.0.read_register(config::PKTLEN)?.packet_length(),
+            address: pktctrl1.adr_chk() != AddressCheck::DISABLED as u8,
+            status: pktctrl1.append_status() != 0,
+        };
 
-        // Validate RSSI and LQI parameters
-        if self.0.rx_status_fields && (rssi.is_none() || lqi.is_none()) {
-            return Err(Error::UserInputError(UserError::MissingRxStatusParameters));
+        if framing.infinite() {
+            return Err(Error::UserInputError(UserError::InfinitePacketLength));
         }
 
-        if data.len() <= data_len_max {
-            self.0.access_fifo(
-                Access::Read,
-                &mut optional_fields[..num_of_optional_fields],
-                data,
-            )?;
+        let mut field = [0];
+        let length = if framing.variable() {
+            self.0.read_burst(multi::FIFO, &mut field)?;
+            Some(field[0])
+        } else {
+            None
+        };
 
-            if self.0.length_field {
-                *length = Some(optional_fields[index]);
-                index += 1;
+        let len = framing.payload_len(length);
+        if len > buf.len() {
+            // Only the length byte has been read, drop the rest of the packet so that the
+            // next one is read from its start.
+            if let Some(length) = length {
+                self.discard_rx(length as usize + framing.status as usize * 2)?;
             }
+            return Err(Error::UserInputError(UserError::BufferTooSmall(len)));
+        }
 
-            if self.0.address_field {
-                *address = Some(optional_fields[index]);
-            }
+        let address = if framing.address {
+            self.0.read_burst(multi::FIFO, &mut field)?;
+            Some(field[0])
+        } else {
+            None
+        };
 
-            if self.0.rx_status_fields {
-                *rssi = Some(from_rssi_to_rssi_dbm(data[data.len() - 2]));
-                *lqi = Some(data[data.len() - 1]);
+        self.0.read_burst(multi::FIFO, &mut buf[..len])?;
 
-                // Overwrite the last 2 bytes with `0` for the user to avoid confusion with data handling
-                data[data.len() - 2] = 0;
-                data[data.len() - 1] = 0;
-            }
+        let status = if framing.status {
+            let mut status = [0; 2];
+            self.0.read_burst(multi::FIFO, &mut status)?;
+            Some(RxStatus::from_bytes(status))
         } else {
-            return Err(Error::UserInputError(UserError::ArrayTooLong(data.len())));
+            None
+        };
+
+        Ok(RxPacket {
+            payload: &buf[..len],
+            length,
+            address,
+            status,
+        })
+    }
+
+    /// Read and drop `len` bytes from the RX FIFO.
+    fn discard_rx(&mut self, mut len: usize) -> Result<(), Error<SpiE>> {
+        let mut scratch = [0; 16];
+        while len > 0 {
+            let chunk = len.min(scratch.len());
+            self.0.read_burst(multi::FIFO, &mut scratch[..chunk])?;
+            len -= chunk;
         }
         Ok(())
     }
 
+    /// Read data from FIFO
+    ///
+    /// Prefer [`Self::receive_packet`], which does not depend on the optional fields
+    /// tracked by the driver.
+    pub fn read_data(
+        &mut self,
+        length: &mut Option<u8>,
+        address: &mut Option<u8>,
+        rssi: &mut Option<i16>,
+        lqi: &mut Option<u8>,
+        data: &mut [u8],
+    ) -> Result<(), Error<SpiE>> {
+        let fields = FifoFields::of(&self.0);
+        let mut optional_fields = [0, 0];
+        let num = fields.check_read(length, address, rssi, lqi, data)?;
+        self.0.access_fifo(Access::Read, &mut optional_fields[..num], data)?;
+        fields.unpack(&optional_fields, length, address, rssi, lqi, data);
+        Ok(())
+    }
+
     /// Write data into FIFO
     pub fn write_data(
         &mut self,
@@ -609,49 +989,126 @@ where
         address: &mut Option<u8>,
         data: &mut [u8],
     ) -> Result<(), Error<SpiE>> {
-        let num_of_optional_fields = self.0.length_field as usize + self.0.address_field as usize;
-        let data_len_max: usize = FIFO_SIZE_MAX as usize - num_of_optional_fields;
+        let fields = FifoFields::of(&self.0);
         let mut optional_fields = [0, 0];
-        let mut index = 0;
+        let num = fields.pack(length, address, data, &mut optional_fields)?;
+        self.0.access_fifo(Access::Write, &mut optional_fields[..num], data)?;
+        Ok(())
+    }
 
-        // Validate Length parameter
-        if self.0.length_field && length.is_none() {
-            return Err(Error::UserInputError(UserError::MissingLengthParameter));
+    /// Set radio in Idle/Sleep/Calibrate/Transmit/Receive mode, giving up after `budget`.
+    ///
+    /// Unlike [`Self::set_radio_mode`] this never spins forever: `MARCSTATE` is polled with
+    /// `delay` between reads, and [`Error::Timeout`] is returned if the target state is not
+    /// reached. RX FIFO overflow and TX FIFO underflow states are recovered automatically
+    /// by flushing the FIFO and re-issuing the command strobe.
+    ///
+    /// `Calibrate` waits for the calibration to complete, i.e. for the radio to return to
+    /// IDLE. `Sleep` is not polled, as any SPI access wakes the radio up again.
+    pub fn set_radio_mode_within<D: DelayNs>(
+        &mut self,
+        radio_mode: RadioMode,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<(), Error<SpiE>> {
+        if radio_mode != RadioMode::Idle {
+            self.set_radio_mode_within(RadioMode::Idle, delay, budget)?;
         }
 
-        // Validate Address parameter
-        if self.0.address_field && address.is_none() {
-            return Err(Error::UserInputError(UserError::MissingAddressParameter));
+        let target = self.enter_radio_mode(radio_mode)?;
+        match radio_mode {
+            RadioMode::Sleep => Ok(()),
+            _ => self.await_machine_state_within(radio_mode, target, delay, budget),
         }
+    }
 
-        if data.len() <= data_len_max {
-            if self.0.length_field {
-                optional_fields[index] = (*length).unwrap();
-                index += 1;
+    /// Issue the command strobe for `radio_mode`, returning the state it settles in.
+    fn enter_radio_mode(&mut self, radio_mode: RadioMode) -> Result<MachineState, Error<SpiE>> {
+        Ok(match radio_mode {
+            RadioMode::Idle => {
+                self.exit_rx_tx()?;
+                MachineState::IDLE
+            }
+            RadioMode::Sleep => {
+                self.enter_power_down_mode()?;
+                MachineState::SLEEP
+            }
+            RadioMode::Calibrate => {
+                self.cal_freq_synth_and_turn_off()?;
+                MachineState::IDLE
+            }
+            RadioMode::Transmit => {
+                self.enable_tx()?;
+                MachineState::TX
             }
+            RadioMode::Receive => {
+                self.enable_rx()?;
+                MachineState::RX
+            }
+        })
+    }
+
+    fn await_machine_state_within<D: DelayNs>(
+        &mut self,
+        radio_mode: RadioMode,
+        target: MachineState,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<(), Error<SpiE>> {
+        let reached = |state| state == target;
+        self.poll_machine_state_within(target, reached, Some(radio_mode), delay, budget)?;
+        Ok(())
+    }
 
-            if self.0.address_field {
-                optional_fields[index] = (*address).unwrap();
+    /// Poll `MARCSTATE` with `delay` between reads until `reached` accepts the state, and
+    /// return it, or fail with [`Error::Timeout`] for `expected` after `budget`.
+    ///
+    /// With `recover`, the FIFO error states are left by flushing the FIFO and re-issuing the
+    /// command strobe for that radio mode.
+    fn poll_machine_state_within<D: DelayNs>(
+        &mut self,
+        expected: MachineState,
+        reached: impl Fn(MachineState) -> bool,
+        recover: Option<RadioMode>,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<MachineState, Error<SpiE>> {
+        let mut last_seen = self.get_machine_state()?;
+
+        for _ in 0..budget.polls {
+            match (last_seen, recover) {
+                (state, _) if reached(state) => return Ok(state),
+                (MachineState::RXFIFO_OVERFLOW, Some(radio_mode)) => {
+                    self.flush_rx_fifo_buffer()?;
+                    self.enter_radio_mode(radio_mode)?;
+                }
+                (MachineState::TXFIFO_UNDERFLOW, Some(radio_mode)) => {
+                    self.flush_tx_fifo_buffer()?;
+                    self.enter_radio_mode(radio_mode)?;
+                }
+                _ => {}
             }
+            delay.delay_us(budget.interval_us);
+            last_seen = self.get_machine_state()?;
+        }
 
-            self.0.access_fifo(
-                Access::Write,
-                &mut optional_fields[..num_of_optional_fields],
-                data,
-            )?;
+        if reached(last_seen) {
+            Ok(last_seen)
         } else {
-            return Err(Error::UserInputError(UserError::ArrayTooLong(data.len())));
+            Err(Error::Timeout {
+                expected,
+                last_seen,
+            })
         }
-        Ok(())
     }
 
-    /// ------------------------------------------------------------------------
-    /// TODO: The functions bellow shall be discontinued in the future.
-    /// 1.  await_machine_state() is a blocking function
-    /// 2.  set_defaults() was written with specific application in mind
-    /// 3.  set_radio_mode() depends on await_machine_state which is blocking
-    /// 4.  receive() was written with specific application in mind
-    /// ------------------------------------------------------------------------
+    // ------------------------------------------------------------------------
+    // TODO: The functions bellow shall be discontinued in the future.
+    // 1.  await_machine_state() is a blocking function
+    // 2.  set_defaults() was written with specific application in mind
+    // 3.  set_radio_mode() depends on await_machine_state which is blocking
+    // 4.  receive() was written with specific application in mind
+    // ------------------------------------------------------------------------
 
     fn await_machine_state(&mut self, target_state: MachineState) -> Result<(), Error<SpiE>> {
         loop {
@@ -663,6 +1120,24 @@ where
         Ok(())
     }
 
+    /// Wait for the radio to leave RX after a packet, returning the state it went to, or fail
+    /// with [`Error::Timeout`] after `budget`.
+    ///
+    /// With `MCSM1.RXOFF_MODE` going to TX, the radio may have gone on to the TX off state
+    /// by the time it is polled, so any state outside of RX ends the wait.
+    fn await_rx_off<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<MachineState, Error<SpiE>> {
+        let rxoff = off_state(self.0.read_register(config::MCSM1)?.rxoff_mode());
+        if rxoff == MachineState::RX {
+            return Ok(rxoff);
+        }
+        let reached = |state| !in_rx(state);
+        self.poll_machine_state_within(rxoff, reached, None, delay, budget)
+    }
+
     /// Configure some default settings, to be removed in the future.
     #[rustfmt::skip]
     pub fn set_defaults(&mut self) -> Result<(), Error<SpiE>> {
@@ -712,26 +1187,45 @@ where
         self.await_machine_state(target)
     }
 
-    fn rx_bytes_available(&mut self) -> Result<u8, Error<SpiE>> {
+    /// Wait for the number of bytes in the RX FIFO to be non-zero and stable over one poll
+    /// interval, or fail with [`Error::Timeout`] after `budget`.
+    fn rx_bytes_available<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<u8, Error<SpiE>> {
         let mut last = 0;
 
-        loop {
+        for _ in 0..budget.polls {
             let num_rxbytes = self.get_rx_bytes()?;
 
             if (num_rxbytes > 0) && (num_rxbytes == last) {
-                break;
+                return Ok(last);
             }
 
             last = num_rxbytes;
+            delay.delay_us(budget.interval_us);
         }
-        Ok(last)
+        let rxoff = off_state(self.0.read_register(config::MCSM1)?.rxoff_mode());
+        Err(Error::Timeout {
+            expected: rxoff,
+            last_seen: self.get_machine_state()?,
+        })
     }
 
-    /// Should also be able to configure MCSM1.RXOFF_MODE to declare what state
-    /// to enter after fully receiving a packet.
-    /// Possible targets: IDLE, FSTON, TX, RX
-    pub fn receive(&mut self, addr: &mut u8, buf: &mut [u8]) -> Result<u8, Error<SpiE>> {
-        match self.rx_bytes_available() {
+    /// Read a received packet, waiting for the radio to enter the state configured with
+    /// [`Self::set_rxoff_mode`]. The RX FIFO is flushed if that is IDLE.
+    ///
+    /// The RX FIFO and `MARCSTATE` are polled with `delay` between reads, and
+    /// [`Error::Timeout`] is returned if the packet is not complete within `budget`.
+    pub fn receive<D: DelayNs>(
+        &mut self,
+        addr: &mut u8,
+        buf: &mut [u8],
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<u8, Error<SpiE>> {
+        match self.rx_bytes_available(delay, budget) {
             Ok(_nbytes) => {
                 let mut length: Option<u8> = Some(0);
                 let mut address: Option<u8> = Some(0);
@@ -740,8 +1234,9 @@ where
                 self.read_data(&mut length, &mut address, &mut rssi, &mut lqi, buf)?;
                 *addr = address.unwrap();
                 let lqi = self.0.read_register(status::LQI)?;
-                self.await_machine_state(MachineState::IDLE)?;
-                self.flush_rx_fifo_buffer()?;
+                if self.await_rx_off(delay, budget)? == MachineState::IDLE {
+                    self.flush_rx_fifo_buffer()?;
+                }
                 if lqi.crc_ok() != 1 {
                     Err(Error::CrcMismatch)
                 } else {
@@ -755,3 +1250,172 @@ where
         }
     }
 }
+
+/// Invert output value for `IOCFGx.GDOx_INV`, i.e. select active low (1) / high (0).
+fn gdo_inv(state: PinState) -> u8 {
+    match state {
+        PinState::Low => 1,
+        PinState::High => 0,
+    }
+}
+
+/// State the radio settles in after a packet, for a `MCSM1.RXOFF_MODE` or `TXOFF_MODE` value.
+///
+/// Both fields encode IDLE, FSTXON, TX and RX in the same order.
+fn off_state(mode: u8) -> MachineState {
+    match mode {
+        0 => MachineState::IDLE,
+        1 => MachineState::FSTXON,
+        2 => MachineState::TX,
+        _ => MachineState::RX,
+    }
+}
+
+/// Whether `state` is RX, or one of the states RX passes through on the way out.
+fn in_rx(state: MachineState) -> bool {
+    matches!(state, MachineState::RX | MachineState::RX_END | MachineState::RX_RST)
+}
+
+/// Decode a `MARCSTATE.MARC_STATE` value.
+fn machine_state<SpiE>(marc_state: u8) -> Result<MachineState, Error<SpiE>> {
+    match MachineState::try_from(marc_state) {
+        Ok(state) => Ok(state),
+        Err(e) => match e {
+            MachineStateError::InvalidState(value) => Err(Error::InvalidState(value)),
+        },
+    }
+}
+
+/// Optional fields framing the payload in the FIFO, as tracked by the low level driver.
+#[derive(Copy, Clone)]
+struct FifoFields {
+    length: bool,
+    address: bool,
+    rx_status: bool,
+}
+
+impl FifoFields {
+    fn of<SPI>(ll: &lowlevel::Cc1101<SPI>) -> Self {
+        FifoFields {
+            length: ll.length_field,
+            address: ll.address_field,
+            rx_status: ll.rx_status_fields,
+        }
+    }
+
+    #[cfg(feature = "async")]
+    fn of_async<SPI>(ll: &lowlevel::asynch::Cc1101<SPI>) -> Self {
+        FifoFields {
+            length: ll.length_field,
+            address: ll.address_field,
+            rx_status: ll.rx_status_fields,
+        }
+    }
+
+    /// Number of length/address bytes preceding the payload.
+    fn count(&self) -> usize {
+        self.length as usize + self.address as usize
+    }
+
+    fn check_lengths<SpiE>(
+        &self,
+        length: &Option<u8>,
+        address: &Option<u8>,
+    ) -> Result<usize, Error<SpiE>> {
+        // Validate Length parameter
+        if self.length && length.is_none() {
+            return Err(Error::UserInputError(UserError::MissingLengthParameter));
+        }
+
+        // Validate Address parameter
+        if self.address && address.is_none() {
+            return Err(Error::UserInputError(UserError::MissingAddressParameter));
+        }
+
+        Ok(self.count())
+    }
+
+    fn check_data_len<SpiE>(&self, data: &[u8]) -> Result<(), Error<SpiE>> {
+        let data_len_max: usize = FIFO_SIZE_MAX as usize - self.count();
+        if data.len() > data_len_max {
+            return Err(Error::UserInputError(UserError::ArrayTooLong(data.len())));
+        }
+        Ok(())
+    }
+
+    /// Validate `read_data` arguments, returning the number of optional fields to read.
+    fn check_read<SpiE>(
+        &self,
+        length: &Option<u8>,
+        address: &Option<u8>,
+        rssi: &Option<i16>,
+        lqi: &Option<u8>,
+        data: &[u8],
+    ) -> Result<usize, Error<SpiE>> {
+        let num = self.check_lengths(length, address)?;
+
+        // Validate RSSI and LQI parameters
+        if self.rx_status && (rssi.is_none() || lqi.is_none()) {
+            return Err(Error::UserInputError(UserError::MissingRxStatusParameters));
+        }
+
+        self.check_data_len(data)?;
+        Ok(num)
+    }
+
+    /// Distribute the optional fields and appended status read from the FIFO.
+    fn unpack(
+        &self,
+        optional_fields: &[u8],
+        length: &mut Option<u8>,
+        address: &mut Option<u8>,
+        rssi: &mut Option<i16>,
+        lqi: &mut Option<u8>,
+        data: &mut [u8],
+    ) {
+        let mut index = 0;
+
+        if self.length {
+            *length = Some(optional_fields[index]);
+            index += 1;
+        }
+
+        if self.address {
+            *address = Some(optional_fields[index]);
+        }
+
+        if self.rx_status {
+            *rssi = Some(from_rssi_to_rssi_dbm(data[data.len() - 2]));
+            *lqi = Some(data[data.len() - 1]);
+
+            // Overwrite the last 2 bytes with `0` for the user to avoid confusion with data handling
+            data[data.len() - 2] = 0;
+            data[data.len() - 1] = 0;
+        }
+    }
+
+    /// Validate `write_data` arguments and fill in the optional fields, returning their number.
+    fn pack<SpiE>(
+        &self,
+        length: &Option<u8>,
+        address: &Option<u8>,
+        data: &[u8],
+        optional_fields: &mut [u8; 2],
+    ) -> Result<usize, Error<SpiE>> {
+        let num = self.check_lengths(length, address)?;
+        self.check_data_len(data)?;
+
+        let mut index = 0;
+
+        if let (true, Some(length)) = (self.length, length) {
+            optional_fields[index] = *length;
+            index += 1;
+        }
+
+        if let (true, Some(address)) = (self.address, address) {
+            optional_fields[index] = *address;
+        }
+
+        Ok(num)
+    }
+}
@@ -5,12 +5,15 @@ use hal::spi::{Operation, SpiDevice};
 mod traits;
 
 pub mod access;
+#[cfg(feature = "async")]
+pub mod asynch;
 pub mod convert;
 pub mod registers;
 pub mod types;
 
 use self::registers::{BurstRead, BurstWrite, Readable, StatusByte, Strobe, Writable};
 
+/// Default crystal frequency, in Hertz. The radio supports 26 to 27 MHz crystals.
 pub const FXOSC: u64 = 26_000_000;
 pub const FIFO_SIZE_MAX: u8 = 64;
 const BLANK_BYTE: u8 = 0;
@@ -21,6 +24,8 @@ pub struct Cc1101<SPI> {
     pub length_field: bool,
     pub address_field: bool,
     pub rx_status_fields: bool,
+    /// Crystal frequency, in Hertz.
+    pub fxosc: u64,
 }
 
 impl<SPI, SpiE> Cc1101<SPI>
@@ -34,6 +39,7 @@ where
             length_field: false,
             address_field: false,
             rx_status_fields: true,
+            fxosc: FXOSC,
         };
         Ok(cc1101)
     }
@@ -100,6 +106,32 @@ where
         Ok(status)
     }
 
+    /// Burst-read consecutive configuration registers, starting at `S`, into `buf`.
+    pub fn read_config_burst<S>(&mut self, _first: S, buf: &mut [u8]) -> Result<(), SpiE>
+    where
+        S: Readable + Writable,
+    {
+        let mut header =
+            [access::Access::Read as u8 | access::Mode::Burst as u8 | <S as Readable>::ADDR];
+        self.spi
+            .transaction(&mut [Operation::TransferInPlace(&mut header), Operation::Read(buf)])?;
+        self.status = Some(StatusByte::from(header[0]));
+        Ok(())
+    }
+
+    /// Burst-write consecutive configuration registers, starting at `S`, from `data`.
+    pub fn write_config_burst<S>(&mut self, _first: S, data: &[u8]) -> Result<(), SpiE>
+    where
+        S: Readable + Writable,
+    {
+        let mut header =
+            [access::Access::Write as u8 | access::Mode::Burst as u8 | <S as Writable>::ADDR];
+        self.spi
+            .transaction(&mut [Operation::TransferInPlace(&mut header), Operation::Write(data)])?;
+        self.status = Some(StatusByte::from(header[0]));
+        Ok(())
+    }
+
     /// Burst-read a multi-byte region (e.g. PATABLE) into `buf`.
     pub fn read_burst<S: BurstRead>(&mut self, _reg: S, buf: &mut [u8]) -> Result<(), SpiE> {
         let mut header = [access::Access::Read as u8 | access::Mode::Burst as u8 | S::ADDR];
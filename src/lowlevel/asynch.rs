@@ -0,0 +1,174 @@
+//! Async twin of the low level driver, built on `embedded-hal-async`.
+//!
+//! Mirrors [`super::Cc1101`] method for method; register selectors and views are
+//! shared with the blocking driver through [`super::registers`].
+
+use embedded_hal_async::spi::{Operation, SpiDevice};
+
+use super::registers::{BurstRead, BurstWrite, Readable, StatusByte, Strobe, Writable};
+use super::{access, registers, BLANK_BYTE, FXOSC};
+
+pub struct Cc1101<SPI> {
+    pub(crate) spi: SPI,
+    pub status: Option<StatusByte>,
+    pub length_field: bool,
+    pub address_field: bool,
+    pub rx_status_fields: bool,
+    /// Crystal frequency, in Hertz.
+    pub fxosc: u64,
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    pub fn new(spi: SPI) -> Result<Self, SpiE> {
+        let cc1101 = Cc1101 {
+            spi,
+            status: None,
+            length_field: false,
+            address_field: false,
+            rx_status_fields: true,
+            fxosc: FXOSC,
+        };
+        Ok(cc1101)
+    }
+
+    /// Read a single-byte register, returning its typed read view.
+    pub async fn read_register<S: Readable>(&mut self, _reg: S) -> Result<S::View, SpiE> {
+        let mut buffer = [access::Access::Read as u8 | S::MODE as u8 | S::ADDR, BLANK_BYTE];
+        self.spi.transfer_in_place(&mut buffer).await?;
+        self.status = Some(StatusByte::from(buffer[0]));
+        Ok(S::view(buffer[1]))
+    }
+
+    /// Write a single-byte register, building its value from the reset state (no readback).
+    pub async fn write_register<S, F>(&mut self, _reg: S, f: F) -> Result<(), SpiE>
+    where
+        S: Writable,
+        F: FnOnce(S::View) -> S::View,
+    {
+        let byte = S::bits(f(S::view(S::RESET)));
+        let mut buffer = [access::Access::Write as u8 | S::MODE as u8 | S::ADDR, byte];
+        self.spi.transfer_in_place(&mut buffer).await?;
+        self.status = Some(StatusByte::from(buffer[0]));
+        Ok(())
+    }
+
+    /// Read-modify-write a single-byte register.
+    pub async fn modify_register<S, F>(&mut self, _reg: S, f: F) -> Result<(), SpiE>
+    where
+        S: Readable + Writable,
+        F: FnOnce(<S as Writable>::View) -> <S as Writable>::View,
+    {
+        let mut rbuffer = [
+            access::Access::Read as u8 | <S as Readable>::MODE as u8 | <S as Readable>::ADDR,
+            BLANK_BYTE,
+        ];
+        self.spi.transfer_in_place(&mut rbuffer).await?;
+        self.status = Some(StatusByte::from(rbuffer[0]));
+
+        let byte = <S as Writable>::bits(f(<S as Writable>::view(rbuffer[1])));
+        let mut wbuffer = [
+            access::Access::Write as u8 | <S as Writable>::MODE as u8 | <S as Writable>::ADDR,
+            byte,
+        ];
+        self.spi.transfer_in_place(&mut wbuffer).await?;
+        self.status = Some(StatusByte::from(wbuffer[0]));
+        Ok(())
+    }
+
+    /// Fire a command strobe and return the chip status byte returned during it.
+    pub async fn strobe<S: Strobe>(&mut self, _cmd: S) -> Result<StatusByte, SpiE> {
+        let mut buffer = [access::Access::Write as u8 | access::Mode::Single as u8 | S::ADDR];
+        self.spi.transfer_in_place(&mut buffer).await?;
+        let status = StatusByte::from(buffer[0]);
+        // See `super::Cc1101::strobe` for why only SNOP yields a current status.
+        self.status = if S::NO_EFFECT {
+            Some(status)
+        } else {
+            None
+        };
+        Ok(status)
+    }
+
+    /// Burst-read consecutive configuration registers, starting at `S`, into `buf`.
+    pub async fn read_config_burst<S>(&mut self, _first: S, buf: &mut [u8]) -> Result<(), SpiE>
+    where
+        S: Readable + Writable,
+    {
+        let mut header =
+            [access::Access::Read as u8 | access::Mode::Burst as u8 | <S as Readable>::ADDR];
+        self.spi
+            .transaction(&mut [Operation::TransferInPlace(&mut header), Operation::Read(buf)])
+            .await?;
+        self.status = Some(StatusByte::from(header[0]));
+        Ok(())
+    }
+
+    /// Burst-write consecutive configuration registers, starting at `S`, from `data`.
+    pub async fn write_config_burst<S>(&mut self, _first: S, data: &[u8]) -> Result<(), SpiE>
+    where
+        S: Readable + Writable,
+    {
+        let mut header =
+            [access::Access::Write as u8 | access::Mode::Burst as u8 | <S as Writable>::ADDR];
+        self.spi
+            .transaction(&mut [Operation::TransferInPlace(&mut header), Operation::Write(data)])
+            .await?;
+        self.status = Some(StatusByte::from(header[0]));
+        Ok(())
+    }
+
+    /// Burst-read a multi-byte region (e.g. PATABLE) into `buf`.
+    pub async fn read_burst<S: BurstRead>(&mut self, _reg: S, buf: &mut [u8]) -> Result<(), SpiE> {
+        let mut header = [access::Access::Read as u8 | access::Mode::Burst as u8 | S::ADDR];
+        self.spi
+            .transaction(&mut [Operation::TransferInPlace(&mut header), Operation::Read(buf)])
+            .await?;
+        self.status = Some(StatusByte::from(header[0]));
+        Ok(())
+    }
+
+    /// Burst-write a multi-byte region (e.g. PATABLE) from `data`.
+    pub async fn write_burst<S: BurstWrite>(&mut self, _reg: S, data: &[u8]) -> Result<(), SpiE> {
+        let mut header = [access::Access::Write as u8 | access::Mode::Burst as u8 | S::ADDR];
+        self.spi
+            .transaction(&mut [Operation::TransferInPlace(&mut header), Operation::Write(data)])
+            .await?;
+        self.status = Some(StatusByte::from(header[0]));
+        Ok(())
+    }
+
+    /// Framed FIFO access: header + optional length/address fields + data, in one transaction.
+    pub async fn access_fifo(
+        &mut self,
+        access: access::Access,
+        optional_fields: &mut [u8],
+        data: &mut [u8],
+    ) -> Result<(), SpiE> {
+        let mut buffer = [access as u8
+            | access::Mode::Burst as u8
+            | <registers::multi::FIFO as BurstRead>::ADDR];
+
+        if optional_fields.is_empty() {
+            self.spi
+                .transaction(&mut [
+                    Operation::TransferInPlace(&mut buffer),
+                    Operation::TransferInPlace(data),
+                ])
+                .await?;
+        } else {
+            self.spi
+                .transaction(&mut [
+                    Operation::TransferInPlace(&mut buffer),
+                    Operation::TransferInPlace(optional_fields),
+                    Operation::TransferInPlace(data),
+                ])
+                .await?;
+        }
+
+        self.status = Some(StatusByte::from(buffer[0]));
+        Ok(())
+    }
+}
@@ -1,51 +1,143 @@
-use crate::lowlevel::FXOSC;
-use core::convert::TryInto;
-
 const RSSI_OFFSET: i16 = 74; // Table 31: Typical RSSI_offset Values
 
-pub const fn from_frequency(hz: u64) -> (u8, u8, u8) {
-    let freq = hz * 1u64.rotate_left(16) / FXOSC;
+/// Frequency bands of the frequency synthesizer, in Hertz, see section 4.1 "Operating
+/// Conditions" of the data sheet (TI document SWRS061I).
+pub const FREQUENCY_BANDS_HZ: [(u64, u64); 3] =
+    [(300_000_000, 348_000_000), (387_000_000, 464_000_000), (779_000_000, 928_000_000)];
+
+/// Supported data rates, in baud.
+pub const DATA_RATE_RANGE: (u64, u64) = (600, 600_000);
+
+// Conversions take the crystal frequency `fxosc` in Hertz, `FXOSC` on most boards. Settings
+// out of range of the registers are clamped to the closest setting, rather than wrapped. Use
+// `FREQUENCY_BANDS_HZ` and `DATA_RATE_RANGE` to check what the chip supports.
+
+pub const fn from_frequency(hz: u64, fxosc: u64) -> (u8, u8, u8) {
+    let freq = hz * 1u64.rotate_left(16) / fxosc;
+    let freq = if freq > 0x3f_ffff {
+        0x3f_ffff
+    } else {
+        freq
+    };
     let freq0 = (freq & 0xff) as u8;
     let freq1 = ((freq >> 8) & 0xff) as u8;
     let freq2 = ((freq >> 16) & 0x3f) as u8;
     (freq0, freq1, freq2)
 }
 
-pub const fn from_frequency_offset(hz: i32) -> u8 {
-    ((hz as i64 * (1u64 << 14) as i64) / FXOSC as i64) as i8 as u8
+pub const fn to_frequency(freq0: u8, freq1: u8, freq2: u8, fxosc: u64) -> u64 {
+    let freq = (freq2 as u64 & 0x3f) << 16 | (freq1 as u64) << 8 | freq0 as u64;
+    freq * fxosc / 1u64.rotate_left(16)
 }
 
-pub const fn to_frequency_offset(value: u8) -> i32 {
-    (((value as i8) as i64 * FXOSC as i64) / (1u64 << 14) as i64) as i32
+pub const fn from_frequency_offset(hz: i32, fxosc: u64) -> u8 {
+    let offset = (hz as i64 * (1u64 << 14) as i64) / fxosc as i64;
+    if offset > i8::MAX as i64 {
+        i8::MAX as u8
+    } else if offset < i8::MIN as i64 {
+        i8::MIN as u8
+    } else {
+        offset as i8 as u8
+    }
 }
 
-pub const fn from_deviation(v: u64) -> (u8, u8) {
-    let exponent = 64 - (v.rotate_left(14) / FXOSC).leading_zeros() - 1;
-    let mantissa = (v.rotate_left(17) / (FXOSC.rotate_left(exponent))) - 7;
-    ((mantissa & 0x7) as u8, (exponent & 0x7) as u8)
+pub const fn to_frequency_offset(value: u8, fxosc: u64) -> i32 {
+    (((value as i8) as i64 * fxosc as i64) / (1u64 << 14) as i64) as i32
+}
+
+pub const fn from_deviation(v: u64, fxosc: u64) -> (u8, u8) {
+    // Deviation is FXOSC / 2^17 * (8 + mantissa) * 2^exponent, round to the closest setting.
+    let steps = (v << 17) / fxosc;
+    let exponent = (64 - steps.leading_zeros()).saturating_sub(4);
+    let mantissa = (((v << 17) + (fxosc << exponent) / 2) / (fxosc << exponent)).saturating_sub(8);
+    // When mantissa is 8, wrap to zero and increase exponent by one
+    let (mantissa, exponent) = if mantissa == 8 {
+        (0, exponent + 1)
+    } else {
+        (mantissa, exponent)
+    };
+    if exponent > 7 {
+        (7, 7)
+    } else {
+        (mantissa as u8, exponent as u8)
+    }
 }
 
-// TODO: Not defined for all values, need to figure out.
-pub const fn from_drate(v: u64) -> (u8, u8) {
-    let exponent = 64 - (v.rotate_left(19) / FXOSC).leading_zeros();
-    let mantissa = ((v.rotate_left(27)) / (FXOSC.rotate_left(exponent - 1))) - 255;
+pub const fn to_deviation(mantissa: u8, exponent: u8, fxosc: u64) -> u64 {
+    ((((8 + (mantissa & 0x7) as u64) << (exponent & 0x7)) * fxosc) + (1 << 16)) >> 17
+}
+
+pub const fn from_drate(v: u64, fxosc: u64) -> (u8, u8) {
+    // Data rate is FXOSC / 2^28 * (256 + mantissa) * 2^exponent, round to the closest setting.
+    let steps = (v << 28) / fxosc;
+    let exponent = (64 - steps.leading_zeros()).saturating_sub(9);
+    let mantissa =
+        (((v << 28) + (fxosc << exponent) / 2) / (fxosc << exponent)).saturating_sub(256);
     // When mantissa is 256, wrap to zero and increase exponent by one
-    if mantissa == 256 {
-        (0u8, (exponent + 1) as u8)
+    let (mantissa, exponent) = if mantissa == 256 {
+        (0, exponent + 1)
+    } else {
+        (mantissa, exponent)
+    };
+    if exponent > 15 {
+        (255, 15)
     } else {
         (mantissa as u8, exponent as u8)
     }
 }
 
-pub fn from_chanbw(v: u64) -> (u8, u8) {
-    let exponent = 64 - (FXOSC / (8 * 4 * v)).leading_zeros() - 1;
-    let mantissa = FXOSC / (v * 8 * 2u64.pow(exponent)) - 4;
-    (mantissa as u8 & 0x3, exponent as u8 & 0x3)
+pub const fn to_drate(mantissa: u8, exponent: u8, fxosc: u64) -> u64 {
+    ((((256 + mantissa as u64) << (exponent & 0xf)) * fxosc) + (1 << 27)) >> 28
+}
+
+pub const fn from_chanbw(v: u64, fxosc: u64) -> (u8, u8) {
+    // Narrowest bandwidth of at least `v`, or the widest one. Settings are ordered from widest
+    // to narrowest by exponent, then mantissa.
+    let mut setting = 15;
+    while setting > 0 && to_chanbw(setting & 0x3, setting >> 2, fxosc) < v {
+        setting -= 1;
+    }
+    (setting & 0x3, setting >> 2)
+}
+
+pub const fn to_chanbw(mantissa: u8, exponent: u8, fxosc: u64) -> u64 {
+    fxosc / ((8 * (4 + (mantissa & 0x3) as u64)) << (exponent & 0x3))
+}
+
+pub const fn from_chanspc(hz: u64, fxosc: u64) -> (u8, u8) {
+    // Spacing is FXOSC / 2^18 * (256 + mantissa) * 2^exponent, round to the closest setting.
+    let mut exponent = 0;
+    let mut steps = ((hz << 18) + fxosc / 2) / fxosc;
+    while steps > 511 && exponent < 3 {
+        exponent += 1;
+        steps = ((hz << 18) + (fxosc << exponent) / 2) / (fxosc << exponent);
+    }
+    let steps = if steps < 256 {
+        256
+    } else if steps > 511 {
+        511
+    } else {
+        steps
+    };
+    ((steps - 256) as u8, exponent as u8)
 }
 
-pub fn from_freq_if(hz: u64) -> u8 {
+pub const fn to_chanspc(mantissa: u8, exponent: u8, fxosc: u64) -> u64 {
+    ((256 + mantissa as u64) << (exponent & 0x3)) * fxosc / (1 << 18)
+}
+
+pub const fn from_freq_if(hz: u64, fxosc: u64) -> u8 {
     // Round towards the closest setting, rather than down.
-    (((hz << 10) + FXOSC / 2) / FXOSC).try_into().unwrap()
+    let value = ((hz << 10) + fxosc / 2) / fxosc;
+    if value > 0x1f {
+        0x1f
+    } else {
+        value as u8
+    }
+}
+
+pub const fn to_freq_if(value: u8, fxosc: u64) -> u64 {
+    (value & 0x1f) as u64 * fxosc / (1 << 10)
 }
 
 pub fn from_rssi_to_rssi_dbm(rssi: u8) -> i16 {
@@ -66,43 +158,65 @@ mod tests {
     #[test]
     fn test_frequency() {
         // (0x10a762 * 26_000_000 / 2**16) == 432_999_816.9
-        assert_eq!(from_frequency(433_000_000), (0x62, 0xA7, 0x10));
+        assert_eq!(from_frequency(433_000_000, FXOSC), (0x62, 0xA7, 0x10));
 
         // (0x10b071 * 26_000_000 / 2**16) == 433_919_830.3
-        assert_eq!(from_frequency(433_920_000), (0x71, 0xb0, 0x10));
+        assert_eq!(from_frequency(433_920_000, FXOSC), (0x71, 0xb0, 0x10));
 
-        assert_eq!(from_frequency(868_000_000), (0x76, 0x62, 0x21));
-        assert_eq!(from_frequency(902_000_000), (0x3B, 0xB1, 0x22));
-        assert_eq!(from_frequency(918_000_000), (0xC4, 0x4E, 0x23));
+        assert_eq!(from_frequency(868_000_000, FXOSC), (0x76, 0x62, 0x21));
+        assert_eq!(from_frequency(902_000_000, FXOSC), (0x3B, 0xB1, 0x22));
+        assert_eq!(from_frequency(918_000_000, FXOSC), (0xC4, 0x4E, 0x23));
+
+        assert_eq!(to_frequency(0x71, 0xb0, 0x10, FXOSC), 433_919_830);
+        assert_eq!(to_frequency(0x76, 0x62, 0x21, FXOSC), 867_999_938);
+
+        assert_eq!(from_frequency(2_000_000_000, FXOSC), (0xFF, 0xFF, 0x3F));
+
+        // 27 MHz crystal.
+        assert_eq!(from_frequency(868_000_000, 27_000_000), (0xED, 0x25, 0x20));
+        assert_eq!(to_frequency(0xED, 0x25, 0x20, 27_000_000), 867_999_984);
     }
 
     #[test]
     fn test_frequency_offset() {
-        assert_eq!(from_frequency_offset(0), 0);
-        assert_eq!(from_frequency_offset(1586 + 1), 1);
-        assert_eq!(from_frequency_offset(201538 + 1), 127);
-        assert_eq!(from_frequency_offset(-203125 + 0), 128);
-        assert_eq!(from_frequency_offset(-1586 - 1), 255);
-
-        assert_eq!(to_frequency_offset(0), 0);
-        assert_eq!(to_frequency_offset(1), 1586);
-        assert_eq!(to_frequency_offset(127), 201538);
-        assert_eq!(to_frequency_offset(128), -203125);
-        assert_eq!(to_frequency_offset(255), -1586);
+        assert_eq!(from_frequency_offset(0, FXOSC), 0);
+        assert_eq!(from_frequency_offset(1586 + 1, FXOSC), 1);
+        assert_eq!(from_frequency_offset(201538 + 1, FXOSC), 127);
+        assert_eq!(from_frequency_offset(-203125, FXOSC), 128);
+        assert_eq!(from_frequency_offset(-1586 - 1, FXOSC), 255);
+        assert_eq!(from_frequency_offset(300_000, FXOSC), 127);
+        assert_eq!(from_frequency_offset(-300_000, FXOSC), 128);
+
+        assert_eq!(to_frequency_offset(0, FXOSC), 0);
+        assert_eq!(to_frequency_offset(1, FXOSC), 1586);
+        assert_eq!(to_frequency_offset(127, FXOSC), 201538);
+        assert_eq!(to_frequency_offset(128, FXOSC), -203125);
+        assert_eq!(to_frequency_offset(255, FXOSC), -1586);
     }
 
     #[test]
     fn test_deviation() {
         // f_dev = f_osc / 2^17 * (8 + DEVIATION_M) * 2^DEVIATION_E
         fn calc_rev_dev(dev_m: u8, dev_e: u8) -> u64 {
-            (((FXOSC as f32 / (2u64.pow(17) as f32)) as f32)
+            ((FXOSC as f32 / (2u64.pow(17) as f32))
                 * (8f32 + dev_m as f32)
                 * (2u64.pow(dev_e as u32) as f32)) as u64
         }
 
         for e in 0..7 {
             for m in 1..7 {
-                assert_eq!(from_deviation(calc_rev_dev(m, e)), (m, e));
+                assert_eq!(from_deviation(calc_rev_dev(m, e), FXOSC), (m, e));
+            }
+        }
+
+        // Reset value, 47.607 kHz.
+        assert_eq!(to_deviation(7, 4, FXOSC), 47607);
+        assert_eq!(from_deviation(47_607, FXOSC), (7, 4));
+        assert_eq!(from_deviation(0, FXOSC), (0, 0));
+        assert_eq!(from_deviation(1_000_000, FXOSC), (7, 7));
+        for e in 0..8 {
+            for m in 0..8 {
+                assert_eq!(from_deviation(to_deviation(m, e, FXOSC), FXOSC), (m, e));
             }
         }
     }
@@ -110,60 +224,96 @@ mod tests {
     #[test]
     fn test_drate() {
         // Some sample settings from SmartRF Studio
-        assert_eq!((117, 5), from_drate(1156));
-        assert_eq!((117, 7), from_drate(4624));
-        assert_eq!((117, 10), from_drate(36994));
-        assert_eq!((34, 12), from_drate(115051));
-        assert_eq!((59, 14), from_drate(499877));
-        assert_eq!((59, 13), from_drate(249938));
-        assert_eq!((248, 11), from_drate(99975));
-        assert_eq!((131, 11), from_drate(76766));
-        assert_eq!((131, 10), from_drate(38383));
-        assert_eq!((147, 8), from_drate(9992));
-        assert_eq!((131, 7), from_drate(4797));
-        assert_eq!((131, 6), from_drate(2398));
-        assert_eq!((131, 5), from_drate(1199));
-
-        /* TODO: make this work
-        fn calc_drate_rev(mantissa: u8, exponent: u8) -> u64 {
-            let q = (256.0 + mantissa as f64) * 2f64.powf(exponent as f64);
-            let p = 2f64.powf(28.0);
-            ((q / p) * FXOSC as f64).floor() as u64
+        assert_eq!((117, 5), from_drate(1156, FXOSC));
+        assert_eq!((117, 7), from_drate(4624, FXOSC));
+        assert_eq!((117, 10), from_drate(36994, FXOSC));
+        assert_eq!((34, 12), from_drate(115051, FXOSC));
+        assert_eq!((59, 14), from_drate(499877, FXOSC));
+        assert_eq!((59, 13), from_drate(249938, FXOSC));
+        assert_eq!((248, 11), from_drate(99975, FXOSC));
+        assert_eq!((131, 11), from_drate(76766, FXOSC));
+        assert_eq!((131, 10), from_drate(38383, FXOSC));
+        assert_eq!((147, 8), from_drate(9992, FXOSC));
+        assert_eq!((131, 7), from_drate(4797, FXOSC));
+        assert_eq!((131, 6), from_drate(2398, FXOSC));
+        assert_eq!((131, 5), from_drate(1199, FXOSC));
+
+        for (mantissa, exponent) in [(0x22, 12), (0x83, 5), (0, 15)] {
+            assert_eq!(
+                from_drate(to_drate(mantissa, exponent, FXOSC), FXOSC),
+                (mantissa, exponent)
+            );
         }
-        for e in 0..255 {
-            for m in 0..255 {
-                let baud = calc_drate_rev(m, e);
-                let (mp, ep) = from_drate(baud);
-                assert_eq!((mp, ep), (m as u64, e as u64));
+        assert_eq!(to_drate(0x22, 12, FXOSC), 115051);
+        assert_eq!(to_drate(0x83, 5, FXOSC), 1199);
+        assert_eq!(from_drate(38_400, 27_000_000), (0x75, 10));
+        assert_eq!(to_drate(0x75, 10, 27_000_000), 38_418);
+
+        assert_eq!(from_drate(0, FXOSC), (0, 0));
+        assert_eq!(from_drate(2_000_000, FXOSC), (255, 15));
+
+        // Below an exponent of 4, settings are less than one baud apart.
+        for exponent in 4..16 {
+            for mantissa in 0..=255 {
+                assert_eq!(
+                    from_drate(to_drate(mantissa, exponent, FXOSC), FXOSC),
+                    (mantissa, exponent)
+                );
             }
         }
-        */
     }
 
     #[test]
     fn test_chanbw() {
-        assert_eq!(from_chanbw(812500), (0b00, 0b00));
-        assert_eq!(from_chanbw(650000), (0b01, 0b00));
-        assert_eq!(from_chanbw(541666), (0b10, 0b00));
-        assert_eq!(from_chanbw(464285), (0b11, 0b00));
-        assert_eq!(from_chanbw(406250), (0b00, 0b01));
-        assert_eq!(from_chanbw(325000), (0b01, 0b01));
-        assert_eq!(from_chanbw(270833), (0b10, 0b01));
-        assert_eq!(from_chanbw(232142), (0b11, 0b01));
-        assert_eq!(from_chanbw(203125), (0b00, 0b10));
-        assert_eq!(from_chanbw(162000), (0b01, 0b10));
-        assert_eq!(from_chanbw(135416), (0b10, 0b10));
-        assert_eq!(from_chanbw(116071), (0b11, 0b10));
-        assert_eq!(from_chanbw(101562), (0b00, 0b11));
-        assert_eq!(from_chanbw(81250), (0b01, 0b11));
-        assert_eq!(from_chanbw(67708), (0b10, 0b11));
-        assert_eq!(from_chanbw(58035), (0b11, 0b11));
+        assert_eq!(from_chanbw(812500, FXOSC), (0b00, 0b00));
+        assert_eq!(from_chanbw(650000, FXOSC), (0b01, 0b00));
+        assert_eq!(from_chanbw(541666, FXOSC), (0b10, 0b00));
+        assert_eq!(from_chanbw(464285, FXOSC), (0b11, 0b00));
+        assert_eq!(from_chanbw(406250, FXOSC), (0b00, 0b01));
+        assert_eq!(from_chanbw(325000, FXOSC), (0b01, 0b01));
+        assert_eq!(from_chanbw(270833, FXOSC), (0b10, 0b01));
+        assert_eq!(from_chanbw(232142, FXOSC), (0b11, 0b01));
+        assert_eq!(from_chanbw(203125, FXOSC), (0b00, 0b10));
+        assert_eq!(from_chanbw(162000, FXOSC), (0b01, 0b10));
+        assert_eq!(from_chanbw(135416, FXOSC), (0b10, 0b10));
+        assert_eq!(from_chanbw(116071, FXOSC), (0b11, 0b10));
+        assert_eq!(from_chanbw(101562, FXOSC), (0b00, 0b11));
+        assert_eq!(from_chanbw(81250, FXOSC), (0b01, 0b11));
+        assert_eq!(from_chanbw(67708, FXOSC), (0b10, 0b11));
+        assert_eq!(from_chanbw(58035, FXOSC), (0b11, 0b11));
+
+        assert_eq!(to_chanbw(0b00, 0b10, FXOSC), 203125);
+        assert_eq!(from_chanbw(0, FXOSC), (0b11, 0b11));
+        assert_eq!(from_chanbw(1_000_000, FXOSC), (0b00, 0b00));
+        assert_eq!(to_chanbw(0b11, 0b11, FXOSC), 58035);
+        for e in 0..4 {
+            for m in 0..4 {
+                assert_eq!(from_chanbw(to_chanbw(m, e, FXOSC), FXOSC), (m, e));
+            }
+        }
+    }
+
+    #[test]
+    fn test_chanspc() {
+        // Reset value, 199.951 kHz.
+        assert_eq!(from_chanspc(200_000, FXOSC), (0xF8, 2));
+        assert_eq!(to_chanspc(0xF8, 2, FXOSC), 199_951);
+        assert_eq!(from_chanspc(25_000, FXOSC), (0x00, 0));
+        assert_eq!(from_chanspc(50_000, FXOSC), (0xF8, 0));
+        assert_eq!(to_chanspc(0xF8, 0, FXOSC), 49_987);
+        assert_eq!(from_chanspc(1_000_000, FXOSC), (0xFF, 3));
     }
 
     #[test]
     fn test_freq_if() {
-        assert_eq!(from_freq_if(381_000), 0x0F);
-        assert_eq!(from_freq_if(203_125), 0x08);
-        assert_eq!(from_freq_if(152_300), 0x06);
+        assert_eq!(from_freq_if(381_000, FXOSC), 0x0F);
+        assert_eq!(from_freq_if(203_125, FXOSC), 0x08);
+        assert_eq!(from_freq_if(152_300, FXOSC), 0x06);
+
+        assert_eq!(to_freq_if(0x0F, FXOSC), 380_859);
+        assert_eq!(from_freq_if(1_000_000, FXOSC), 0x1F);
+        for value in 0..32 {
+            assert_eq!(from_freq_if(to_freq_if(value, FXOSC), FXOSC), value);
+        }
     }
 }
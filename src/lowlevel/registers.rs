@@ -92,6 +92,14 @@ macro_rules! bitfields {
                 bitfields!(@readable $NAME, $addr, $rd);
                 bitfields!(@writable $NAME, $addr, $reset, $wr);
             )*
+
+            /// Reset values of all registers in this category, in declaration order.
+            #[allow(dead_code)]
+            pub(crate) const RESET: &[u8] = &[$($reset),*];
+
+            /// Names of all registers in this category, in declaration order.
+            #[allow(dead_code)]
+            pub(crate) const NAMES: &[&str] = &[$(stringify!($NAME)),*];
         }
     };
 
@@ -1,11 +1,22 @@
 mod address_check;
+mod agc_freeze;
 mod agc_lna_priority;
 mod auto_calibration;
+mod bs_limit;
+mod bs_post_ki;
+mod bs_post_kp;
+mod bs_pre_ki;
+mod bs_pre_kp;
+mod carrier_sense_absolute_threshold;
 mod carrier_sense_relative_threshold;
 mod cca_mode;
 mod fifo_threshold;
 mod filter_length;
+mod foc_limit;
+mod foc_post_k;
+mod foc_pre_k;
 mod gdo_cfg;
+mod hyst_level;
 mod length_config;
 mod machine_state;
 mod max_dvga_gain;
@@ -15,17 +26,33 @@ mod num_preamble;
 mod packet_format;
 mod packet_status;
 mod po_timeout;
+mod preamble_quality_threshold;
+mod rx_attenuation;
+mod rxoff_mode;
 mod sync_check;
 mod target_amplitude;
+mod txoff_mode;
+mod wait_time;
 
 pub use self::address_check::*;
+pub use self::agc_freeze::*;
 pub use self::agc_lna_priority::*;
 pub use self::auto_calibration::*;
+pub use self::bs_limit::*;
+pub use self::bs_post_ki::*;
+pub use self::bs_post_kp::*;
+pub use self::bs_pre_ki::*;
+pub use self::bs_pre_kp::*;
+pub use self::carrier_sense_absolute_threshold::*;
 pub use self::carrier_sense_relative_threshold::*;
 pub use self::cca_mode::*;
 pub use self::fifo_threshold::*;
 pub use self::filter_length::*;
+pub use self::foc_limit::*;
+pub use self::foc_post_k::*;
+pub use self::foc_pre_k::*;
 pub use self::gdo_cfg::*;
+pub use self::hyst_level::*;
 pub use self::length_config::*;
 pub use self::machine_state::*;
 pub use self::max_dvga_gain::*;
@@ -35,5 +62,10 @@ pub use self::num_preamble::*;
 pub use self::packet_format::*;
 pub use self::packet_status::*;
 pub use self::po_timeout::*;
+pub use self::preamble_quality_threshold::*;
+pub use self::rx_attenuation::*;
+pub use self::rxoff_mode::*;
 pub use self::sync_check::*;
 pub use self::target_amplitude::*;
+pub use self::txoff_mode::*;
+pub use self::wait_time::*;
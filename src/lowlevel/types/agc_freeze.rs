@@ -0,0 +1,21 @@
+/// When to freeze the AGC gain.
+/// CC1101 datasheet p. 87
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum AgcFreeze {
+    /// Normal operation. Always adjust gain when required.
+    Normal = 0,
+    /// The gain setting is frozen when a sync word has been found.
+    OnSyncWord = 1,
+    /// Manually freeze the analogue gain setting and continue to adjust the digital gain.
+    AnalogOnly = 2,
+    /// Manually freeze both the analogue and the digital gain setting.
+    All = 3,
+}
+
+impl From<AgcFreeze> for u8 {
+    fn from(value: AgcFreeze) -> Self {
+        value as Self
+    }
+}
@@ -0,0 +1,21 @@
+/// Saturation point for the data rate offset compensation algorithm.
+/// CC1101 datasheet p. 84
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum BsLimit {
+    /// ±0 (no data rate offset compensation)
+    Disabled = 0,
+    /// ±3.125 % data rate offset
+    Percent3_125 = 1,
+    /// ±6.25 % data rate offset
+    Percent6_25 = 2,
+    /// ±12.5 % data rate offset
+    Percent12_5 = 3,
+}
+
+impl From<BsLimit> for u8 {
+    fn from(value: BsLimit) -> Self {
+        value as Self
+    }
+}
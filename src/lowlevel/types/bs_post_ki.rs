@@ -0,0 +1,17 @@
+/// Clock recovery loop integral gain after a sync word is detected.
+/// CC1101 datasheet p. 84
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum BsPostKi {
+    /// Same as `BS_PRE_KI`
+    SameAsPreKi = 0,
+    /// KI/2
+    HalfKi = 1,
+}
+
+impl From<BsPostKi> for u8 {
+    fn from(value: BsPostKi) -> Self {
+        value as Self
+    }
+}
@@ -0,0 +1,17 @@
+/// Clock recovery loop proportional gain after a sync word is detected.
+/// CC1101 datasheet p. 84
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum BsPostKp {
+    /// Same as `BS_PRE_KP`
+    SameAsPreKp = 0,
+    /// KP
+    Kp = 1,
+}
+
+impl From<BsPostKp> for u8 {
+    fn from(value: BsPostKp) -> Self {
+        value as Self
+    }
+}
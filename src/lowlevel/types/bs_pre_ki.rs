@@ -0,0 +1,21 @@
+/// Clock recovery loop integral gain before a sync word is detected.
+/// CC1101 datasheet p. 84
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum BsPreKi {
+    /// KI
+    Ki1 = 0,
+    /// 2KI
+    Ki2 = 1,
+    /// 3KI
+    Ki3 = 2,
+    /// 4KI
+    Ki4 = 3,
+}
+
+impl From<BsPreKi> for u8 {
+    fn from(value: BsPreKi) -> Self {
+        value as Self
+    }
+}
@@ -0,0 +1,21 @@
+/// Clock recovery loop proportional gain before a sync word is detected.
+/// CC1101 datasheet p. 84
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum BsPreKp {
+    /// KP
+    Kp1 = 0,
+    /// 2KP
+    Kp2 = 1,
+    /// 3KP
+    Kp3 = 2,
+    /// 4KP
+    Kp4 = 3,
+}
+
+impl From<BsPreKp> for u8 {
+    fn from(value: BsPreKp) -> Self {
+        value as Self
+    }
+}
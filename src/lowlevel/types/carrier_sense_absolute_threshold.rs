@@ -0,0 +1,43 @@
+/// Absolute carrier sense threshold, relative to `AGCCTRL2.MAGN_TARGET`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum CarrierSenseAbsoluteThreshold {
+    /// Absolute carrier sense threshold disabled.
+    Disabled = 0b1000,
+    /// 7 dB below `MAGN_TARGET`
+    DbMinus7 = 0b1001,
+    /// 6 dB below `MAGN_TARGET`
+    DbMinus6 = 0b1010,
+    /// 5 dB below `MAGN_TARGET`
+    DbMinus5 = 0b1011,
+    /// 4 dB below `MAGN_TARGET`
+    DbMinus4 = 0b1100,
+    /// 3 dB below `MAGN_TARGET`
+    DbMinus3 = 0b1101,
+    /// 2 dB below `MAGN_TARGET`
+    DbMinus2 = 0b1110,
+    /// 1 dB below `MAGN_TARGET`
+    DbMinus1 = 0b1111,
+    /// At `MAGN_TARGET`
+    Db0 = 0,
+    /// 1 dB above `MAGN_TARGET`
+    Db1 = 1,
+    /// 2 dB above `MAGN_TARGET`
+    Db2 = 2,
+    /// 3 dB above `MAGN_TARGET`
+    Db3 = 3,
+    /// 4 dB above `MAGN_TARGET`
+    Db4 = 4,
+    /// 5 dB above `MAGN_TARGET`
+    Db5 = 5,
+    /// 6 dB above `MAGN_TARGET`
+    Db6 = 6,
+    /// 7 dB above `MAGN_TARGET`
+    Db7 = 7,
+}
+
+impl From<CarrierSenseAbsoluteThreshold> for u8 {
+    fn from(value: CarrierSenseAbsoluteThreshold) -> Self {
+        value as Self
+    }
+}
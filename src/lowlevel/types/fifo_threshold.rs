@@ -42,3 +42,15 @@ impl From<FifoThreshold> for u8 {
         value as Self
     }
 }
+
+impl FifoThreshold {
+    /// Number of bytes in the TX FIFO at which the threshold is reached.
+    pub const fn tx_bytes(self) -> u8 {
+        61 - 4 * self as u8
+    }
+
+    /// Number of bytes in the RX FIFO at which the threshold is reached.
+    pub const fn rx_bytes(self) -> u8 {
+        4 * (self as u8 + 1)
+    }
+}
@@ -0,0 +1,19 @@
+/// Saturation point for the frequency offset compensation algorithm.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum FocLimit {
+    /// ±0 (no frequency offset compensation)
+    Disabled = 0,
+    /// ±channel bandwidth/8
+    ChanBwDiv8 = 1,
+    /// ±channel bandwidth/4
+    ChanBwDiv4 = 2,
+    /// ±channel bandwidth/2
+    ChanBwDiv2 = 3,
+}
+
+impl From<FocLimit> for u8 {
+    fn from(value: FocLimit) -> Self {
+        value as Self
+    }
+}
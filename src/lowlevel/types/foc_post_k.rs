@@ -0,0 +1,15 @@
+/// Frequency compensation loop gain after a sync word is detected.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum FocPostK {
+    /// Same as `FOC_PRE_K`
+    SameAsPreK = 0,
+    /// K/2
+    HalfK = 1,
+}
+
+impl From<FocPostK> for u8 {
+    fn from(value: FocPostK) -> Self {
+        value as Self
+    }
+}
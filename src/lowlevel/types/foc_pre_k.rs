@@ -0,0 +1,19 @@
+/// Frequency compensation loop gain before a sync word is detected.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum FocPreK {
+    /// K
+    K1 = 0,
+    /// 2K
+    K2 = 1,
+    /// 3K
+    K3 = 2,
+    /// 4K
+    K4 = 3,
+}
+
+impl From<FocPreK> for u8 {
+    fn from(value: FocPreK) -> Self {
+        value as Self
+    }
+}
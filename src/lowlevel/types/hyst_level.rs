@@ -0,0 +1,21 @@
+/// Level of hysteresis on the magnitude deviation of the AGC.
+/// CC1101 datasheet p. 87
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum HystLevel {
+    /// No hysteresis, small symmetric dead zone, high gain
+    None = 0,
+    /// Low hysteresis, small asymmetric dead zone, medium gain
+    Low = 1,
+    /// Medium hysteresis, medium asymmetric dead zone, medium gain
+    Medium = 2,
+    /// Large hysteresis, large asymmetric dead zone, low gain
+    Large = 3,
+}
+
+impl From<HystLevel> for u8 {
+    fn from(value: HystLevel) -> Self {
+        value as Self
+    }
+}
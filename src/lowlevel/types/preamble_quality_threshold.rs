@@ -0,0 +1,30 @@
+/// Preamble quality threshold, `PKTCTRL1.PQT`.
+///
+/// A sync word is only accepted once the preamble quality estimator has counted this many more
+/// good bits than bad ones.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum PreambleQualityThreshold {
+    /// Accept any sync word.
+    Disabled = 0,
+    /// 4 bits
+    Pqi4 = 1,
+    /// 8 bits
+    Pqi8 = 2,
+    /// 12 bits
+    Pqi12 = 3,
+    /// 16 bits
+    Pqi16 = 4,
+    /// 20 bits
+    Pqi20 = 5,
+    /// 24 bits
+    Pqi24 = 6,
+    /// 28 bits
+    Pqi28 = 7,
+}
+
+impl From<PreambleQualityThreshold> for u8 {
+    fn from(value: PreambleQualityThreshold) -> Self {
+        value as Self
+    }
+}
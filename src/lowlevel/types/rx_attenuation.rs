@@ -0,0 +1,21 @@
+/// RX attenuation for reception of strong signals, e.g. from a nearby transmitter.
+/// CC1101 datasheet p. 72, see also DN010 "Close-in Reception"
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum RxAttenuation {
+    /// 0 dB
+    Db0 = 0,
+    /// 6 dB
+    Db6 = 1,
+    /// 12 dB
+    Db12 = 2,
+    /// 18 dB
+    Db18 = 3,
+}
+
+impl From<RxAttenuation> for u8 {
+    fn from(value: RxAttenuation) -> Self {
+        value as Self
+    }
+}
@@ -0,0 +1,21 @@
+/// What should happen when a packet has been received.
+/// CC1101 datasheet p. 81
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum RxOffMode {
+    /// Go to IDLE
+    Idle = 0,
+    /// Go to FSTXON, with the frequency synthesizer on and ready for TX
+    Fstxon = 1,
+    /// Go to TX, e.g. to send an acknowledgement already in the TX FIFO
+    Tx = 2,
+    /// Stay in RX
+    StayInRx = 3,
+}
+
+impl From<RxOffMode> for u8 {
+    fn from(value: RxOffMode) -> Self {
+        value as Self
+    }
+}
@@ -0,0 +1,21 @@
+/// What should happen when a packet has been sent.
+/// CC1101 datasheet p. 81
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum TxOffMode {
+    /// Go to IDLE
+    Idle = 0,
+    /// Go to FSTXON, with the frequency synthesizer on and ready for TX
+    Fstxon = 1,
+    /// Stay in TX, sending preamble until the next packet is written to the TX FIFO
+    StayInTx = 2,
+    /// Go to RX, e.g. to wait for an acknowledgement
+    Rx = 3,
+}
+
+impl From<TxOffMode> for u8 {
+    fn from(value: TxOffMode) -> Self {
+        value as Self
+    }
+}
@@ -0,0 +1,21 @@
+/// Channel filter samples after a gain adjustment before the AGC accumulates new samples.
+/// CC1101 datasheet p. 87
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum WaitTime {
+    /// 8 samples
+    Samples8 = 0,
+    /// 16 samples
+    Samples16 = 1,
+    /// 24 samples
+    Samples24 = 2,
+    /// 32 samples
+    Samples32 = 3,
+}
+
+impl From<WaitTime> for u8 {
+    fn from(value: WaitTime) -> Self {
+        value as Self
+    }
+}
@@ -0,0 +1,142 @@
+//! Output power and PATABLE programming.
+//!
+//! The PA output power is set by the PATABLE, an 8-byte table of PA settings. `FREND0.PA_POWER`
+//! selects the entry used when sending a '1'; the entries below it are used for ramping the
+//! power up and down in ASK/OOK, while a '0' in OOK is sent with entry 0. In FSK modes only
+//! entry 0 is used.
+
+use hal::spi::SpiDevice;
+
+use crate::lowlevel::registers::*;
+use crate::{Cc1101, Error, ModulationFormat, UserError};
+
+/// Number of entries in the PATABLE.
+pub const PATABLE_SIZE: usize = 8;
+
+/// Output power levels of the data sheet lookup tables, in dBm.
+const OUTPUT_POWER_DBM: [i8; 8] = [-30, -20, -15, -10, 0, 5, 7, 10];
+
+/// Table 39 "Optimum PATABLE Settings for Various Output Power Levels and Frequency Bands" of
+/// the data sheet (TI document SWRS061I), per entry of [`OUTPUT_POWER_DBM`].
+const PATABLE_315: [u8; 8] = [0x12, 0x0D, 0x1C, 0x34, 0x51, 0x85, 0xCB, 0xC2];
+const PATABLE_433: [u8; 8] = [0x12, 0x0E, 0x1D, 0x34, 0x60, 0x84, 0xC8, 0xC0];
+const PATABLE_868: [u8; 8] = [0x03, 0x0F, 0x1E, 0x27, 0x50, 0x81, 0xCB, 0xC2];
+const PATABLE_915: [u8; 8] = [0x03, 0x0E, 0x1E, 0x27, 0x8E, 0xCD, 0xC7, 0xC0];
+
+/// Frequency band with an output power lookup table.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PowerBand {
+    Mhz315,
+    Mhz433,
+    Mhz868,
+    Mhz915,
+}
+
+impl PowerBand {
+    /// Band whose lookup table is closest to the carrier frequency `hz`.
+    pub fn from_frequency(hz: u64) -> Self {
+        match hz / 1_000_000 {
+            0..=373 => PowerBand::Mhz315,
+            374..=650 => PowerBand::Mhz433,
+            651..=890 => PowerBand::Mhz868,
+            _ => PowerBand::Mhz915,
+        }
+    }
+
+    /// PATABLE setting for the highest output power not above `dbm`, and that output power.
+    ///
+    /// Powers below the lowest table entry get the lowest entry.
+    pub fn pa_setting(self, dbm: i8) -> (u8, i8) {
+        let table = match self {
+            PowerBand::Mhz315 => &PATABLE_315,
+            PowerBand::Mhz433 => &PATABLE_433,
+            PowerBand::Mhz868 => &PATABLE_868,
+            PowerBand::Mhz915 => &PATABLE_915,
+        };
+        let index = OUTPUT_POWER_DBM.iter().rposition(|&p| p <= dbm).unwrap_or(0);
+        (table[index], OUTPUT_POWER_DBM[index])
+    }
+}
+
+/// Check that `table` fits the PATABLE, returning the `FREND0.PA_POWER` index of its last entry.
+pub(crate) fn pa_power_index(table: &[u8]) -> Result<u8, UserError> {
+    match table.len() {
+        0 => Err(UserError::BufferTooSmall(1)),
+        len if len > PATABLE_SIZE => Err(UserError::ArrayTooLong(len)),
+        len => Ok(len as u8 - 1),
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Set the output power, using the lookup table for the currently programmed frequency.
+    ///
+    /// Selects the highest output power not above `dbm`, and returns it. In ASK/OOK a '0' is
+    /// sent with the PA off. Set the frequency and modulation format first.
+    pub fn set_output_power_dbm(&mut self, dbm: i8) -> Result<i8, Error<SpiE>> {
+        let band = PowerBand::from_frequency(self.get_frequency()?);
+        let (setting, actual) = band.pa_setting(dbm);
+
+        let mod_format = self.0.read_register(config::MDMCFG2)?.mod_format();
+        if mod_format == ModulationFormat::AmplitudeShiftOnOffKeying as u8 {
+            self.set_patable(&[0x00, setting])?;
+        } else {
+            self.set_patable(&[setting])?;
+        }
+        Ok(actual)
+    }
+
+    /// Write the first `table.len()` (1 to 8) PATABLE entries, and use the last one for
+    /// sending a '1' (`FREND0.PA_POWER`).
+    pub fn set_patable(&mut self, table: &[u8]) -> Result<(), Error<SpiE>> {
+        let pa_power = pa_power_index(table).map_err(Error::UserInputError)?;
+        self.0.write_burst(multi::PATABLE, table)?;
+        self.0.modify_register(config::FREND0, |r| r.pa_power(pa_power))?;
+        Ok(())
+    }
+
+    /// Read all PATABLE entries.
+    pub fn get_patable(&mut self) -> Result<[u8; PATABLE_SIZE], Error<SpiE>> {
+        let mut table = [0; PATABLE_SIZE];
+        self.0.read_burst(multi::PATABLE, &mut table)?;
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChip, SimSpi};
+    use core::cell::RefCell;
+
+    #[test]
+    fn test_pa_setting() {
+        assert_eq!(PowerBand::from_frequency(433_920_000).pa_setting(8), (0xC8, 7));
+        assert_eq!(PowerBand::from_frequency(868_300_000).pa_setting(0), (0x50, 0));
+        assert_eq!(PowerBand::from_frequency(915_000_000).pa_setting(-40), (0x03, -30));
+        assert_eq!(PowerBand::from_frequency(315_000_000).pa_setting(12), (0xC2, 10));
+    }
+
+    #[test]
+    fn test_set_output_power() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        cc1101.set_frequency(433_920_000).unwrap();
+        assert_eq!(cc1101.set_output_power_dbm(5).unwrap(), 5);
+        assert_eq!(cc1101.get_patable().unwrap()[0], 0x84);
+        assert_eq!(chip.borrow().config().read(config::FREND0).pa_power(), 0);
+
+        cc1101.set_modulation_format(ModulationFormat::AmplitudeShiftOnOffKeying).unwrap();
+        cc1101.set_output_power_dbm(10).unwrap();
+        assert_eq!(&cc1101.get_patable().unwrap()[..2], &[0x00, 0xC0]);
+        assert_eq!(chip.borrow().config().read(config::FREND0).pa_power(), 1);
+
+        assert_eq!(
+            cc1101.set_patable(&[0; 9]),
+            Err(Error::UserInputError(UserError::ArrayTooLong(9)))
+        );
+    }
+}
@@ -0,0 +1,499 @@
+//! Whole-configuration snapshot of the radio.
+
+use crate::lowlevel::convert::*;
+use crate::lowlevel::registers::*;
+use crate::lowlevel::FXOSC;
+use crate::{
+    AddressFilter, AgcFreeze, AgcLnaPriority, AgcProfile, AutoCalibration, BitSyncConfig,
+    CarrierSenseAbsoluteThreshold, CarrierSenseRelativeThreshold, CcaMode, ChannelPlan,
+    FifoThreshold, FilterLength, FocConfig, FrontEndConfig, FrontEndCurrent, GdoCfg, HystLevel,
+    MaxDvgaGain, MaxLnaGain, ModulationFormat, NumPreamble, PacketFormat, PacketLength, PoTimeout,
+    PreambleQualityThreshold, RcCalibration, ReceiverProfile, RxAttenuation, RxOffMode, RxTime,
+    RxTimeQualifier, SyncMode, TargetAmplitude, TxOffMode, WaitTime, WakeOnRadioSettings,
+};
+
+/// Number of configuration registers, `IOCFG2` (0x00) through `TEST0` (0x2E).
+pub const CONFIG_REGISTERS: usize = 0x2F;
+
+/// Values of all configuration registers, `IOCFG2` through `TEST0`.
+///
+/// Written to and read from the radio with a single burst access, see
+/// [`crate::Cc1101::apply_config`] and [`crate::Cc1101::read_config`]. The builder methods
+/// set the same fields as the corresponding `Cc1101` setters, without any SPI traffic.
+///
+/// Settings in Hertz or baud are converted for the crystal frequency of the configuration,
+/// 26 MHz unless set with [`Self::crystal`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RadioConfig {
+    regs: [u8; CONFIG_REGISTERS],
+    fxosc: u64,
+}
+
+/// A register that differs between two configurations.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RegisterDiff {
+    /// Register address.
+    pub addr: u8,
+    /// Value in the configuration compared against.
+    pub expected: u8,
+    /// Value in the configuration being compared.
+    pub actual: u8,
+}
+
+impl Default for RadioConfig {
+    /// Reset values of all configuration registers.
+    fn default() -> Self {
+        let mut regs = [0; CONFIG_REGISTERS];
+        regs.copy_from_slice(config::RESET);
+        RadioConfig::from_bytes(regs)
+    }
+}
+
+impl RadioConfig {
+    /// Build a configuration from raw register values, indexed by register address.
+    pub const fn from_bytes(regs: [u8; CONFIG_REGISTERS]) -> Self {
+        RadioConfig {
+            regs,
+            fxosc: FXOSC,
+        }
+    }
+
+    /// Raw register values, indexed by register address.
+    pub const fn as_bytes(&self) -> &[u8; CONFIG_REGISTERS] {
+        &self.regs
+    }
+
+    /// Crystal frequency (in Hertz) for the builder methods that follow. No register changes.
+    pub const fn crystal(self, fxosc: u64) -> Self {
+        RadioConfig {
+            fxosc,
+            ..self
+        }
+    }
+
+    /// Crystal frequency, in Hertz.
+    pub const fn crystal_hz(&self) -> u64 {
+        self.fxosc
+    }
+
+    /// Typed read view of a single register.
+    pub fn read<S>(&self, _reg: S) -> <S as Readable>::View
+    where
+        S: Readable + Writable,
+    {
+        <S as Readable>::view(self.regs[<S as Readable>::ADDR as usize])
+    }
+
+    /// Modify a single register through its typed write view.
+    pub fn modify<S, F>(&mut self, _reg: S, f: F)
+    where
+        S: Readable + Writable,
+        F: FnOnce(<S as Writable>::View) -> <S as Writable>::View,
+    {
+        let addr = <S as Writable>::ADDR as usize;
+        self.regs[addr] = <S as Writable>::bits(f(<S as Writable>::view(self.regs[addr])));
+    }
+
+    /// Registers whose value differs from `expected`.
+    pub fn diff<'a>(
+        &'a self,
+        expected: &'a RadioConfig,
+    ) -> impl Iterator<Item = RegisterDiff> + 'a {
+        (0..CONFIG_REGISTERS).filter(|&i| self.regs[i] != expected.regs[i]).map(|i| RegisterDiff {
+            addr: i as u8,
+            expected: expected.regs[i],
+            actual: self.regs[i],
+        })
+    }
+
+    fn with<S, F>(mut self, reg: S, f: F) -> Self
+    where
+        S: Readable + Writable,
+        F: FnOnce(<S as Writable>::View) -> <S as Writable>::View,
+    {
+        self.modify(reg, f);
+        self
+    }
+
+    /// GDO0 output pin configuration.
+    pub fn gdo0_config(self, config: GdoCfg) -> Self {
+        self.with(config::IOCFG0, |r| r.gdo0_cfg(config.into()))
+    }
+
+    /// GDO1 output pin configuration.
+    pub fn gdo1_config(self, config: GdoCfg) -> Self {
+        self.with(config::IOCFG1, |r| r.gdo1_cfg(config.into()))
+    }
+
+    /// GDO2 output pin configuration.
+    pub fn gdo2_config(self, config: GdoCfg) -> Self {
+        self.with(config::IOCFG2, |r| r.gdo2_cfg(config.into()))
+    }
+
+    /// Preamble quality threshold, see [`crate::Cc1101::set_preamble_quality_threshold`].
+    pub fn preamble_quality_threshold(self, threshold: PreambleQualityThreshold) -> Self {
+        self.with(config::PKTCTRL1, |r| r.pqt(threshold.into()))
+    }
+
+    /// Threshold for the TX FIFO and RX FIFO.
+    pub fn fifo_threshold(self, threshold: FifoThreshold) -> Self {
+        self.with(config::FIFOTHR, |r| r.fifo_thr(threshold.into()))
+    }
+
+    /// Sync word, and at what level it should be verified.
+    pub fn sync_mode(self, sync_mode: SyncMode) -> Self {
+        let (mode, word) = sync_mode.fields();
+        self.with(config::MDMCFG2, |r| r.sync_mode(mode.into()))
+            .with(config::SYNC1, |w| w.sync(((word >> 8) & 0xff) as u8))
+            .with(config::SYNC0, |w| w.sync((word & 0xff) as u8))
+    }
+
+    /// Packet mode, and length.
+    pub fn packet_length(self, length: PacketLength) -> Self {
+        let (format, pktlen) = length.fields();
+        self.with(config::PKTCTRL0, |r| r.length_config(format.into()))
+            .with(config::PKTLEN, |w| w.packet_length(pktlen))
+    }
+
+    /// Device address, and address filtering.
+    pub fn address_filter(self, filter: AddressFilter) -> Self {
+        let (mode, addr) = filter.fields();
+        self.with(config::PKTCTRL1, |r| r.adr_chk(mode.into()))
+            .with(config::ADDR, |w| w.device_addr(addr))
+    }
+
+    /// Automatic flush of RX FIFO when CRC is not OK.
+    pub fn crc_autoflush(self, enable: bool) -> Self {
+        self.with(config::PKTCTRL1, |r| r.crc_autoflush(enable as u8))
+    }
+
+    /// Append RSSI and LQI status bytes to the payload of received packets.
+    pub fn append_status(self, enable: bool) -> Self {
+        self.with(config::PKTCTRL1, |r| r.append_status(enable as u8))
+    }
+
+    /// Data whitening.
+    pub fn white_data(self, enable: bool) -> Self {
+        self.with(config::PKTCTRL0, |r| r.white_data(enable as u8))
+    }
+
+    /// Format of RX and TX data.
+    pub fn packet_format(self, format: PacketFormat) -> Self {
+        self.with(config::PKTCTRL0, |r| r.pkt_format(format.into()))
+    }
+
+    /// CRC calculation in TX and CRC check in RX.
+    pub fn crc(self, enable: bool) -> Self {
+        self.with(config::PKTCTRL0, |r| r.crc_en(enable as u8))
+    }
+
+    /// Channel number.
+    pub fn channel(self, channel: u8) -> Self {
+        self.with(config::CHANNR, |w| w.chan(channel))
+    }
+
+    /// Frequency synthesizer intermediate frequency (in Hertz).
+    pub fn freq_if(self, hz: u64) -> Self {
+        self.with(config::FSCTRL1, |r| r.freq_if(from_freq_if(hz, self.fxosc)))
+    }
+
+    /// Frequency offset added to the carrier frequency (in Hertz).
+    pub fn frequency_offset(self, hz: i32) -> Self {
+        self.with(config::FSCTRL0, |w| w.freqoff(from_frequency_offset(hz, self.fxosc)))
+    }
+
+    /// Carrier frequency (in Hertz).
+    pub fn frequency(self, hz: u64) -> Self {
+        let (freq0, freq1, freq2) = from_frequency(hz, self.fxosc);
+        self.with(config::FREQ0, |w| w.freq(freq0))
+            .with(config::FREQ1, |w| w.freq(freq1))
+            .with(config::FREQ2, |w| w.freq(freq2))
+    }
+
+    /// Base frequency and channel spacing of `plan`, with channel 0 selected.
+    pub fn channel_plan(self, plan: &ChannelPlan) -> Self {
+        let ((freq0, freq1, freq2), chanspc_m, chanspc_e) = plan.registers();
+        self.with(config::FREQ0, |w| w.freq(freq0))
+            .with(config::FREQ1, |w| w.freq(freq1))
+            .with(config::FREQ2, |w| w.freq(freq2))
+            .with(config::MDMCFG0, |w| w.chanspc_m(chanspc_m))
+            .with(config::MDMCFG1, |r| r.chanspc_e(chanspc_e))
+            .with(config::CHANNR, |w| w.chan(0))
+    }
+
+    /// Channel bandwidth (in Hertz).
+    pub fn channel_bandwidth(self, bandwidth_hz: u64) -> Self {
+        let (mantissa, exponent) = from_chanbw(bandwidth_hz, self.fxosc);
+        self.with(config::MDMCFG4, |r| r.chanbw_m(mantissa).chanbw_e(exponent))
+    }
+
+    /// Data rate (in bits per second).
+    pub fn data_rate(self, baud: u64) -> Self {
+        let (mantissa, exponent) = from_drate(baud, self.fxosc);
+        self.with(config::MDMCFG4, |r| r.drate_e(exponent))
+            .with(config::MDMCFG3, |r| r.drate_m(mantissa))
+    }
+
+    /// Modulation format of the radio signal.
+    pub fn modulation_format(self, mod_format: ModulationFormat) -> Self {
+        self.with(config::MDMCFG2, |r| r.mod_format(mod_format.into()))
+    }
+
+    /// Manchester encoding.
+    pub fn manchester_encoding(self, enable: bool) -> Self {
+        self.with(config::MDMCFG2, |r| r.manchester_en(enable as u8))
+    }
+
+    /// Forward Error Correction (FEC) with interleaving for packet payload.
+    pub fn fec(self, enable: bool) -> Self {
+        self.with(config::MDMCFG1, |r| r.fec_en(enable as u8))
+    }
+
+    /// Minimum number of preamble bytes to be transmitted.
+    pub fn num_preamble(self, num_preamble: NumPreamble) -> Self {
+        self.with(config::MDMCFG1, |r| r.num_preamble(num_preamble.into()))
+    }
+
+    /// Modem deviation (in Hertz).
+    pub fn deviation_hz(self, deviation: u64) -> Self {
+        let (mantissa, exponent) = from_deviation(deviation, self.fxosc);
+        self.with(config::DEVIATN, |r| r.deviation_m(mantissa).deviation_e(exponent))
+    }
+
+    /// Frequency offset compensation loop.
+    pub fn foc_config(self, foc: &FocConfig) -> Self {
+        self.with(config::FOCCFG, |r| {
+            r.foc_bs_cs_gate(foc.cs_gate as u8)
+                .foc_pre_k(foc.pre_k.into())
+                .foc_post_k(foc.post_k.into())
+                .foc_limit(foc.limit.into())
+        })
+    }
+
+    /// Clear channel assessment mode.
+    pub fn cca_mode(self, cca_mode: CcaMode) -> Self {
+        self.with(config::MCSM1, |r| r.cca_mode(cca_mode.into()))
+    }
+
+    /// State to enter when a packet has been received.
+    pub fn rxoff_mode(self, mode: RxOffMode) -> Self {
+        self.with(config::MCSM1, |r| r.rxoff_mode(mode.into()))
+    }
+
+    /// State to enter when a packet has been sent.
+    pub fn txoff_mode(self, mode: TxOffMode) -> Self {
+        self.with(config::MCSM1, |r| r.txoff_mode(mode.into()))
+    }
+
+    /// Timeout for the sync word search in RX, see [`crate::Cc1101::set_rx_time`].
+    pub fn rx_time(self, rx_time: RxTime) -> Self {
+        self.with(config::MCSM2, |r| r.rx_time(rx_time.into()))
+    }
+
+    /// Leave RX as soon as carrier sense is not asserted, without waiting for the RX timeout.
+    pub fn rx_time_rssi(self, enable: bool) -> Self {
+        self.with(config::MCSM2, |r| r.rx_time_rssi(enable as u8))
+    }
+
+    /// What to look for when the RX timeout expires.
+    pub fn rx_time_qualifier(self, qualifier: RxTimeQualifier) -> Self {
+        self.with(config::MCSM2, |r| r.rx_time_qual(qualifier.into()))
+    }
+
+    /// Wait after the crystal oscillator has stabilized, when leaving SLEEP.
+    pub fn po_timeout(self, timeout: PoTimeout) -> Self {
+        self.with(config::MCSM0, |r| r.po_timeout(timeout.into()))
+    }
+
+    /// Pin radio control option.
+    pub fn pin_control(self, enable: bool) -> Self {
+        self.with(config::MCSM0, |r| r.pin_ctrl_en(enable as u8))
+    }
+
+    /// Keep the crystal oscillator running in SLEEP.
+    pub fn xosc_force_on(self, enable: bool) -> Self {
+        self.with(config::MCSM0, |r| r.xosc_force_on(enable as u8))
+    }
+
+    /// When to run automatic calibration.
+    pub fn autocalibration(self, autocal: AutoCalibration) -> Self {
+        self.with(config::MCSM0, |r| r.fs_autocal(autocal.into()))
+    }
+
+    /// Target value for the averaged amplitude from the digital channel filter.
+    pub fn magn_target(self, target: TargetAmplitude) -> Self {
+        self.with(config::AGCCTRL2, |r| r.magn_target(target.into()))
+    }
+
+    /// Maximum allowable DVGA gain.
+    pub fn max_dvga_gain(self, gain: MaxDvgaGain) -> Self {
+        self.with(config::AGCCTRL2, |r| r.max_dvga_gain(gain.into()))
+    }
+
+    /// Maximum allowable `LNA + LNA2` gain.
+    pub fn max_lna_gain(self, gain: MaxLnaGain) -> Self {
+        self.with(config::AGCCTRL2, |r| r.max_lna_gain(gain.into()))
+    }
+
+    /// AGC gain-reduction priority between `LNA` and `LNA2`.
+    pub fn agc_lna_priority(self, priority: AgcLnaPriority) -> Self {
+        self.with(config::AGCCTRL1, |r| r.agc_lna_priority(priority.into()))
+    }
+
+    /// Relative RSSI increase threshold for asserting carrier sense.
+    pub fn carrier_sense_relative_threshold(
+        self,
+        threshold: CarrierSenseRelativeThreshold,
+    ) -> Self {
+        self.with(config::AGCCTRL1, |r| r.carrier_sense_rel_thr(threshold.into()))
+    }
+
+    /// Absolute RSSI threshold for asserting carrier sense, see
+    /// [`crate::Cc1101::set_carrier_sense_threshold`].
+    pub fn carrier_sense_threshold(self, threshold: CarrierSenseAbsoluteThreshold) -> Self {
+        self.with(config::AGCCTRL1, |r| r.carrier_sense_abs_thr(threshold.into()))
+    }
+
+    /// Filter length (in FSK/MSK mode) or decision boundary (in OOK/ASK mode) for the AGC.
+    pub fn filter_length(self, filter_length: FilterLength) -> Self {
+        self.with(config::AGCCTRL0, |r| r.filter_length(filter_length.into()))
+    }
+
+    /// Level of hysteresis on the magnitude deviation of the AGC.
+    pub fn agc_hysteresis(self, level: HystLevel) -> Self {
+        self.with(config::AGCCTRL0, |r| r.hyst_level(level.into()))
+    }
+
+    /// Channel filter samples the AGC waits after a gain adjustment.
+    pub fn agc_wait_time(self, wait_time: WaitTime) -> Self {
+        self.with(config::AGCCTRL0, |r| r.wait_time(wait_time.into()))
+    }
+
+    /// When the AGC gain is frozen.
+    pub fn agc_freeze(self, freeze: AgcFreeze) -> Self {
+        self.with(config::AGCCTRL0, |r| r.agc_freeze(freeze.into()))
+    }
+
+    /// Every field of the AGC, see [`crate::Cc1101::set_agc_profile`].
+    pub fn agc_profile(self, profile: &AgcProfile) -> Self {
+        self.with(config::AGCCTRL2, |r| {
+            r.max_dvga_gain(profile.max_dvga_gain.into())
+                .max_lna_gain(profile.max_lna_gain.into())
+                .magn_target(profile.magn_target.into())
+        })
+        .with(config::AGCCTRL1, |r| {
+            r.agc_lna_priority(profile.lna_priority.into())
+                .carrier_sense_rel_thr(profile.carrier_sense_relative_threshold.into())
+                .carrier_sense_abs_thr(profile.carrier_sense_threshold.into())
+        })
+        .with(config::AGCCTRL0, |r| {
+            r.hyst_level(profile.hysteresis.into())
+                .wait_time(profile.wait_time.into())
+                .agc_freeze(profile.freeze.into())
+                .filter_length(profile.filter_length.into())
+        })
+    }
+
+    /// Currents of the RX front end.
+    pub fn front_end(self, front_end: &FrontEndConfig) -> Self {
+        self.with(config::FREND1, |r| {
+            r.lna_current(front_end.lna_current.into())
+                .lna2mix_current(front_end.lna2mix_current.into())
+                .lodiv_buf_current_rx(front_end.lodiv_buf_current.into())
+                .mix_current(front_end.mix_current.into())
+        })
+    }
+
+    /// Current of the TX LO buffer (input to the PA).
+    pub fn tx_lo_buffer_current(self, current: FrontEndCurrent) -> Self {
+        self.with(config::FREND0, |r| r.lodiv_buf_current_tx(current.into()))
+    }
+
+    /// RX attenuation, for links where the other side can be very close.
+    pub fn rx_attenuation(self, attenuation: RxAttenuation) -> Self {
+        self.with(config::FIFOTHR, |r| r.close_in_rx(attenuation.into()))
+    }
+
+    /// All settings of a receiver profile, see [`crate::Cc1101::set_receiver_profile`].
+    pub fn receiver_profile(self, profile: &ReceiverProfile) -> Self {
+        self.front_end(&profile.front_end)
+            .with(config::TEST2, |w| w.test2(profile.test2))
+            .with(config::TEST1, |w| w.test1(profile.test1))
+            .with(config::FIFOTHR, |r| {
+                r.adc_retention(profile.adc_retention as u8)
+                    .close_in_rx(profile.rx_attenuation.into())
+            })
+            .agc_profile(&profile.agc)
+    }
+
+    /// Clock recovery loop.
+    pub fn bit_sync(self, bit_sync: &BitSyncConfig) -> Self {
+        self.with(config::BSCFG, |r| {
+            r.bs_pre_ki(bit_sync.pre_ki.into())
+                .bs_pre_kp(bit_sync.pre_kp.into())
+                .bs_post_ki(bit_sync.post_ki.into())
+                .bs_post_kp(bit_sync.post_kp.into())
+                .bs_limit(bit_sync.limit.into())
+        })
+    }
+
+    /// Wake-on-Radio timing, see [`crate::Cc1101::set_wake_on_radio`].
+    pub fn wake_on_radio(self, settings: &WakeOnRadioSettings) -> Self {
+        let config = self
+            .with(config::MCSM2, |r| {
+                r.rx_time_rssi(settings.rx_time_rssi as u8)
+                    .rx_time_qual(settings.rx_time_qual.into())
+                    .rx_time(settings.rx_time)
+            })
+            .with(config::WOREVT1, |w| w.event((settings.event0 >> 8) as u8))
+            .with(config::WOREVT0, |w| w.event((settings.event0 & 0xff) as u8))
+            .with(config::WORCTRL, |r| {
+                r.rc_pd(0)
+                    .event(settings.event1)
+                    .rc_cal((settings.rc_calibration == RcCalibration::Automatic) as u8)
+                    .wor_res(settings.wor_res)
+            });
+        match settings.rc_calibration {
+            RcCalibration::Automatic => config,
+            RcCalibration::Manual {
+                rcctrl1,
+                rcctrl0,
+            } => config
+                .with(config::RCCTRL1, |w| w.rcctrl1(rcctrl1))
+                .with(config::RCCTRL0, |w| w.rcctrl0(rcctrl0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lowlevel::registers::*;
+    use crate::*;
+
+    #[test]
+    fn test_default_is_reset() {
+        let config = RadioConfig::default();
+        assert_eq!(config.as_bytes()[0x00], 0x29); // IOCFG2
+        assert_eq!(config.as_bytes()[0x0D], 0x1E); // FREQ2
+        assert_eq!(config.as_bytes()[0x2E], 0x0B); // TEST0
+    }
+
+    #[test]
+    fn test_builder() {
+        let config = RadioConfig::default()
+            .frequency(433_920_000)
+            .sync_mode(SyncMode::Match16of16(0xBEEF))
+            .packet_length(PacketLength::Variable(61))
+            .modulation_format(ModulationFormat::GaussianFrequencyShiftKeying);
+
+        assert_eq!(&config.as_bytes()[0x0D..=0x0F], &[0x10, 0xB0, 0x71]);
+        assert_eq!(&config.as_bytes()[0x04..=0x06], &[0xBE, 0xEF, 61]);
+        assert_eq!(config.read(config::MDMCFG2).sync_mode(), SyncCheck::CHECK_16_16 as u8);
+        assert_eq!(config.read(config::MDMCFG2).mod_format(), 1);
+        assert_eq!(config.read(config::PKTCTRL0).length_config(), LengthConfig::VARIABLE as u8);
+
+        let reset = RadioConfig::default();
+        let diff = config.diff(&reset).map(|d| d.addr);
+        assert!(diff.eq([0x04, 0x05, 0x06, 0x0D, 0x0E, 0x0F, 0x12]));
+    }
+}
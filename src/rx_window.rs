@@ -0,0 +1,226 @@
+//! Receive windows bounded by the RX timeout.
+//!
+//! Outside of Wake-on-Radio, `MCSM2.RX_TIME` still bounds the sync word search after a `SRX`
+//! strobe, see section 19.7 "RX Termination Timer" of the data sheet (TI document SWRS061I).
+//! The timeout is a fraction of the Event 0 period, so it is computed from the `WOREVT1`/
+//! `WOREVT0` and `WORCTRL.WOR_RES` values in the chip. RX can also be left early when there is
+//! no carrier sense (`RX_TIME_RSSI`), or kept open past the timeout once the preamble quality
+//! threshold (`PKTCTRL1.PQT`) is reached (`RX_TIME_QUAL`).
+
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+use crate::lowlevel::registers::*;
+use crate::wor::{rx_time_for, rx_timeout_ns};
+use crate::{
+    Cc1101, Error, MachineState, PollBudget, PreambleQualityThreshold, RadioMode, Rounded,
+    RxTimeQualifier,
+};
+
+/// Receive window settings, `MCSM2` and `PKTCTRL1.PQT`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RxWindowConfig {
+    /// Leave RX after at least this long without a sync word. The shortest RX timeout that is
+    /// not shorter is used, or the longest one.
+    pub timeout_us: u32,
+    /// Leave RX as soon as there is no carrier sense, without waiting for the timeout.
+    pub rssi_termination: bool,
+    /// What to look for to stay in RX when the timeout expires.
+    pub qualifier: RxTimeQualifier,
+    /// Preamble quality threshold, see [`Cc1101::set_preamble_quality_threshold`].
+    pub preamble_quality_threshold: PreambleQualityThreshold,
+}
+
+impl RxWindowConfig {
+    /// Leave RX after `timeout_us` unless a sync word has been found.
+    pub const fn new(timeout_us: u32) -> Self {
+        RxWindowConfig {
+            timeout_us,
+            rssi_termination: false,
+            qualifier: RxTimeQualifier::SyncWord,
+            preamble_quality_threshold: PreambleQualityThreshold::Disabled,
+        }
+    }
+}
+
+/// How a receive window ended.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RxWindowEnd {
+    /// A packet was received, and is waiting in the RX FIFO.
+    Packet,
+    /// No sync word was found before the RX timeout, or before the polling budget ran out.
+    Timeout,
+    /// RX was left early as carrier sense was not asserted.
+    NoCarrierSense,
+    /// The preamble quality threshold was not reached when the RX timeout expired.
+    NoPreambleQuality,
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Configure the receive window, returning the RX timeout achieved (in microseconds).
+    ///
+    /// Set the Event 0 period first, e.g. with [`Self::set_wake_on_radio`]; the reset value
+    /// gives RX timeouts of 1.95 ms to 125 ms.
+    pub fn set_rx_window(&mut self, window: &RxWindowConfig) -> Result<Rounded, Error<SpiE>> {
+        let event1 = self.0.read_register(config::WOREVT1)?.event() as u16;
+        let event0 = event1 << 8 | self.0.read_register(config::WOREVT0)?.event() as u16;
+        let wor_res = self.0.read_register(config::WORCTRL)?.wor_res();
+        let rx_time = rx_time_for(event0, wor_res, window.timeout_us, self.0.fxosc);
+
+        self.0.modify_register(config::MCSM2, |r| {
+            r.rx_time_rssi(window.rssi_termination as u8)
+                .rx_time_qual(window.qualifier.into())
+                .rx_time(rx_time)
+        })?;
+        self.set_preamble_quality_threshold(window.preamble_quality_threshold)?;
+
+        let timeout_ns = rx_timeout_ns(event0, wor_res, rx_time, self.0.fxosc).unwrap_or(0);
+        Ok(Rounded::new(window.timeout_us as u64, timeout_ns / 1_000))
+    }
+
+    /// Open a receive window as configured with [`Self::set_rx_window`], and wait for it to end.
+    ///
+    /// The RX FIFO is flushed first. The radio is polled every `budget.interval_us` until it
+    /// leaves RX, or until a packet is in the RX FIFO with `MCSM1.RXOFF_MODE` staying in RX. A
+    /// window still open after `budget.polls` polls is closed by going to IDLE, aborting any
+    /// packet being received, and reported as [`RxWindowEnd::Timeout`].
+    pub fn receive_window<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<RxWindowEnd, Error<SpiE>> {
+        let mcsm2 = self.0.read_register(config::MCSM2)?;
+        self.set_radio_mode_within(RadioMode::Idle, delay, budget)?;
+        self.flush_rx_fifo_buffer()?;
+        self.enable_rx()?;
+
+        let mut carrier_sense = false;
+        for _ in 0..budget.polls {
+            delay.delay_us(budget.interval_us);
+            let status = self.get_packet_status()?;
+            carrier_sense |= status.carrier_sense;
+            match self.get_machine_state()? {
+                MachineState::RX | MachineState::RX_END | MachineState::RX_RST => {
+                    if !status.sof_delimiter && self.get_rx_bytes()? > 0 {
+                        return Ok(RxWindowEnd::Packet);
+                    }
+                }
+                MachineState::RXFIFO_OVERFLOW => {
+                    self.flush_rx_fifo_buffer()?;
+                    return Err(Error::RxOverflow);
+                }
+                _ if self.get_rx_bytes()? > 0 => return Ok(RxWindowEnd::Packet),
+                _ if mcsm2.rx_time_rssi() != 0 && !carrier_sense => {
+                    return Ok(RxWindowEnd::NoCarrierSense)
+                }
+                _ if mcsm2.rx_time_qual() == RxTimeQualifier::SyncWordOrPqi.into() => {
+                    return Ok(RxWindowEnd::NoPreambleQuality)
+                }
+                _ => return Ok(RxWindowEnd::Timeout),
+            }
+        }
+
+        self.set_radio_mode_within(RadioMode::Idle, delay, budget)?;
+        Ok(RxWindowEnd::Timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChip, SimDelay, SimPacket, SimSpi};
+    use crate::{RadioConfig, RxTime, WakeOnRadioConfig};
+    use core::cell::RefCell;
+
+    const BUDGET: PollBudget = PollBudget::new(100, 1_000);
+
+    #[test]
+    fn test_settings() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        // Event 0 of 1 s at reset, 1/8 of which is 125 ms, scaled by 2^-RX_TIME.
+        let window = RxWindowConfig {
+            rssi_termination: true,
+            qualifier: RxTimeQualifier::SyncWordOrPqi,
+            preamble_quality_threshold: PreambleQualityThreshold::Pqi12,
+            ..RxWindowConfig::new(5_000)
+        };
+        assert_eq!(cc1101.set_rx_window(&window).unwrap(), Rounded::new(5_000, 7_812));
+        let expected = RadioConfig::default()
+            .rx_time(RxTime::Div128)
+            .rx_time_rssi(true)
+            .rx_time_qualifier(RxTimeQualifier::SyncWordOrPqi)
+            .preamble_quality_threshold(PreambleQualityThreshold::Pqi12);
+        assert_eq!(chip.borrow().config(), expected);
+
+        // The timeout follows the Event 0 period, and is clamped to the longest one.
+        cc1101.set_wake_on_radio(&WakeOnRadioConfig::from_period_ms(100)).unwrap();
+        let achieved = cc1101.set_rx_window(&RxWindowConfig::new(5_000)).unwrap();
+        assert_eq!(achieved, Rounded::new(5_000, 6_250));
+        let achieved = cc1101.set_rx_window(&RxWindowConfig::new(100_000)).unwrap();
+        assert_eq!(achieved, Rounded::new(100_000, 12_501));
+        assert_eq!(chip.borrow().config().read(config::MCSM2).rx_time(), 0);
+    }
+
+    #[test]
+    fn test_receive_window() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+        let mut delay = SimDelay::new(&chip);
+        cc1101.set_rx_window(&RxWindowConfig::new(5_000)).unwrap();
+
+        let start_us = chip.borrow().now_us();
+        assert_eq!(cc1101.receive_window(&mut delay, BUDGET).unwrap(), RxWindowEnd::Timeout);
+        let elapsed_us = chip.borrow().now_us() - start_us;
+        assert!((7_812..8_000).contains(&elapsed_us), "{elapsed_us}");
+        assert_eq!(chip.borrow().state(), MachineState::IDLE);
+
+        chip.borrow_mut().inject(SimPacket::new(&[2, 7, 8]));
+        assert_eq!(cc1101.receive_window(&mut delay, BUDGET).unwrap(), RxWindowEnd::Packet);
+        assert_eq!(cc1101.get_rx_bytes().unwrap(), 5);
+
+        // Without a timeout, the budget closes the window.
+        cc1101.set_rx_time(RxTime::NoTimeout).unwrap();
+        let budget = PollBudget::new(100, 10);
+        assert_eq!(cc1101.receive_window(&mut delay, budget).unwrap(), RxWindowEnd::Timeout);
+        assert_eq!(chip.borrow().state(), MachineState::IDLE);
+        assert_eq!(cc1101.get_rx_bytes().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_qualifiers() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+        let mut delay = SimDelay::new(&chip);
+
+        let window = RxWindowConfig {
+            rssi_termination: true,
+            ..RxWindowConfig::new(5_000)
+        };
+        cc1101.set_rx_window(&window).unwrap();
+        let start_us = chip.borrow().now_us();
+        let end = cc1101.receive_window(&mut delay, BUDGET).unwrap();
+        assert_eq!(end, RxWindowEnd::NoCarrierSense);
+        assert!(chip.borrow().now_us() - start_us < 1_000);
+
+        // A carrier without a preamble keeps RX open until the timeout.
+        chip.borrow_mut().set_channel_busy(true);
+        assert_eq!(cc1101.receive_window(&mut delay, BUDGET).unwrap(), RxWindowEnd::Timeout);
+
+        let window = RxWindowConfig {
+            qualifier: RxTimeQualifier::SyncWordOrPqi,
+            preamble_quality_threshold: PreambleQualityThreshold::Pqi16,
+            ..window
+        };
+        cc1101.set_rx_window(&window).unwrap();
+        let end = cc1101.receive_window(&mut delay, BUDGET).unwrap();
+        assert_eq!(end, RxWindowEnd::NoPreambleQuality);
+
+        chip.borrow_mut().inject(SimPacket::new(&[1, 9]));
+        assert_eq!(cc1101.receive_window(&mut delay, BUDGET).unwrap(), RxWindowEnd::Packet);
+    }
+}
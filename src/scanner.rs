@@ -0,0 +1,313 @@
+//! RSSI spectrum sweep.
+//!
+//! The radio is tuned to each point of a frequency range or channel list in turn, and `RSSI`
+//! is sampled in RX once it has settled on the new channel. `RSSI` is updated at
+//! `2 * BW_channel / (8 * 2^FILTER_LENGTH)`, see section 17.3 "RSSI" of the data sheet (TI
+//! document SWRS061I). Sampling starts two updates and one symbol time after RX is entered, so
+//! that the AGC has settled and the averaged value only covers the new channel.
+
+use hal::delay::DelayNs;
+use hal::spi::SpiDevice;
+
+use crate::channel::carrier_hz;
+use crate::lowlevel::convert::*;
+use crate::lowlevel::registers::*;
+use crate::UserError;
+use crate::{AutoCalibration, Cc1101, ConfigError, Error, MachineState, PollBudget, RadioMode};
+
+/// Points to sweep.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ScanRange<'a> {
+    /// Carrier frequencies from `start_hz` up to and including `stop_hz`, `step_hz` apart.
+    Frequencies {
+        start_hz: u64,
+        stop_hz: u64,
+        step_hz: u64,
+    },
+    /// Channels of the programmed channel plan, see [`crate::ChannelPlan`].
+    Channels(&'a [u8]),
+}
+
+impl ScanRange<'_> {
+    /// Number of points in the range, none with a `step_hz` of zero.
+    pub fn len(&self) -> usize {
+        match *self {
+            ScanRange::Frequencies {
+                start_hz,
+                stop_hz,
+                step_hz,
+            } => match stop_hz.checked_sub(start_hz) {
+                Some(span) if step_hz > 0 => (span / step_hz) as usize + 1,
+                _ => 0,
+            },
+            ScanRange::Channels(channels) => channels.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Sweep settings.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ScanConfig {
+    /// RSSI samples per point, one RSSI update period apart.
+    pub samples: u16,
+    /// Bound on each radio state change.
+    pub budget: PollBudget,
+}
+
+/// RSSI measured at one point of a sweep.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct RssiBin {
+    /// Carrier frequency, in Hertz.
+    pub frequency_hz: u64,
+    /// Lowest sample, in dBm.
+    pub min_dbm: i16,
+    /// Average of the samples, in dBm.
+    pub avg_dbm: i16,
+    /// Highest sample, in dBm.
+    pub peak_dbm: i16,
+}
+
+/// RSSI settling time after entering RX and RSSI update period, in microseconds, from the
+/// channel bandwidth (`MDMCFG4.CHANBW_M/E`), data rate (`MDMCFG4.DRATE_E`, `MDMCFG3.DRATE_M`)
+/// and `AGCCTRL0.FILTER_LENGTH`, with a `fxosc` Hertz crystal.
+pub(crate) fn rssi_timing(
+    chanbw: (u8, u8),
+    drate: (u8, u8),
+    filter_length: u8,
+    fxosc: u64,
+) -> (u32, u32) {
+    let (drate_m, drate_e) = (drate.0 as u64, drate.1 as u64);
+
+    let bandwidth_hz = to_chanbw(chanbw.0, chanbw.1, fxosc);
+    let update_us = ((8u64 << filter_length) * 1_000_000).div_ceil(2 * bandwidth_hz);
+    let symbol_us = (1_000_000u64 << 28).div_ceil(((256 + drate_m) << drate_e) * fxosc);
+    ((2 * update_us + symbol_us) as u32, update_us as u32)
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Sweep `range`, storing the RSSI of each point in `bins` in order, and return the number
+    /// of points.
+    ///
+    /// The frequency synthesizer is calibrated at each point, by `MCSM0.FS_AUTOCAL` or else
+    /// manually. Frequencies are swept on channel 0 of the programmed channel plan. The
+    /// frequency, channel, calibration and radio mode (RX or IDLE) are restored afterwards,
+    /// also when a frequency of the range is rejected with [`Error::InvalidConfig`].
+    ///
+    /// Fails with [`ConfigError::FrequencyStep`] if the frequency step is zero.
+    pub fn scan_rssi<D: DelayNs>(
+        &mut self,
+        range: ScanRange,
+        bins: &mut [RssiBin],
+        config: ScanConfig,
+        delay: &mut D,
+    ) -> Result<usize, Error<SpiE>> {
+        if let ScanRange::Frequencies {
+            step_hz: 0,
+            ..
+        } = range
+        {
+            return Err(Error::InvalidConfig(ConfigError::FrequencyStep(0)));
+        }
+        let points = range.len();
+        if bins.len() < points {
+            return Err(Error::UserInputError(UserError::BufferTooSmall(points)));
+        }
+
+        let mode = match self.get_machine_state()? {
+            MachineState::RX => RadioMode::Receive,
+            _ => RadioMode::Idle,
+        };
+        let mut freq = [0; 3];
+        self.0.read_config_burst(config::FREQ2, &mut freq)?;
+        let channel = self.get_channel()?;
+        let mut fscal = [0; 3];
+        self.0.read_config_burst(config::FSCAL3, &mut fscal)?;
+
+        let result = self.sweep(range, &mut bins[..points], config, delay);
+        // Restore even if the sweep failed, but report the sweep's error first.
+        let restored = self.restore_scan(mode, &freq, channel, &fscal, delay, config.budget);
+        result?;
+        restored?;
+        Ok(points)
+    }
+
+    fn restore_scan<D: DelayNs>(
+        &mut self,
+        mode: RadioMode,
+        freq: &[u8; 3],
+        channel: u8,
+        fscal: &[u8; 3],
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<(), Error<SpiE>> {
+        self.set_radio_mode_within(RadioMode::Idle, delay, budget)?;
+        self.0.write_config_burst(config::FREQ2, freq)?;
+        self.set_channel(channel)?;
+        self.0.write_config_burst(config::FSCAL3, fscal)?;
+        if mode == RadioMode::Receive {
+            self.set_radio_mode_within(RadioMode::Receive, delay, budget)?;
+        }
+        Ok(())
+    }
+
+    /// RSSI settling time after entering RX and RSSI update period, in microseconds, for the
+    /// current configuration.
+    pub(crate) fn rssi_timing(&mut self) -> Result<(u32, u32), Error<SpiE>> {
+        let mdmcfg4 = self.0.read_register(config::MDMCFG4)?;
+        let chanbw = (mdmcfg4.chanbw_m(), mdmcfg4.chanbw_e());
+        let drate = (self.0.read_register(config::MDMCFG3)?.drate_m(), mdmcfg4.drate_e());
+        let filter_length = self.0.read_register(config::AGCCTRL0)?.filter_length();
+        Ok(rssi_timing(chanbw, drate, filter_length, self.0.fxosc))
+    }
+
+    fn sweep<D: DelayNs>(
+        &mut self,
+        range: ScanRange,
+        bins: &mut [RssiBin],
+        config: ScanConfig,
+        delay: &mut D,
+    ) -> Result<(), Error<SpiE>> {
+        let (settle_us, update_us) = self.rssi_timing()?;
+        let manual_cal =
+            self.0.read_register(config::MCSM0)?.fs_autocal() != AutoCalibration::FromIdle as u8;
+
+        let freq = (
+            self.0.read_register(config::FREQ0)?.freq(),
+            self.0.read_register(config::FREQ1)?.freq(),
+            self.0.read_register(config::FREQ2)?.freq(),
+        );
+        let chanspc_m = self.0.read_register(config::MDMCFG0)?.chanspc_m();
+        let chanspc_e = self.0.read_register(config::MDMCFG1)?.chanspc_e();
+
+        for (i, bin) in bins.iter_mut().enumerate() {
+            let frequency_hz = match range {
+                ScanRange::Frequencies {
+                    start_hz,
+                    step_hz,
+                    ..
+                } => {
+                    let hz = start_hz + i as u64 * step_hz;
+                    let actual = self.set_frequency_within(hz, delay, config.budget)?.actual;
+                    self.set_channel(0)?;
+                    actual
+                }
+                ScanRange::Channels(channels) => {
+                    self.set_radio_mode_within(RadioMode::Idle, delay, config.budget)?;
+                    self.set_channel(channels[i])?;
+                    carrier_hz(freq, chanspc_m, chanspc_e, channels[i], self.0.fxosc)
+                }
+            };
+            if manual_cal {
+                self.set_radio_mode_within(RadioMode::Calibrate, delay, config.budget)?;
+            }
+            self.set_radio_mode_within(RadioMode::Receive, delay, config.budget)?;
+            delay.delay_us(settle_us);
+
+            let (mut min_dbm, mut peak_dbm, mut sum) = (i16::MAX, i16::MIN, 0i32);
+            let samples = config.samples.max(1);
+            for sample in 0..samples {
+                if sample > 0 {
+                    delay.delay_us(update_us);
+                }
+                let rssi_dbm = self.get_rssi_dbm()?;
+                min_dbm = min_dbm.min(rssi_dbm);
+                peak_dbm = peak_dbm.max(rssi_dbm);
+                sum += rssi_dbm as i32;
+            }
+            *bin = RssiBin {
+                frequency_hz,
+                min_dbm,
+                avg_dbm: (sum / samples as i32) as i16,
+                peak_dbm,
+            };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lowlevel::FXOSC;
+    use crate::sim::{SimChip, SimDelay, SimSpi};
+    use crate::ChannelPlan;
+    use core::cell::RefCell;
+
+    const CONFIG: ScanConfig = ScanConfig {
+        samples: 4,
+        budget: PollBudget::new(100, 20),
+    };
+
+    #[test]
+    fn test_rssi_timing() {
+        // Reset values: 203 kHz channel bandwidth, 115.051 kBaud, 16 samples.
+        assert_eq!(rssi_timing((0, 2), (0x22, 12), 1, FXOSC), (89, 40));
+        // 58 kHz channel bandwidth, 1.2 kBaud, 32 samples.
+        assert_eq!(rssi_timing((3, 3), (0x83, 5), 2, FXOSC), (1386, 276));
+    }
+
+    #[test]
+    fn test_scan_rssi() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+        let mut delay = SimDelay::new(&chip);
+
+        chip.borrow_mut().set_noise_dbm(-95);
+        chip.borrow_mut().set_interferer(Some((868_300_000, -40)));
+        // Frequencies are swept on channel 0, whatever the channel selected.
+        cc1101.set_channel_plan(&ChannelPlan::new(433_000_000, 100_000, 10).unwrap()).unwrap();
+        cc1101.set_channel(3).unwrap();
+        cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, CONFIG.budget).unwrap();
+        let before = chip.borrow().config();
+
+        let range = ScanRange::Frequencies {
+            start_hz: 868_000_000,
+            stop_hz: 868_600_000,
+            step_hz: 100_000,
+        };
+        let mut bins = [RssiBin::default(); 8];
+        assert_eq!(cc1101.scan_rssi(range, &mut bins, CONFIG, &mut delay).unwrap(), 7);
+        assert_eq!(bins[3].frequency_hz, 868_299_865);
+        assert_eq!(bins[3].peak_dbm, -40);
+        assert_eq!(bins[0].peak_dbm, -95);
+        assert_eq!(bins[6].avg_dbm, -95);
+
+        assert_eq!(chip.borrow().state(), MachineState::RX);
+        assert_eq!(chip.borrow().config(), before);
+
+        let range = ScanRange::Frequencies {
+            start_hz: 868_000_000,
+            stop_hz: 868_600_000,
+            step_hz: 0,
+        };
+        assert!(range.is_empty());
+        assert_eq!(
+            cc1101.scan_rssi(range, &mut bins, CONFIG, &mut delay),
+            Err(Error::InvalidConfig(ConfigError::FrequencyStep(0)))
+        );
+
+        let plan = ChannelPlan::new(868_000_000, 100_000, 10).unwrap();
+        assert_eq!(
+            cc1101.set_channel_plan(&plan),
+            Err(Error::UserInputError(UserError::NotIdle(MachineState::RX)))
+        );
+        cc1101.set_radio_mode_within(RadioMode::Idle, &mut delay, CONFIG.budget).unwrap();
+        cc1101.set_channel_plan(&plan).unwrap();
+        let range = ScanRange::Channels(&[0, 3, 5]);
+        assert_eq!(cc1101.scan_rssi(range, &mut bins, CONFIG, &mut delay).unwrap(), 3);
+        assert_eq!(bins[1].peak_dbm, -40);
+        assert_eq!(bins[2].min_dbm, -95);
+
+        assert_eq!(
+            cc1101.scan_rssi(range, &mut bins[..2], CONFIG, &mut delay),
+            Err(Error::UserInputError(UserError::BufferTooSmall(3)))
+        );
+    }
+}
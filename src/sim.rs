@@ -0,0 +1,1467 @@
+//! Software model of the CC1101, for testing the driver off-target.
+//!
+//! [`SimChip`] decodes SPI traffic the way the radio does, see section 10 "4-wire Serial
+//! Configuration and Data Interface" of the data sheet (TI document SWRS061I): a header byte
+//! selects read or write, single or burst access and an address, and the chip status byte is
+//! returned on MISO while it is shifted in. It models the configuration and status registers
+//! with their reset values, PATABLE, both 64-byte FIFOs, and the main radio control state
+//! machine as driven by the command strobes.
+//!
+//! The chip is shared between the driver and the test through a `RefCell`: the driver owns a
+//! [`SimSpi`] (and optionally a [`SimDelay`] and [`SimGdo`] pins) borrowing the same chip
+//! that the test uses to inject packets and inspect state.
+//!
+//! Time only passes through SPI traffic, one microsecond per byte, and through [`SimDelay`].
+//! Packets go on air one byte at a time at the data rate configured in `MDMCFG4`/`MDMCFG3`;
+//! preamble, sync word, CRC and PLL settling take no time. Timing and frequencies follow a
+//! 26 MHz crystal, or the one given to [`SimChip::with_crystal`].
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use hal::delay::DelayNs;
+use hal::digital::{self, InputPin};
+use hal::spi::{self, Operation, SpiDevice};
+
+use crate::lowlevel::access;
+use crate::lowlevel::convert::to_chanbw;
+use crate::lowlevel::registers::*;
+use crate::lowlevel::{FIFO_SIZE_MAX, FXOSC};
+use crate::wor::rx_timeout_ns;
+use crate::{
+    AddressCheck, CcaMode, Clock, Gdo, GdoCfg, LengthConfig, MachineState, RadioConfig,
+    CONFIG_REGISTERS, PATABLE_SIZE,
+};
+
+mod medium;
+pub use medium::*;
+
+/// Largest packet the simulator can carry, including the length and address bytes.
+pub const SIM_PACKET_MAX: usize = 512;
+
+/// Duration of a manual frequency synthesizer calibration (`SCAL`).
+pub const CALIBRATION_US: u32 = 720;
+
+/// Duration of the RXTX_SWITCH state, between RX and TX after a `STX` strobe on a clear
+/// channel.
+pub const RXTX_SWITCH_US: u32 = 30;
+
+/// Duration of a single byte on the SPI bus.
+const SPI_BYTE_NS: u64 = 1_000;
+
+const FIFO_SIZE: usize = FIFO_SIZE_MAX as usize;
+
+// Addresses as encoded by the driver, so that the two cannot drift apart.
+const SRES: u8 = command::SRES::ADDR;
+const SFSTXON: u8 = command::SFSTXON::ADDR;
+const SXOFF: u8 = command::SXOFF::ADDR;
+const SCAL: u8 = command::SCAL::ADDR;
+const SRX: u8 = command::SRX::ADDR;
+const STX: u8 = command::STX::ADDR;
+const SIDLE: u8 = command::SIDLE::ADDR;
+const SWOR: u8 = command::SWOR::ADDR;
+const SPWD: u8 = command::SPWD::ADDR;
+const SFRX: u8 = command::SFRX::ADDR;
+const SFTX: u8 = command::SFTX::ADDR;
+const SNOP: u8 = command::SNOP::ADDR;
+
+const PARTNUM: u8 = status::PARTNUM::ADDR;
+const VERSION: u8 = status::VERSION::ADDR;
+const FREQEST: u8 = status::FREQEST::ADDR;
+const LQI: u8 = status::LQI::ADDR;
+const RSSI: u8 = status::RSSI::ADDR;
+const MARCSTATE: u8 = status::MARCSTATE::ADDR;
+const PKTSTATUS: u8 = status::PKTSTATUS::ADDR;
+const TXBYTES: u8 = status::TXBYTES::ADDR;
+const RXBYTES: u8 = status::RXBYTES::ADDR;
+const RCCTRL1_STATUS: u8 = status::RCCTRL1_STATUS::ADDR;
+const RCCTRL0_STATUS: u8 = status::RCCTRL0_STATUS::ADDR;
+
+const PATABLE: u8 = <multi::PATABLE as BurstRead>::ADDR;
+const FIFO: u8 = <multi::FIFO as BurstRead>::ADDR;
+
+/// Header bits selecting a read, and a burst access.
+const READ: u8 = access::Access::Read as u8;
+const BURST: u8 = access::Mode::Burst as u8;
+
+/// A packet on air: everything following the sync word, as seen by the receiver.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SimPacket {
+    data: [u8; SIM_PACKET_MAX],
+    len: usize,
+    /// Signal strength at the receiver.
+    pub rssi_dbm: i16,
+    /// Link Quality Indicator reported by the receiver.
+    pub lqi: u8,
+    /// Whether the receiver finds the CRC to match.
+    pub crc_ok: bool,
+}
+
+impl SimPacket {
+    /// A packet carrying `data`, received at -60 dBm with a matching CRC.
+    ///
+    /// Panics if `data` is longer than [`SIM_PACKET_MAX`].
+    pub fn new(data: &[u8]) -> Self {
+        let mut packet = SimPacket {
+            data: [0; SIM_PACKET_MAX],
+            len: data.len(),
+            rssi_dbm: -60,
+            lqi: 0x10,
+            crc_ok: true,
+        };
+        packet.data[..data.len()].copy_from_slice(data);
+        packet
+    }
+
+    /// Set the signal strength at the receiver.
+    pub fn with_rssi_dbm(mut self, rssi_dbm: i16) -> Self {
+        self.rssi_dbm = rssi_dbm;
+        self
+    }
+
+    /// Set the Link Quality Indicator reported by the receiver.
+    pub fn with_lqi(mut self, lqi: u8) -> Self {
+        self.lqi = lqi & 0x7F;
+        self
+    }
+
+    /// Set whether the receiver finds the CRC to match.
+    pub fn with_crc_ok(mut self, crc_ok: bool) -> Self {
+        self.crc_ok = crc_ok;
+        self
+    }
+
+    /// Bytes following the sync word.
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.data[self.len] = byte;
+        self.len += 1;
+    }
+}
+
+/// A packet being transmitted or received, with the number of bytes on air so far.
+struct OnAir {
+    packet: SimPacket,
+    pos: usize,
+}
+
+/// One of the two 64-byte FIFOs.
+struct Fifo {
+    buf: [u8; FIFO_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl Fifo {
+    const fn new() -> Self {
+        Fifo {
+            buf: [0; FIFO_SIZE],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8) -> bool {
+        if self.len == FIFO_SIZE {
+            return false;
+        }
+        self.buf[(self.head + self.len) % FIFO_SIZE] = byte;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let byte = self.buf[self.head];
+        self.head = (self.head + 1) % FIFO_SIZE;
+        self.len -= 1;
+        Some(byte)
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+/// Register access selected by the last header byte.
+#[derive(Copy, Clone)]
+enum Access {
+    /// The next byte is a header byte.
+    Header,
+    Config {
+        addr: u8,
+        read: bool,
+        burst: bool,
+    },
+    Status {
+        addr: u8,
+    },
+    Patable {
+        read: bool,
+        burst: bool,
+    },
+    Fifo {
+        read: bool,
+        burst: bool,
+    },
+}
+
+/// Simulated CC1101.
+pub struct SimChip {
+    /// Crystal frequency, in Hertz.
+    fxosc: u64,
+    regs: [u8; CONFIG_REGISTERS],
+    patable: [u8; PATABLE_SIZE],
+    patable_index: usize,
+    state: MachineState,
+    tx_fifo: Fifo,
+    rx_fifo: Fifo,
+    access: Access,
+    /// `SPWD` or `SWOR` was strobed, sleep once CSn goes high.
+    sleep_pending: bool,
+    calibration_ns: u64,
+    /// Time left in RXTX_SWITCH.
+    switch_ns: u64,
+    /// Time on air not yet spent on a whole byte.
+    air_ns: u64,
+    tx: Option<OnAir>,
+    rx: Option<OnAir>,
+    incoming: Option<SimPacket>,
+    transmitted: Option<SimPacket>,
+    last_rssi_dbm: i16,
+    last_lqi: u8,
+    last_crc_ok: bool,
+    noise_dbm: i16,
+    interferer: Option<(u64, i16)>,
+    channel_busy: bool,
+    /// Time left until the RX timeout of `MCSM2.RX_TIME`, while searching for a sync word.
+    rx_timer_ns: Option<u64>,
+    /// Carrier frequency of received signals relative to the programmed one, in Hertz.
+    frequency_error_hz: i32,
+    now_ns: u64,
+}
+
+impl Default for SimChip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimChip {
+    /// A chip in `IDLE`, with all registers at their reset values.
+    pub fn new() -> Self {
+        Self::with_crystal(FXOSC)
+    }
+
+    /// A chip as for [`Self::new`], with a `fxosc` Hertz crystal instead of 26 MHz.
+    pub fn with_crystal(fxosc: u64) -> Self {
+        SimChip {
+            fxosc,
+            regs: *RadioConfig::default().as_bytes(),
+            patable: [0xC6, 0, 0, 0, 0, 0, 0, 0],
+            patable_index: 0,
+            state: MachineState::IDLE,
+            tx_fifo: Fifo::new(),
+            rx_fifo: Fifo::new(),
+            access: Access::Header,
+            sleep_pending: false,
+            calibration_ns: 0,
+            switch_ns: 0,
+            air_ns: 0,
+            tx: None,
+            rx: None,
+            incoming: None,
+            transmitted: None,
+            last_rssi_dbm: 0,
+            last_lqi: 0,
+            last_crc_ok: false,
+            noise_dbm: -100,
+            interferer: None,
+            channel_busy: false,
+            rx_timer_ns: None,
+            frequency_error_hz: 0,
+            now_ns: 0,
+        }
+    }
+
+    /// Current main radio control state (`MARCSTATE`).
+    pub fn state(&self) -> MachineState {
+        self.state
+    }
+
+    /// Carrier frequency in Hertz, from `FREQ2/1/0` and the selected channel of the plan.
+    pub fn carrier_hz(&self) -> u64 {
+        medium::carrier(&self.config()) * self.fxosc / (1 << 18)
+    }
+
+    /// Current configuration register values.
+    pub fn config(&self) -> RadioConfig {
+        RadioConfig::from_bytes(self.regs)
+    }
+
+    /// Current PATABLE contents.
+    pub fn patable(&self) -> [u8; PATABLE_SIZE] {
+        self.patable
+    }
+
+    /// Simulated time since creation, in microseconds.
+    pub fn now_us(&self) -> u64 {
+        self.now_ns / 1_000
+    }
+
+    /// Number of bytes in the TX FIFO.
+    pub fn tx_fifo_len(&self) -> usize {
+        self.tx_fifo.len
+    }
+
+    /// Number of bytes in the RX FIFO.
+    pub fn rx_fifo_len(&self) -> usize {
+        self.rx_fifo.len
+    }
+
+    /// Signal strength reported by `RSSI` while no packet is being received.
+    pub fn set_noise_dbm(&mut self, noise_dbm: i16) {
+        self.noise_dbm = noise_dbm;
+    }
+
+    /// Continuous signal of `rssi_dbm` at a carrier frequency in Hertz, reported by `RSSI`
+    /// instead of the noise floor while the receive channel covers it.
+    pub fn set_interferer(&mut self, interferer: Option<(u64, i16)>) {
+        self.interferer = interferer;
+    }
+
+    /// Offset of the carrier frequency of received signals from the programmed one, as caused
+    /// by crystal tolerances. Reported by `FREQEST`, less the compensation in `FSCTRL0`.
+    pub fn set_frequency_error(&mut self, hz: i32) {
+        self.frequency_error_hz = hz;
+    }
+
+    /// Make clear channel assessment report the channel as busy, as if RSSI was above the
+    /// carrier sense threshold.
+    pub fn set_channel_busy(&mut self, busy: bool) {
+        self.channel_busy = busy;
+    }
+
+    /// Queue a packet to be received as soon as the radio is in RX.
+    ///
+    /// Returns `false`, dropping the packet, if one is already queued.
+    pub fn inject(&mut self, packet: SimPacket) -> bool {
+        if self.incoming.is_some() {
+            return false;
+        }
+        self.incoming = Some(packet);
+        true
+    }
+
+    /// Whether the chip is in RX, listening for packets on the medium.
+    pub(crate) fn listening(&self) -> bool {
+        self.state == MachineState::RX
+    }
+
+    /// A packet from the medium reaches the chip while it is listening.
+    ///
+    /// If another packet is still being received, the new one is lost, and with `collisions`
+    /// it also corrupts the one being received.
+    pub(crate) fn receive_from_air(&mut self, packet: SimPacket, collisions: bool) {
+        let busy = self.rx.as_mut().map(|rx| &mut rx.packet).or(self.incoming.as_mut());
+        match busy {
+            Some(busy) => busy.crc_ok &= !collisions,
+            None => self.incoming = Some(packet),
+        }
+    }
+
+    /// Take the last packet that was completely transmitted.
+    pub fn take_transmitted(&mut self) -> Option<SimPacket> {
+        self.transmitted.take()
+    }
+
+    /// Current level of a GDO pin, as configured by its `IOCFGx` register.
+    ///
+    /// Signals that are not modelled (clocks, serial data, analog test signals) read low.
+    pub fn gdo(&self, gdo: Gdo) -> bool {
+        let config = self.config();
+        let (cfg, inv) = match gdo {
+            Gdo::Gdo0 => {
+                let r = config.read(config::IOCFG0);
+                (r.gdo0_cfg(), r.gdo0_inv())
+            }
+            Gdo::Gdo1 => {
+                let r = config.read(config::IOCFG1);
+                (r.gdo1_cfg(), r.gdo1_inv())
+            }
+            Gdo::Gdo2 => {
+                let r = config.read(config::IOCFG2);
+                (r.gdo2_cfg(), r.gdo2_inv())
+            }
+        };
+        let threshold = config.read(config::FIFOTHR).fifo_thr() as usize;
+        let rx_threshold = 4 * (threshold + 1);
+        let tx_threshold = 61 - 4 * threshold;
+
+        let level = match cfg {
+            c if c == GdoCfg::RX_FIFO_FILLED as u8 => self.rx_fifo.len >= rx_threshold,
+            c if c == GdoCfg::RX_FIFO_FILLED_END_OF_PKT as u8 => {
+                self.rx_fifo.len >= rx_threshold || (self.rx.is_none() && self.rx_fifo.len > 0)
+            }
+            c if c == GdoCfg::TX_FIFO_FILLED as u8 => self.tx_fifo.len >= tx_threshold,
+            c if c == GdoCfg::TX_FIFO_FULL as u8 => self.tx_fifo.len == FIFO_SIZE,
+            c if c == GdoCfg::RX_FIFO_OVERFLOW as u8 => self.state == MachineState::RXFIFO_OVERFLOW,
+            c if c == GdoCfg::TX_FIFO_UNDERFLOW as u8 => {
+                self.state == MachineState::TXFIFO_UNDERFLOW
+            }
+            c if c == GdoCfg::SYNC_WORD as u8 => self.rx.is_some() || self.tx.is_some(),
+            c if c == GdoCfg::CRC_OK as u8 || c == GdoCfg::LAST_CRC_OK as u8 => self.last_crc_ok,
+            c if c == GdoCfg::PQT_REACHED as u8 => self.rx.is_some(),
+            c if c == GdoCfg::CARRIER_SENSE as u8 => self.carrier_sense(),
+            c if c == GdoCfg::CHANNEL_CLEAR as u8 => self.channel_clear(),
+            c if c == GdoCfg::PLL_LOCK as u8 => {
+                matches!(self.state, MachineState::RX | MachineState::TX | MachineState::FSTXON)
+            }
+            c if c == GdoCfg::XOSC_STABLE as u8 => {
+                !matches!(self.state, MachineState::SLEEP | MachineState::XOFF)
+            }
+            // CHIP_RDYn is low while the crystal runs.
+            c if c == GdoCfg::CHIP_RDYn as u8 => {
+                matches!(self.state, MachineState::SLEEP | MachineState::XOFF)
+            }
+            _ => false,
+        };
+        level ^ (inv != 0)
+    }
+
+    /// Let `us` microseconds pass.
+    pub fn advance_us(&mut self, us: u32) {
+        self.advance_ns(us as u64 * 1_000);
+    }
+
+    /// Let `ns` nanoseconds pass, moving packets on air and finishing calibration.
+    pub fn advance_ns(&mut self, ns: u64) {
+        self.now_ns += ns;
+
+        if self.calibration_ns > 0 {
+            self.calibration_ns = self.calibration_ns.saturating_sub(ns);
+            if self.calibration_ns == 0 && self.state == MachineState::MANCAL {
+                self.state = MachineState::IDLE;
+                self.calibrate();
+            }
+        }
+
+        if self.switch_ns > 0 {
+            self.switch_ns = self.switch_ns.saturating_sub(ns);
+            if self.switch_ns == 0 && self.state == MachineState::RXTX_SWITCH {
+                self.enter(MachineState::TX);
+            }
+        }
+
+        self.advance_rx_timer(ns);
+
+        let on_air = match self.state {
+            MachineState::TX => true,
+            MachineState::RX => self.rx.is_some() || self.incoming.is_some(),
+            _ => false,
+        };
+        if !on_air {
+            self.air_ns = 0;
+            return;
+        }
+
+        let byte_ns = self.byte_ns();
+        self.air_ns += ns;
+        while self.air_ns >= byte_ns {
+            self.air_ns -= byte_ns;
+            match self.state {
+                MachineState::TX => self.tx_byte(),
+                MachineState::RX if self.rx.is_some() || self.incoming.is_some() => self.rx_byte(),
+                _ => {
+                    self.air_ns = 0;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Leave RX for IDLE when the RX timeout expires without a sync word, or without reaching
+    /// the preamble quality threshold with `MCSM2.RX_TIME_QUAL`, or as soon as carrier sense is
+    /// lost with `MCSM2.RX_TIME_RSSI`. A queued packet counts as found, as its sync word
+    /// follows at once.
+    fn advance_rx_timer(&mut self, ns: u64) {
+        if self.state != MachineState::RX || self.rx.is_some() || self.incoming.is_some() {
+            self.rx_timer_ns = None;
+            return;
+        }
+        let mcsm2 = self.config().read(config::MCSM2);
+        let expired = match self.rx_timer_ns {
+            Some(left) => {
+                self.rx_timer_ns = Some(left.saturating_sub(ns));
+                left <= ns
+            }
+            None => false,
+        };
+        // Without a packet there is no preamble, so the threshold is never reached.
+        if expired || (mcsm2.rx_time_rssi() != 0 && !self.carrier_sense()) {
+            self.enter(MachineState::IDLE);
+        }
+    }
+
+    /// RX timeout for the configured `MCSM2.RX_TIME` and Event 0 period, in nanoseconds.
+    fn rx_timeout_ns(&self) -> Option<u64> {
+        let config = self.config();
+        let event1 = config.read(config::WOREVT1).event();
+        let event0 = u16::from_be_bytes([event1, config.read(config::WOREVT0).event()]);
+        let wor_res = config.read(config::WORCTRL).wor_res();
+        let rx_time = config.read(config::MCSM2).rx_time();
+        rx_timeout_ns(event0, wor_res, rx_time, self.fxosc)
+    }
+
+    /// Whether carrier sense is asserted: a packet is being received, or the channel is busy.
+    fn carrier_sense(&self) -> bool {
+        self.rx.is_some() || (self.state == MachineState::RX && self.channel_busy)
+    }
+
+    /// Time on air of one byte at the configured data rate, see section 12 "Data Rate
+    /// Programming" of the data sheet.
+    fn byte_ns(&self) -> u64 {
+        let config = self.config();
+        let drate_e = config.read(config::MDMCFG4).drate_e() as u32;
+        let drate_m = config.read(config::MDMCFG3).drate_m() as u128;
+        let scaled_baud = ((256 + drate_m) << drate_e) * self.fxosc as u128;
+        ((8_000_000_000u128 << 28) / scaled_baud).max(1) as u64
+    }
+
+    /// Whether clear channel assessment, per `MCSM1.CCA_MODE`, allows going from RX to TX.
+    fn channel_clear(&self) -> bool {
+        let rssi_clear = !self.channel_busy && self.rx.is_none();
+        let receiving = self.rx.is_some();
+        match self.config().read(config::MCSM1).cca_mode() {
+            m if m == CcaMode::CciAlways as u8 => true,
+            m if m == CcaMode::CciRssiBelowThreshold as u8 => rssi_clear,
+            m if m == CcaMode::CciUnlessCurrentlyReceivingPacket as u8 => !receiving,
+            _ => rssi_clear && !receiving,
+        }
+    }
+
+    /// Whether a packet of which `count` bytes are on air is complete, per the live
+    /// `PKTCTRL0.LENGTH_CONFIG` and `PKTLEN` settings.
+    fn packet_complete(&self, packet: &SimPacket, count: usize) -> bool {
+        let config = self.config();
+        let packet_length = config.read(config::PKTLEN).packet_length() as usize;
+        match config.read(config::PKTCTRL0).length_config() {
+            // The packet byte counter is 8 bits wide, PKTLEN=0 completes after 256 bytes.
+            l if l == LengthConfig::FIXED as u8 => count > 0 && count % 256 == packet_length,
+            l if l == LengthConfig::VARIABLE as u8 => count == 1 + packet.data[0] as usize,
+            _ => count == SIM_PACKET_MAX,
+        }
+    }
+
+    /// Whether the packet filters in `PKTCTRL1` and `PKTLEN` accept an incoming packet.
+    fn accept(&self, packet: &SimPacket) -> bool {
+        let config = self.config();
+        let variable =
+            config.read(config::PKTCTRL0).length_config() == LengthConfig::VARIABLE as u8;
+        let data = packet.data();
+
+        if variable
+            && data.first().is_none_or(|&len| len > config.read(config::PKTLEN).packet_length())
+        {
+            return false;
+        }
+
+        let address = config.read(config::ADDR).device_addr();
+        let received = data.get(variable as usize).copied();
+        match config.read(config::PKTCTRL1).adr_chk() {
+            c if c == AddressCheck::DISABLED as u8 => true,
+            c if c == AddressCheck::SELF as u8 => received == Some(address),
+            c if c == AddressCheck::SELF_LOW_BROADCAST as u8 => {
+                matches!(received, Some(a) if a == address || a == 0x00)
+            }
+            _ => matches!(received, Some(a) if a == address || a == 0x00 || a == 0xFF),
+        }
+    }
+
+    fn tx_byte(&mut self) {
+        if self.tx.is_none() && self.tx_fifo.len == 0 {
+            // Preamble is sent until the first byte of a packet is written to the TX FIFO.
+            return;
+        }
+        let Some(byte) = self.tx_fifo.pop() else {
+            self.tx = None;
+            self.state = MachineState::TXFIFO_UNDERFLOW;
+            return;
+        };
+
+        let tx = self.tx.get_or_insert_with(|| OnAir {
+            packet: SimPacket::new(&[]),
+            pos: 0,
+        });
+        tx.packet.push(byte);
+        tx.pos += 1;
+
+        let tx = self.tx.as_ref().unwrap();
+        if self.packet_complete(&tx.packet, tx.pos) {
+            self.transmitted = self.tx.take().map(|tx| tx.packet);
+            self.state = match self.config().read(config::MCSM1).txoff_mode() {
+                0 => MachineState::IDLE,
+                1 => MachineState::FSTXON,
+                2 => MachineState::TX,
+                _ => MachineState::RX,
+            };
+        }
+    }
+
+    fn rx_byte(&mut self) {
+        if self.rx.is_none() {
+            let Some(packet) = self.incoming.take() else {
+                return;
+            };
+            if !self.accept(&packet) {
+                return;
+            }
+            self.rx = Some(OnAir {
+                packet,
+                pos: 0,
+            });
+        }
+
+        let rx = self.rx.as_mut().unwrap();
+        let Some(&byte) = rx.packet.data().get(rx.pos) else {
+            // The transmitter stopped before the packet was complete.
+            self.rx = None;
+            return;
+        };
+        rx.pos += 1;
+
+        if !self.rx_fifo.push(byte) {
+            self.rx = None;
+            self.state = MachineState::RXFIFO_OVERFLOW;
+            return;
+        }
+
+        let rx = self.rx.as_ref().unwrap();
+        if self.packet_complete(&rx.packet, rx.pos) {
+            self.finish_rx();
+        }
+    }
+
+    fn finish_rx(&mut self) {
+        let packet = self.rx.take().unwrap().packet;
+        self.last_rssi_dbm = packet.rssi_dbm;
+        self.last_lqi = packet.lqi;
+        self.last_crc_ok = packet.crc_ok;
+
+        let config = self.config();
+        let pktctrl1 = config.read(config::PKTCTRL1);
+        let crc_en = config.read(config::PKTCTRL0).crc_en() != 0;
+        if crc_en && pktctrl1.crc_autoflush() != 0 && !packet.crc_ok {
+            self.rx_fifo.clear();
+        } else if pktctrl1.append_status() != 0 {
+            let pushed = self.rx_fifo.push(rssi_from_dbm(packet.rssi_dbm))
+                && self.rx_fifo.push(self.lqi_byte());
+            if !pushed {
+                self.state = MachineState::RXFIFO_OVERFLOW;
+                return;
+            }
+        }
+
+        self.state = match config.read(config::MCSM1).rxoff_mode() {
+            0 => MachineState::IDLE,
+            1 => MachineState::FSTXON,
+            2 => MachineState::TX,
+            _ => MachineState::RX,
+        };
+    }
+
+    fn lqi_byte(&self) -> u8 {
+        self.last_lqi | (self.last_crc_ok as u8) << 7
+    }
+
+    /// Chip status byte, with the free TX FIFO bytes for writes or the RX FIFO bytes for reads.
+    fn status_byte(&self, read: bool) -> u8 {
+        let state = match self.state {
+            MachineState::IDLE | MachineState::SLEEP | MachineState::XOFF => 0b000,
+            MachineState::RX | MachineState::RX_END | MachineState::RX_RST => 0b001,
+            MachineState::TX | MachineState::TX_END => 0b010,
+            MachineState::FSTXON => 0b011,
+            MachineState::MANCAL
+            | MachineState::STARTCAL
+            | MachineState::BWBOOST
+            | MachineState::FS_LOCK
+            | MachineState::IFADCON
+            | MachineState::ENDCAL => 0b100,
+            MachineState::RXFIFO_OVERFLOW => 0b110,
+            MachineState::TXFIFO_UNDERFLOW => 0b111,
+            _ => 0b101,
+        };
+        let fifo = if read {
+            self.rx_fifo.len
+        } else {
+            FIFO_SIZE - self.tx_fifo.len
+        };
+        state << 4 | fifo.min(0x0F) as u8
+    }
+
+    fn status_register(&self, addr: u8) -> u8 {
+        match addr {
+            PARTNUM => 0x00,
+            VERSION => 0x14,
+            LQI => self.lqi_byte(),
+            RSSI => rssi_from_dbm(match &self.rx {
+                Some(rx) => rx.packet.rssi_dbm,
+                None => self.background_dbm(),
+            }),
+            MARCSTATE => self.state as u8,
+            PKTSTATUS => {
+                (self.last_crc_ok as u8) << 7
+                    | (self.carrier_sense() as u8) << 6
+                    | (self.rx.is_some() as u8) << 5
+                    | (self.channel_clear() as u8) << 4
+                    | ((self.rx.is_some() || self.tx.is_some()) as u8) << 3
+                    | (self.gdo(Gdo::Gdo2) as u8) << 2
+                    | self.gdo(Gdo::Gdo0) as u8
+            }
+            TXBYTES => {
+                ((self.state == MachineState::TXFIFO_UNDERFLOW) as u8) << 7 | self.tx_fifo.len as u8
+            }
+            RXBYTES => {
+                ((self.state == MachineState::RXFIFO_OVERFLOW) as u8) << 7 | self.rx_fifo.len as u8
+            }
+            RCCTRL1_STATUS => self.config().read(config::RCCTRL1).rcctrl1(),
+            RCCTRL0_STATUS => self.config().read(config::RCCTRL0).rcctrl0(),
+            FREQEST => {
+                let error = (self.frequency_error_hz as i64 * (1 << 14)) / self.fxosc as i64;
+                let freqoff = self.config().read(config::FSCTRL0).freqoff();
+                let compensated = error - freqoff as i8 as i64;
+                compensated.clamp(i8::MIN as i64, i8::MAX as i64) as i8 as u8
+            }
+            // WORTIME1/0 and VCO_VC_DAC are not modelled.
+            _ => 0x00,
+        }
+    }
+
+    /// Signal strength while no packet is being received: the interferer if it falls within
+    /// the receive channel bandwidth, see section 13 "Receiver Channel Filter Bandwidth" of the
+    /// data sheet, or the noise floor.
+    fn background_dbm(&self) -> i16 {
+        let config = self.config();
+        let carrier_hz = self.carrier_hz();
+        let mdmcfg4 = config.read(config::MDMCFG4);
+        let bandwidth_hz = to_chanbw(mdmcfg4.chanbw_m(), mdmcfg4.chanbw_e(), self.fxosc);
+        match self.interferer {
+            Some((hz, rssi_dbm)) if hz.abs_diff(carrier_hz) <= bandwidth_hz / 2 => rssi_dbm,
+            _ => self.noise_dbm,
+        }
+    }
+
+    fn strobe(&mut self, addr: u8) {
+        use MachineState::*;
+
+        // The settling time of an automatic calibration is not modelled.
+        let fs_autocal = self.config().read(config::MCSM0).fs_autocal();
+        if matches!(addr, SFSTXON | SRX | STX) && self.state == IDLE && fs_autocal == 1 {
+            self.calibrate();
+        }
+
+        match (addr, self.state) {
+            (SRES, _) => {
+                let (now_ns, noise_dbm, interferer, channel_busy) =
+                    (self.now_ns, self.noise_dbm, self.interferer, self.channel_busy);
+                *self = SimChip {
+                    now_ns,
+                    noise_dbm,
+                    interferer,
+                    channel_busy,
+                    ..SimChip::with_crystal(self.fxosc)
+                };
+            }
+            (SFSTXON, IDLE | RX) => self.enter(FSTXON),
+            (SXOFF, IDLE) => self.enter(XOFF),
+            (SCAL, IDLE) => {
+                self.enter(MANCAL);
+                self.calibration_ns = CALIBRATION_US as u64 * 1_000;
+            }
+            (SRX, IDLE | FSTXON | TX) => self.enter(RX),
+            (STX, IDLE | FSTXON) => self.enter(TX),
+            (STX, RX) if self.channel_clear() => {
+                self.enter(RXTX_SWITCH);
+                self.switch_ns = RXTX_SWITCH_US as u64 * 1_000;
+            }
+            // The FIFO error states are only left through SFRX and SFTX.
+            (SIDLE, state) if !matches!(state, RXFIFO_OVERFLOW | TXFIFO_UNDERFLOW) => {
+                self.enter(IDLE);
+                self.sleep_pending = false;
+                self.calibration_ns = 0;
+                self.switch_ns = 0;
+            }
+            (SWOR | SPWD, IDLE) => self.sleep_pending = true,
+            (SFRX, IDLE | RXFIFO_OVERFLOW) => {
+                self.rx_fifo.clear();
+                self.state = IDLE;
+            }
+            (SFTX, IDLE | TXFIFO_UNDERFLOW) => {
+                self.tx_fifo.clear();
+                self.state = IDLE;
+            }
+            // SWORRST, SNOP, and strobes that are ignored in the current state.
+            _ => {}
+        }
+    }
+
+    /// Store frequency synthesizer calibration results in `FSCAL3`/`FSCAL2`/`FSCAL1`. The
+    /// values are made up, but differ between carrier frequencies.
+    fn calibrate(&mut self) {
+        let carrier = medium::carrier(&self.config());
+        let fscal3 = <config::FSCAL3 as Writable>::ADDR as usize;
+        self.regs[fscal3] = (self.regs[fscal3] & 0xF0) | (carrier >> 8 & 0x0F) as u8;
+        self.regs[fscal3 + 1] = 0x20 | (carrier >> 12 & 0x1F) as u8;
+        self.regs[fscal3 + 2] = (carrier >> 17 & 0x3F) as u8;
+    }
+
+    /// Enter `state`, aborting any packet on air. Starts the RX timeout when entering RX.
+    fn enter(&mut self, state: MachineState) {
+        self.state = state;
+        self.tx = None;
+        self.rx = None;
+        self.air_ns = 0;
+        self.rx_timer_ns = match state {
+            MachineState::RX => self.rx_timeout_ns(),
+            _ => None,
+        };
+    }
+
+    /// CSn goes low. Wakes the chip from SLEEP and XOFF.
+    fn select(&mut self) {
+        if matches!(self.state, MachineState::SLEEP | MachineState::XOFF) {
+            self.state = MachineState::IDLE;
+        }
+        self.access = Access::Header;
+        self.patable_index = 0;
+    }
+
+    /// CSn goes high.
+    fn deselect(&mut self) {
+        if self.sleep_pending {
+            self.sleep_pending = false;
+            self.state = MachineState::SLEEP;
+        }
+    }
+
+    /// Shift one byte in on MOSI, returning the byte shifted out on MISO.
+    fn exchange(&mut self, mosi: u8) -> u8 {
+        self.advance_ns(SPI_BYTE_NS);
+
+        match self.access {
+            Access::Header => self.header(mosi),
+            Access::Config {
+                addr,
+                read,
+                burst,
+            } => {
+                let miso = if read {
+                    self.regs.get(addr as usize).copied().unwrap_or(0)
+                } else {
+                    if let Some(reg) = self.regs.get_mut(addr as usize) {
+                        *reg = mosi;
+                    }
+                    self.status_byte(false)
+                };
+                self.access = if burst {
+                    Access::Config {
+                        addr: addr.wrapping_add(1),
+                        read,
+                        burst,
+                    }
+                } else {
+                    Access::Header
+                };
+                miso
+            }
+            Access::Status {
+                addr,
+            } => {
+                self.access = Access::Header;
+                self.status_register(addr)
+            }
+            Access::Patable {
+                read,
+                burst,
+            } => {
+                let index = self.patable_index % PATABLE_SIZE;
+                self.patable_index += 1;
+                if !burst {
+                    self.access = Access::Header;
+                }
+                if read {
+                    self.patable[index]
+                } else {
+                    self.patable[index] = mosi;
+                    self.status_byte(false)
+                }
+            }
+            Access::Fifo {
+                read,
+                burst,
+            } => {
+                if !burst {
+                    self.access = Access::Header;
+                }
+                if read {
+                    // Reading an empty RX FIFO returns stale data, not modelled.
+                    self.rx_fifo.pop().unwrap_or(0)
+                } else {
+                    // Bytes written to a full TX FIFO are lost.
+                    self.tx_fifo.push(mosi);
+                    self.status_byte(false)
+                }
+            }
+        }
+    }
+
+    fn header(&mut self, header: u8) -> u8 {
+        let read = header & READ != 0;
+        let burst = header & BURST != 0;
+        let addr = header & !(READ | BURST);
+        // The status byte reflects the state before any strobe takes effect.
+        let status = self.status_byte(read);
+
+        self.access = match addr {
+            PATABLE => Access::Patable {
+                read,
+                burst,
+            },
+            FIFO => Access::Fifo {
+                read,
+                burst,
+            },
+            // Status registers share addresses with the strobes and need the burst bit.
+            PARTNUM..=RCCTRL0_STATUS if read && burst => Access::Status {
+                addr,
+            },
+            SRES..=SNOP => {
+                self.strobe(addr);
+                Access::Header
+            }
+            _ => Access::Config {
+                addr,
+                read,
+                burst,
+            },
+        };
+        status
+    }
+}
+
+/// Raw `RSSI` register value for a signal strength, see section 17.3 of the data sheet.
+fn rssi_from_dbm(rssi_dbm: i16) -> u8 {
+    ((rssi_dbm + 74) * 2).clamp(i8::MIN as i16, i8::MAX as i16) as i8 as u8
+}
+
+/// SPI device connected to a [`SimChip`].
+pub struct SimSpi<'a> {
+    chip: &'a RefCell<SimChip>,
+}
+
+impl<'a> SimSpi<'a> {
+    pub fn new(chip: &'a RefCell<SimChip>) -> Self {
+        SimSpi {
+            chip,
+        }
+    }
+}
+
+impl spi::ErrorType for SimSpi<'_> {
+    type Error = Infallible;
+}
+
+impl SpiDevice<u8> for SimSpi<'_> {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+        let mut chip = self.chip.borrow_mut();
+        chip.select();
+        for operation in operations {
+            match operation {
+                Operation::Read(buf) => buf.iter_mut().for_each(|b| *b = chip.exchange(0)),
+                Operation::Write(buf) => buf.iter().for_each(|&b| {
+                    chip.exchange(b);
+                }),
+                Operation::Transfer(read, write) => {
+                    for i in 0..read.len().max(write.len()) {
+                        let miso = chip.exchange(write.get(i).copied().unwrap_or(0));
+                        if let Some(b) = read.get_mut(i) {
+                            *b = miso;
+                        }
+                    }
+                }
+                Operation::TransferInPlace(buf) => {
+                    buf.iter_mut().for_each(|b| *b = chip.exchange(*b))
+                }
+                Operation::DelayNs(ns) => chip.advance_ns(*ns as u64),
+            }
+        }
+        chip.deselect();
+        Ok(())
+    }
+}
+
+/// Delay that lets simulated time pass on a [`SimChip`].
+pub struct SimDelay<'a> {
+    chip: &'a RefCell<SimChip>,
+}
+
+impl<'a> SimDelay<'a> {
+    pub fn new(chip: &'a RefCell<SimChip>) -> Self {
+        SimDelay {
+            chip,
+        }
+    }
+}
+
+impl DelayNs for SimDelay<'_> {
+    fn delay_ns(&mut self, ns: u32) {
+        self.chip.borrow_mut().advance_ns(ns as u64);
+    }
+}
+
+impl Clock for SimDelay<'_> {
+    fn now_us(&mut self) -> u64 {
+        self.chip.borrow().now_us()
+    }
+}
+
+/// MCU input connected to a GDO pin of a [`SimChip`].
+pub struct SimGdo<'a> {
+    chip: &'a RefCell<SimChip>,
+    gdo: Gdo,
+}
+
+impl<'a> SimGdo<'a> {
+    pub fn new(chip: &'a RefCell<SimChip>, gdo: Gdo) -> Self {
+        SimGdo {
+            chip,
+            gdo,
+        }
+    }
+}
+
+impl digital::ErrorType for SimGdo<'_> {
+    type Error = Infallible;
+}
+
+impl InputPin for SimGdo<'_> {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.chip.borrow().gdo(self.gdo))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.chip.borrow().gdo(self.gdo))
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::spi::SpiDevice<u8> for SimSpi<'_> {
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Infallible> {
+        SpiDevice::transaction(self, operations)
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for SimDelay<'_> {
+    async fn delay_ns(&mut self, ns: u32) {
+        DelayNs::delay_ns(self, ns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AddressFilter, Cc1101, ChannelPlan, ConfigError, Error, FifoThreshold, PacketLength,
+        PoTimeout, PollBudget, RadioMode, RxOffMode, RxStatus, RxTime, RxTimeQualifier,
+        RxWindowConfig, RxWindowEnd, StreamConfig, StreamLength, SyncMode, TxOffMode, UserError,
+    };
+
+    const BUDGET: PollBudget = PollBudget::new(100, 1_000);
+
+    fn stream_config() -> StreamConfig {
+        StreamConfig {
+            threshold: FifoThreshold::TX_33_RX_32,
+            budget: BUDGET,
+        }
+    }
+
+    #[test]
+    fn test_registers() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        assert_eq!(cc1101.get_hw_info().unwrap(), (0x00, 0x14));
+        cc1101.set_frequency(868_000_000).unwrap();
+        cc1101.set_sync_mode(SyncMode::Match16of16(0xD201)).unwrap();
+
+        let expected =
+            RadioConfig::default().frequency(868_000_000).sync_mode(SyncMode::Match16of16(0xD201));
+        assert_eq!(chip.borrow().config(), expected);
+        assert_eq!(cc1101.read_config().unwrap(), expected);
+
+        let config = expected.channel(7);
+        cc1101.apply_config(&config).unwrap();
+        assert_eq!(chip.borrow().config(), config);
+    }
+
+    #[test]
+    fn test_modem_settings() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        let frequency = cc1101.set_frequency(868_000_000).unwrap();
+        assert_eq!((frequency.actual, frequency.error()), (867_999_938, -62));
+        assert_eq!(cc1101.get_frequency().unwrap(), frequency.actual);
+        let data_rate = cc1101.set_data_rate(38_400).unwrap();
+        assert_eq!((data_rate.actual, data_rate.error()), (38_383, -17));
+        assert_eq!(cc1101.get_data_rate().unwrap(), data_rate.actual);
+        assert_eq!(cc1101.set_channel_bandwidth(100_000).unwrap().actual, 101_562);
+        assert_eq!(cc1101.get_channel_bandwidth().unwrap(), 101_562);
+        assert_eq!(cc1101.set_deviation_hz(20_000).unwrap().actual, 20_630);
+        assert_eq!(cc1101.get_deviation_hz().unwrap(), 20_630);
+        assert_eq!(cc1101.set_freq_if(152_300).unwrap().actual, 152_343);
+
+        let before = chip.borrow().config();
+        assert_eq!(
+            cc1101.set_frequency(350_000_000),
+            Err(Error::InvalidConfig(ConfigError::Frequency(350_000_000)))
+        );
+        assert_eq!(
+            cc1101.set_data_rate(500),
+            Err(Error::InvalidConfig(ConfigError::DataRate(500)))
+        );
+        assert_eq!(
+            cc1101.set_data_rate(700_000),
+            Err(Error::InvalidConfig(ConfigError::DataRate(700_000)))
+        );
+        assert_eq!(
+            cc1101.set_channel_bandwidth(900_000),
+            Err(Error::InvalidConfig(ConfigError::ChannelBandwidth(900_000)))
+        );
+        assert_eq!(
+            cc1101.set_deviation_hz(400_000),
+            Err(Error::InvalidConfig(ConfigError::Deviation(400_000)))
+        );
+        assert_eq!(
+            cc1101.set_freq_if(800_000),
+            Err(Error::InvalidConfig(ConfigError::IntermediateFrequency(800_000)))
+        );
+        assert_eq!(chip.borrow().config(), before);
+    }
+
+    #[test]
+    fn test_crystal() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::with_crystal(SimSpi::new(&chip), 27_000_000).unwrap();
+        assert_eq!(cc1101.crystal_hz(), 27_000_000);
+
+        assert_eq!(cc1101.set_frequency(868_000_000).unwrap().actual, 867_999_984);
+        assert_eq!(cc1101.set_data_rate(38_400).unwrap().actual, 38_418);
+        let expected =
+            RadioConfig::default().crystal(27_000_000).frequency(868_000_000).data_rate(38_400);
+        assert_eq!(chip.borrow().config().as_bytes(), expected.as_bytes());
+        assert_eq!(cc1101.read_config().unwrap(), expected);
+        assert_eq!(cc1101.get_frequency().unwrap(), 867_999_984);
+
+        let plan = ChannelPlan::new(868_000_000, 100_000, 10).unwrap();
+        assert_eq!(
+            cc1101.set_channel_plan(&plan),
+            Err(Error::InvalidConfig(ConfigError::Crystal(26_000_000)))
+        );
+        let plan = ChannelPlan::with_crystal(868_000_000, 100_000, 10, 27_000_000).unwrap();
+        cc1101.set_channel_plan(&plan).unwrap();
+        assert_eq!(plan.frequency(0), Some(cc1101.get_frequency().unwrap()));
+
+        assert_eq!(
+            Cc1101::with_crystal(SimSpi::new(&chip), 32_000_000).err(),
+            Some(Error::InvalidConfig(ConfigError::Crystal(32_000_000)))
+        );
+    }
+
+    #[test]
+    fn test_crystal_timing() {
+        let chip = RefCell::new(SimChip::with_crystal(27_000_000));
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::with_crystal(SimSpi::new(&chip), 27_000_000).unwrap();
+
+        let frequency = cc1101.set_frequency(868_300_000).unwrap();
+        assert_eq!(chip.borrow().carrier_hz(), frequency.actual);
+        chip.borrow_mut().set_interferer(Some((868_300_000, -40)));
+        chip.borrow_mut().set_frequency_error(20_000);
+        cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+        assert_eq!(cc1101.get_rssi_dbm().unwrap(), -40);
+        assert!(cc1101.get_est_freq_offset().unwrap().abs_diff(20_000) < 1_648);
+
+        // 20 bytes at 38.418 kBaud take 4165 us.
+        cc1101.set_radio_mode_within(RadioMode::Idle, &mut delay, BUDGET).unwrap();
+        assert_eq!(cc1101.set_data_rate(38_400).unwrap().actual, 38_418);
+        cc1101.set_packet_length(PacketLength::Fixed(20)).unwrap();
+        cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+        chip.borrow_mut().inject(SimPacket::new(&[0x55; 20]));
+        delay.delay_us(4_100);
+        assert_eq!(chip.borrow().state(), MachineState::RX);
+        delay.delay_us(100);
+        assert_eq!(chip.borrow().state(), MachineState::IDLE);
+
+        // The RX timeout follows the Event 0 period of the 27 MHz crystal.
+        let timeout = cc1101.set_rx_window(&RxWindowConfig::new(5_000)).unwrap();
+        let start_us = chip.borrow().now_us();
+        assert_eq!(cc1101.receive_window(&mut delay, BUDGET).unwrap(), RxWindowEnd::Timeout);
+        let elapsed_us = chip.borrow().now_us() - start_us;
+        assert!((timeout.actual..timeout.actual + 200).contains(&elapsed_us), "{elapsed_us}");
+    }
+
+    #[test]
+    fn test_strobes() {
+        let chip = RefCell::new(SimChip::new());
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+        assert_eq!(chip.borrow().state(), MachineState::RX);
+        cc1101.set_radio_mode_within(RadioMode::Calibrate, &mut delay, BUDGET).unwrap();
+        assert_eq!(chip.borrow().state(), MachineState::IDLE);
+        assert!(chip.borrow().now_us() >= CALIBRATION_US as u64);
+
+        cc1101.set_radio_mode_within(RadioMode::Sleep, &mut delay, BUDGET).unwrap();
+        assert_eq!(chip.borrow().state(), MachineState::SLEEP);
+
+        // Waking up on the next access.
+        assert_eq!(cc1101.get_machine_state().unwrap(), MachineState::IDLE);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let chip = RefCell::new(SimChip::new());
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        assert_eq!(
+            cc1101.set_radio_mode_within(RadioMode::Calibrate, &mut delay, PollBudget::new(10, 2)),
+            Err(Error::Timeout {
+                expected: MachineState::IDLE,
+                last_seen: MachineState::MANCAL,
+            })
+        );
+
+        // Frequency programming stops the calibration first.
+        let frequency =
+            cc1101.set_frequency_within(868_000_000, &mut delay, PollBudget::new(10, 2));
+        assert_eq!(frequency.unwrap().actual, 867_999_938);
+        assert_eq!(chip.borrow().state(), MachineState::IDLE);
+    }
+
+    #[test]
+    fn test_receive_packet() {
+        let chip = RefCell::new(SimChip::new());
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        cc1101.set_packet_length(PacketLength::Variable(32)).unwrap();
+        cc1101.set_address_filter(AddressFilter::Device(0x42)).unwrap();
+
+        // Filtered out on address.
+        chip.borrow_mut().inject(SimPacket::new(&[3, 0x43, 1, 2]));
+        cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+        delay.delay_ms(10);
+        assert_eq!(cc1101.get_rx_bytes().unwrap(), 0);
+
+        chip.borrow_mut().inject(SimPacket::new(&[3, 0x42, 1, 2]).with_rssi_dbm(-50).with_lqi(5));
+        delay.delay_ms(10);
+        assert_eq!(chip.borrow().state(), MachineState::IDLE);
+
+        let mut buf = [0; 8];
+        let packet = cc1101.receive_packet(&mut buf).unwrap();
+        assert_eq!(packet.payload, &[1, 2]);
+        assert_eq!(packet.length, Some(3));
+        assert_eq!(packet.address, Some(0x42));
+        assert_eq!(
+            packet.status,
+            Some(RxStatus {
+                rssi_dbm: -50,
+                lqi: 5,
+                crc_ok: true,
+            })
+        );
+
+        // A packet that does not fit is dropped, and the next one is read from its start.
+        chip.borrow_mut().inject(SimPacket::new(&[6, 0x42, 1, 2, 3, 4, 5]));
+        cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+        delay.delay_ms(10);
+        assert_eq!(
+            cc1101.receive_packet(&mut buf[..4]).err(),
+            Some(Error::UserInputError(UserError::BufferTooSmall(5)))
+        );
+        assert_eq!(chip.borrow().rx_fifo_len(), 0);
+        chip.borrow_mut().inject(SimPacket::new(&[2, 0x42, 9]));
+        cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+        delay.delay_ms(10);
+        let packet = cc1101.receive_packet(&mut buf).unwrap();
+        assert_eq!(
+            (packet.length, packet.address, packet.payload),
+            (Some(2), Some(0x42), &[9][..])
+        );
+
+        // In fixed packet length mode, nothing is read.
+        cc1101.set_packet_length(PacketLength::Fixed(6)).unwrap();
+        chip.borrow_mut().inject(SimPacket::new(&[0x42, 1, 2, 3, 4, 5]));
+        cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+        delay.delay_ms(10);
+        assert_eq!(
+            cc1101.receive_packet(&mut buf[..4]).err(),
+            Some(Error::UserInputError(UserError::BufferTooSmall(5)))
+        );
+        assert_eq!(cc1101.receive_packet(&mut buf).unwrap().payload, &[1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_transmit() {
+        let chip = RefCell::new(SimChip::new());
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        cc1101.set_packet_length(PacketLength::Variable(32)).unwrap();
+        cc1101.transmit_stream(&[3, 0x42, 1, 2], &mut delay, stream_config()).unwrap();
+
+        assert_eq!(chip.borrow().state(), MachineState::IDLE);
+        assert_eq!(chip.borrow_mut().take_transmitted().unwrap().data(), &[3, 0x42, 1, 2]);
+    }
+
+    #[test]
+    fn test_off_modes() {
+        let chip = RefCell::new(SimChip::new());
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        cc1101.set_packet_length(PacketLength::Variable(32)).unwrap();
+        cc1101.set_address_filter(AddressFilter::Device(0x42)).unwrap();
+
+        cc1101.set_txoff_mode(TxOffMode::Rx).unwrap();
+        cc1101.transmit_stream(&[3, 0x42, 1, 2], &mut delay, stream_config()).unwrap();
+        assert_eq!(chip.borrow().state(), MachineState::RX);
+        assert!(chip.borrow_mut().take_transmitted().is_some());
+
+        cc1101.set_txoff_mode(TxOffMode::StayInTx).unwrap();
+        cc1101.transmit_stream(&[3, 0x42, 3, 4], &mut delay, stream_config()).unwrap();
+        delay.delay_ms(10);
+        assert_eq!(chip.borrow().state(), MachineState::TX);
+        assert_eq!(chip.borrow_mut().take_transmitted().unwrap().data(), &[3, 0x42, 3, 4]);
+
+        // Acknowledge straight from RX, with the ACK written to the TX FIFO beforehand.
+        cc1101.set_radio_mode_within(RadioMode::Idle, &mut delay, BUDGET).unwrap();
+        cc1101.set_rxoff_mode(RxOffMode::Tx).unwrap();
+        cc1101.set_txoff_mode(TxOffMode::Idle).unwrap();
+        cc1101.write_data(&mut Some(2), &mut Some(0x01), &mut [0xAC]).unwrap();
+        chip.borrow_mut().inject(SimPacket::new(&[3, 0x42, 5, 6]));
+        cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+        delay.delay_ms(10);
+
+        // The appended status bytes are read into, and cleared at, the end of `buf`.
+        let (mut addr, mut buf) = (0, [0; 4]);
+        assert_eq!(cc1101.receive(&mut addr, &mut buf, &mut delay, BUDGET).unwrap(), 3);
+        assert_eq!((addr, buf), (0x42, [5, 6, 0, 0]));
+        assert_eq!(chip.borrow().state(), MachineState::IDLE);
+        assert_eq!(chip.borrow_mut().take_transmitted().unwrap().data(), &[2, 0x01, 0xAC]);
+
+        let expected = RadioConfig::default()
+            .packet_length(PacketLength::Variable(32))
+            .address_filter(AddressFilter::Device(0x42))
+            .rxoff_mode(RxOffMode::Tx)
+            .txoff_mode(TxOffMode::Idle);
+        assert_eq!(chip.borrow().config(), expected);
+
+        // Without a packet, receive gives up once the budget is spent.
+        cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+        assert_eq!(
+            cc1101.receive(&mut addr, &mut buf, &mut delay, PollBudget::new(100, 5)),
+            Err(Error::Timeout {
+                expected: MachineState::TX,
+                last_seen: MachineState::RX,
+            })
+        );
+    }
+
+    #[test]
+    fn test_state_machine_settings() {
+        let chip = RefCell::new(SimChip::new());
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        cc1101.set_po_timeout(PoTimeout::EXPIRE_COUNT_64).unwrap();
+        cc1101.pin_control_enable(true).unwrap();
+        cc1101.set_xosc_force_on(true).unwrap();
+        cc1101.set_rx_time(RxTime::Div64).unwrap();
+        cc1101.set_rx_time_rssi(true).unwrap();
+        cc1101.set_rx_time_qualifier(RxTimeQualifier::SyncWordOrPqi).unwrap();
+
+        let expected = RadioConfig::default()
+            .po_timeout(PoTimeout::EXPIRE_COUNT_64)
+            .pin_control(true)
+            .xosc_force_on(true)
+            .rx_time(RxTime::Div64)
+            .rx_time_rssi(true)
+            .rx_time_qualifier(RxTimeQualifier::SyncWordOrPqi);
+        assert_eq!(chip.borrow().config(), expected);
+        assert_eq!(expected.as_bytes()[0x18], 0x0B);
+        assert_eq!(expected.as_bytes()[0x16], 0x1B);
+    }
+
+    #[test]
+    fn test_stream_long_packets() {
+        let data: [u8; 300] = core::array::from_fn(|i| i as u8);
+
+        let chip = RefCell::new(SimChip::new());
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        cc1101.set_fifo_threshold(FifoThreshold::TX_61_RX_4).unwrap();
+        let before = chip.borrow().config();
+        let mut gdo = SimGdo::new(&chip, Gdo::Gdo0);
+        cc1101
+            .transmit_stream_gdo(&data, Gdo::Gdo0, &mut gdo, &mut delay, stream_config())
+            .unwrap();
+        let sent = chip.borrow_mut().take_transmitted().unwrap();
+        assert_eq!(sent.data(), &data);
+        // The FIFO threshold, packet length and GDO settings are restored.
+        assert_eq!(chip.borrow().config(), before);
+
+        assert_eq!(
+            cc1101.transmit_stream(&[], &mut delay, stream_config()),
+            Err(Error::UserInputError(UserError::BufferTooSmall(1)))
+        );
+
+        chip.borrow_mut().inject(sent);
+        let mut buf = [0; 302];
+        let read = cc1101
+            .receive_stream(&mut buf, StreamLength::Fixed(300), &mut delay, stream_config())
+            .unwrap();
+        assert_eq!(read, 302);
+        assert_eq!(&buf[..300], &data);
+        assert_eq!(chip.borrow().config(), before);
+    }
+
+    #[test]
+    fn test_overflow_recovery() {
+        let chip = RefCell::new(SimChip::new());
+        let mut delay = SimDelay::new(&chip);
+        let mut cc1101 = Cc1101::new(SimSpi::new(&chip)).unwrap();
+
+        cc1101.set_packet_length(PacketLength::Fixed(100)).unwrap();
+        chip.borrow_mut().inject(SimPacket::new(&[0; 100]));
+        cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+        delay.delay_ms(20);
+        assert_eq!(chip.borrow().state(), MachineState::RXFIFO_OVERFLOW);
+
+        cc1101.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+        assert_eq!(chip.borrow().state(), MachineState::RX);
+        assert_eq!(chip.borrow().rx_fifo_len(), 0);
+    }
+}
@@ -0,0 +1,320 @@
+//! Virtual air interface linking several simulated chips.
+//!
+//! A packet is put on the medium once its transmitter has sent the last byte, and is delivered
+//! to every other chip that is in RX, tuned to the same carrier frequency and using a
+//! compatible modem configuration. The receiver then applies its own packet length, address
+//! and CRC handling as for [`SimChip::inject`]. A packet that reaches a receiver which is still
+//! busy with another one collides with it.
+//!
+//! The carrier sense qualifiers of `MDMCFG2.SYNC_MODE` are not modelled, and a receiver that
+//! checks CRC fails it on packets from a transmitter that does not append one.
+
+use core::cell::{Cell, RefCell};
+
+use hal::delay::DelayNs;
+
+use super::SimChip;
+use crate::lowlevel::registers::*;
+use crate::RadioConfig;
+
+/// Longest step of simulated time between two deliveries.
+const STEP_NS: u64 = 100_000;
+
+/// Impairments applied by a [`SimMedium`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MediumConfig {
+    /// Chance, in percent, that a receiver misses a packet.
+    pub loss_percent: u8,
+    /// Chance, in percent, that a receiver gets a packet with a bit error and a failed CRC.
+    pub corruption_percent: u8,
+    /// Signal strength at the receiver, unless set per link.
+    pub rssi_dbm: i16,
+    /// Link Quality Indicator reported by the receiver.
+    pub lqi: u8,
+    /// Whether a packet reaching a busy receiver corrupts the packet being received. If not,
+    /// the later packet is just lost.
+    pub collisions: bool,
+    /// Seed for the loss and corruption generator, must not be zero.
+    pub seed: u32,
+}
+
+impl Default for MediumConfig {
+    /// A perfect medium.
+    fn default() -> Self {
+        MediumConfig {
+            loss_percent: 0,
+            corruption_percent: 0,
+            rssi_dbm: -60,
+            lqi: 0x10,
+            collisions: true,
+            seed: 0x2545_F491,
+        }
+    }
+}
+
+/// Virtual air interface linking `N` simulated chips.
+pub struct SimMedium<'a, const N: usize> {
+    chips: [&'a RefCell<SimChip>; N],
+    config: MediumConfig,
+    rssi_dbm: [[i16; N]; N],
+    rng: Cell<u32>,
+}
+
+impl<'a, const N: usize> SimMedium<'a, N> {
+    pub fn new(chips: [&'a RefCell<SimChip>; N], config: MediumConfig) -> Self {
+        SimMedium {
+            chips,
+            config,
+            rssi_dbm: [[config.rssi_dbm; N]; N],
+            rng: Cell::new(config.seed.max(1)),
+        }
+    }
+
+    /// Set the signal strength at chip `to` of packets sent by chip `from`.
+    pub fn set_link_rssi_dbm(&mut self, from: usize, to: usize, rssi_dbm: i16) {
+        self.rssi_dbm[from][to] = rssi_dbm;
+    }
+
+    /// Delay that lets simulated time pass on all chips, delivering packets as they are sent.
+    pub fn delay(&self) -> MediumDelay<'_, 'a, N> {
+        MediumDelay {
+            medium: self,
+        }
+    }
+
+    /// Let `ns` nanoseconds pass on all chips, delivering packets as they are sent.
+    pub fn advance_ns(&self, ns: u64) {
+        let mut left = ns;
+        while left > 0 {
+            let step = left.min(STEP_NS);
+            for chip in self.chips {
+                chip.borrow_mut().advance_ns(step);
+            }
+            self.deliver();
+            left -= step;
+        }
+    }
+
+    /// Deliver packets that have been sent since the last call.
+    pub fn deliver(&self) {
+        for (from, sender) in self.chips.iter().enumerate() {
+            let Some(packet) = sender.borrow_mut().take_transmitted() else {
+                continue;
+            };
+            let tx_config = sender.borrow().config();
+
+            for (to, receiver) in self.chips.iter().enumerate() {
+                if to == from {
+                    continue;
+                }
+                let mut receiver = receiver.borrow_mut();
+                if !receiver.listening() || !compatible(&tx_config, &receiver.config()) {
+                    continue;
+                }
+                if self.chance(self.config.loss_percent) {
+                    continue;
+                }
+
+                let mut packet = packet
+                    .clone()
+                    .with_rssi_dbm(self.rssi_dbm[from][to])
+                    .with_lqi(self.config.lqi)
+                    .with_crc_ok(crc_ok(&tx_config, &receiver.config()));
+                if self.chance(self.config.corruption_percent) && !packet.data().is_empty() {
+                    let bit = self.next() as usize % (packet.data().len() * 8);
+                    packet.data[bit / 8] ^= 1 << (bit % 8);
+                    packet.crc_ok = false;
+                }
+                receiver.receive_from_air(packet, self.config.collisions);
+            }
+        }
+    }
+
+    fn chance(&self, percent: u8) -> bool {
+        percent > 0 && self.next() % 100 < percent as u32
+    }
+
+    /// xorshift32.
+    fn next(&self) -> u32 {
+        let mut x = self.rng.get();
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng.set(x);
+        x
+    }
+}
+
+/// Delay that lets simulated time pass on all chips of a [`SimMedium`].
+pub struct MediumDelay<'m, 'a, const N: usize> {
+    medium: &'m SimMedium<'a, N>,
+}
+
+impl<const N: usize> DelayNs for MediumDelay<'_, '_, N> {
+    fn delay_ns(&mut self, ns: u32) {
+        self.medium.advance_ns(ns as u64);
+    }
+}
+
+/// Whether a receiver configured as `rx` picks up packets from a transmitter configured as
+/// `tx`: same carrier frequency, modem settings and sync word.
+fn compatible(tx: &RadioConfig, rx: &RadioConfig) -> bool {
+    let modem = |c: &RadioConfig| {
+        (
+            c.read(config::MDMCFG4).drate_e(),
+            c.read(config::MDMCFG3).drate_m(),
+            c.read(config::MDMCFG2).mod_format(),
+            c.read(config::MDMCFG2).manchester_en(),
+            c.read(config::MDMCFG1).fec_en(),
+            c.read(config::PKTCTRL0).white_data(),
+        )
+    };
+    let sync_word = |c: &RadioConfig| (c.read(config::SYNC1).sync(), c.read(config::SYNC0).sync());
+
+    let tx_sync = tx.read(config::MDMCFG2).sync_mode();
+    let sync_ok = match rx.read(config::MDMCFG2).sync_mode() {
+        // No sync word, or carrier sense only.
+        0 | 4 => true,
+        // 30 of 32 sync bits, the transmitter must repeat the sync word.
+        3 | 7 => matches!(tx_sync, 3 | 7) && sync_word(tx) == sync_word(rx),
+        _ => !matches!(tx_sync, 0 | 4) && sync_word(tx) == sync_word(rx),
+    };
+
+    sync_ok && carrier(tx) == carrier(rx) && modem(tx) == modem(rx)
+}
+
+/// Carrier frequency in units of FXOSC/2^18, see section 21 "Frequency Programming" of the
+/// data sheet.
+pub(super) fn carrier(c: &RadioConfig) -> u64 {
+    let freq = (c.read(config::FREQ2).freq() as u64) << 16
+        | (c.read(config::FREQ1).freq() as u64) << 8
+        | c.read(config::FREQ0).freq() as u64;
+    let chanspc_m = c.read(config::MDMCFG0).chanspc_m() as u64;
+    let chanspc_e = c.read(config::MDMCFG1).chanspc_e();
+    let chan = c.read(config::CHANNR).chan() as u64;
+    4 * freq + ((256 + chanspc_m) << chanspc_e) * chan
+}
+
+/// Whether the receiver's CRC check, if any, passes.
+fn crc_ok(tx: &RadioConfig, rx: &RadioConfig) -> bool {
+    rx.read(config::PKTCTRL0).crc_en() == 0 || tx.read(config::PKTCTRL0).crc_en() != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::SimSpi;
+    use crate::{
+        AddressFilter, Cc1101, FifoThreshold, PacketLength, PollBudget, RadioMode, StreamConfig,
+    };
+
+    const BUDGET: PollBudget = PollBudget::new(100, 1_000);
+    const STREAM: StreamConfig = StreamConfig {
+        threshold: FifoThreshold::TX_33_RX_32,
+        budget: BUDGET,
+    };
+
+    #[test]
+    fn test_delivery() {
+        let chips: [RefCell<SimChip>; 3] = Default::default();
+        let mut medium = SimMedium::new([&chips[0], &chips[1], &chips[2]], MediumConfig::default());
+        medium.set_link_rssi_dbm(0, 1, -42);
+        let mut delay = medium.delay();
+
+        let mut tx = Cc1101::new(SimSpi::new(&chips[0])).unwrap();
+        let mut rx = Cc1101::new(SimSpi::new(&chips[1])).unwrap();
+        let mut other = Cc1101::new(SimSpi::new(&chips[2])).unwrap();
+        for cc1101 in [&mut tx, &mut rx, &mut other] {
+            cc1101.set_packet_length(PacketLength::Variable(32)).unwrap();
+            cc1101.set_address_filter(AddressFilter::Device(0x42)).unwrap();
+        }
+        other.0.write_register(config::CHANNR, |w| w.chan(1)).unwrap();
+        rx.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+        other.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+
+        tx.transmit_stream(&[3, 0x42, 1, 2], &mut delay, STREAM).unwrap();
+        delay.delay_ms(5);
+
+        let mut buf = [0; 8];
+        let packet = rx.receive_packet(&mut buf).unwrap();
+        assert_eq!(packet.payload, &[1, 2]);
+        assert_eq!(packet.status.unwrap().rssi_dbm, -42);
+        assert_eq!(other.get_rx_bytes().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_sync_word_mismatch() {
+        let chips: [RefCell<SimChip>; 2] = Default::default();
+        let medium = SimMedium::new([&chips[0], &chips[1]], MediumConfig::default());
+        let mut delay = medium.delay();
+
+        let mut tx = Cc1101::new(SimSpi::new(&chips[0])).unwrap();
+        let mut rx = Cc1101::new(SimSpi::new(&chips[1])).unwrap();
+        rx.set_sync_mode(crate::SyncMode::Match16of16(0x1234)).unwrap();
+        rx.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+
+        tx.set_packet_length(PacketLength::Variable(32)).unwrap();
+        tx.transmit_stream(&[2, 1, 2], &mut delay, STREAM).unwrap();
+        delay.delay_ms(5);
+        assert_eq!(rx.get_rx_bytes().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_loss_and_corruption() {
+        let chips: [RefCell<SimChip>; 2] = Default::default();
+        let lossy = MediumConfig {
+            loss_percent: 100,
+            ..MediumConfig::default()
+        };
+        let noisy = MediumConfig {
+            corruption_percent: 100,
+            ..MediumConfig::default()
+        };
+
+        for (config, expected) in [(lossy, 0), (noisy, 3)] {
+            let medium = SimMedium::new([&chips[0], &chips[1]], config);
+            let mut delay = medium.delay();
+            let mut tx = Cc1101::new(SimSpi::new(&chips[0])).unwrap();
+            let mut rx = Cc1101::new(SimSpi::new(&chips[1])).unwrap();
+            rx.set_packet_length(PacketLength::Fixed(3)).unwrap();
+            rx.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+
+            tx.transmit_stream(&[1, 2, 3], &mut delay, STREAM).unwrap();
+            delay.delay_ms(5);
+
+            let mut buf = [0; 5];
+            if expected == 0 {
+                assert_eq!(rx.get_rx_bytes().unwrap(), 0);
+            } else {
+                let packet = rx.receive_packet(&mut buf).unwrap();
+                assert_ne!(packet.payload, &[1, 2, 3]);
+                assert!(!packet.status.unwrap().crc_ok);
+            }
+        }
+    }
+
+    #[test]
+    fn test_collision() {
+        let chips: [RefCell<SimChip>; 3] = Default::default();
+        let medium = SimMedium::new([&chips[0], &chips[1], &chips[2]], MediumConfig::default());
+        let mut delay = medium.delay();
+
+        let mut rx = Cc1101::new(SimSpi::new(&chips[2])).unwrap();
+        rx.set_packet_length(PacketLength::Fixed(16)).unwrap();
+        rx.set_radio_mode_within(RadioMode::Receive, &mut delay, BUDGET).unwrap();
+
+        // Both transmitters start at the same time.
+        for chip in &chips[..2] {
+            let mut tx = Cc1101::new(SimSpi::new(chip)).unwrap();
+            tx.set_packet_length(PacketLength::Fixed(16)).unwrap();
+            tx.0.write_burst(multi::FIFO, &[0x55; 16]).unwrap();
+            tx.enable_tx().unwrap();
+        }
+        delay.delay_ms(5);
+
+        let mut buf = [0; 18];
+        let packet = rx.receive_packet(&mut buf).unwrap();
+        assert!(!packet.status.unwrap().crc_ok);
+        assert_eq!(rx.get_rx_bytes().unwrap(), 0);
+    }
+}
@@ -0,0 +1,231 @@
+//! Import and export of TI SmartRF Studio register settings.
+//!
+//! Two export formats are understood: the plain "name value" register list (e.g.
+//! `IOCFG2 0x29`), and the C header style (e.g. `#define SMARTRF_SETTING_IOCFG2 0x29`).
+//! Trailing descriptions and `//` comments are ignored.
+
+use std::fmt::{self, Display, Formatter, Write};
+use std::string::String;
+use std::vec::Vec;
+
+use hal::spi::SpiDevice;
+
+use crate::lowlevel::registers::config;
+use crate::{Cc1101, Error, RadioConfig};
+
+/// Registers that are for test only and are never imported.
+const TEST_ONLY: [&str; 3] = ["FSTEST", "PTEST", "AGCTEST"];
+
+/// Register name prefixes used by SmartRF Studio templates.
+const PREFIXES: [&str; 2] = ["SMARTRF_SETTING_", "CC1101_"];
+
+/// SmartRF Studio export format.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SmartRfFormat {
+    /// One `NAME 0xVV` pair per line.
+    NameValue,
+    /// One `#define SMARTRF_SETTING_NAME 0xVV` per line.
+    CHeader,
+}
+
+/// A register setting that was read but not imported.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SkippedSetting {
+    /// Line number, starting at 1.
+    pub line: usize,
+    /// Register name, without template prefix.
+    pub name: String,
+    /// Register value.
+    pub value: u8,
+}
+
+/// Result of importing a SmartRF Studio export.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SmartRfImport {
+    /// Reset values, overridden by every imported setting.
+    pub config: RadioConfig,
+    /// Test-only registers (FSTEST, PTEST, AGCTEST), not imported.
+    pub test_only: Vec<SkippedSetting>,
+    /// Settings that are not configuration registers (e.g. PA_TABLE0), not imported.
+    pub unknown: Vec<SkippedSetting>,
+}
+
+/// Errors while parsing a SmartRF Studio export.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SmartRfError {
+    /// A configuration register has a value that is not a byte.
+    InvalidValue {
+        /// Line number, starting at 1.
+        line: usize,
+        /// Register name, without template prefix.
+        name: String,
+    },
+}
+
+impl Display for SmartRfError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidValue {
+                line,
+                name,
+            } => {
+                write!(f, "Invalid value for {} on line {}", name, line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SmartRfError {}
+
+/// Parse a SmartRF Studio register export into a full configuration.
+pub fn parse_smartrf(input: &str) -> Result<SmartRfImport, SmartRfError> {
+    let mut import = SmartRfImport {
+        config: RadioConfig::default(),
+        test_only: Vec::new(),
+        unknown: Vec::new(),
+    };
+    let mut regs = *import.config.as_bytes();
+
+    for (index, line) in input.lines().enumerate() {
+        let line_no = index + 1;
+        let line = line.split("//").next().unwrap_or_default();
+        let line = line.replace(['{', '}', ','], " ");
+        let mut tokens = line.split_whitespace();
+
+        let name = match tokens.next() {
+            Some("#define") => tokens.next(),
+            Some(token) if token.starts_with('#') => None,
+            token => token,
+        };
+        let Some(name) = name else {
+            continue;
+        };
+        let name = PREFIXES.iter().fold(name, |name, prefix| name.trim_start_matches(prefix));
+        let addr = config::NAMES.iter().position(|n| *n == name);
+
+        let value = match (tokens.next().and_then(parse_value), addr) {
+            (Some(value), _) => value,
+            // Headings, include guards and the like.
+            (None, None) => continue,
+            (None, Some(_)) => {
+                return Err(SmartRfError::InvalidValue {
+                    line: line_no,
+                    name: name.into(),
+                })
+            }
+        };
+
+        let setting = SkippedSetting {
+            line: line_no,
+            name: name.into(),
+            value,
+        };
+        match addr {
+            Some(_) if TEST_ONLY.contains(&name) => import.test_only.push(setting),
+            Some(addr) => regs[addr] = value,
+            None => import.unknown.push(setting),
+        }
+    }
+
+    import.config = RadioConfig::from_bytes(regs);
+    Ok(import)
+}
+
+/// Export a configuration in a SmartRF Studio register export format.
+pub fn export_smartrf(config: &RadioConfig, format: SmartRfFormat) -> String {
+    let mut out = String::new();
+    for (name, value) in config::NAMES.iter().zip(config.as_bytes()) {
+        // Writing to a `String` cannot fail.
+        let _ = match format {
+            SmartRfFormat::NameValue => writeln!(out, "{:<10} 0x{:02X}", name, value),
+            SmartRfFormat::CHeader => {
+                writeln!(out, "#define SMARTRF_SETTING_{:<10} 0x{:02X}", name, value)
+            }
+        };
+    }
+    out
+}
+
+fn parse_value(token: &str) -> Option<u8> {
+    match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => token.parse().ok(),
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Export the live configuration in a SmartRF Studio register export format.
+    pub fn export_smartrf(&mut self, format: SmartRfFormat) -> Result<String, Error<SpiE>> {
+        Ok(export_smartrf(&self.read_config()?, format))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_name_value() {
+        let import = parse_smartrf(
+            "IOCFG0     0x06  GDO0 Output Pin Configuration\n\
+             FREQ2      0x10  Frequency Control Word, High Byte\n\
+             FSTEST     0x59  Frequency Synthesizer Calibration Control\n\
+             PA_TABLE0  0xC0\n",
+        )
+        .unwrap();
+
+        assert_eq!(import.config.as_bytes()[0x02], 0x06);
+        assert_eq!(import.config.as_bytes()[0x0D], 0x10);
+        assert_eq!(import.config.as_bytes()[0x29], 0x59); // reset, not imported
+        assert_eq!(import.test_only[0].name, "FSTEST");
+        assert_eq!(
+            import.unknown[0],
+            SkippedSetting {
+                line: 4,
+                name: "PA_TABLE0".into(),
+                value: 0xC0
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_c_header() {
+        let import = parse_smartrf(
+            "#ifndef SMARTRF_CC1101_H\n\
+             #define SMARTRF_CC1101_H\n\
+             #define SMARTRF_RADIO_CC1101\n\
+             #define SMARTRF_SETTING_IOCFG2     0x0B // serial clock\n\
+             #define SMARTRF_SETTING_MDMCFG2    0x13\n\
+             #endif\n",
+        )
+        .unwrap();
+
+        assert_eq!(import.config.as_bytes()[0x00], 0x0B);
+        assert_eq!(import.config.as_bytes()[0x12], 0x13);
+        assert!(import.unknown.is_empty());
+    }
+
+    #[test]
+    fn test_parse_invalid_value() {
+        assert_eq!(
+            parse_smartrf("PKTLEN 0x100\n"),
+            Err(SmartRfError::InvalidValue {
+                line: 1,
+                name: "PKTLEN".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let config = RadioConfig::default().frequency(868_000_000).channel(3);
+        for format in [SmartRfFormat::NameValue, SmartRfFormat::CHeader] {
+            let import = parse_smartrf(&export_smartrf(&config, format)).unwrap();
+            assert_eq!(import.config, config);
+            assert_eq!(import.test_only.len(), 3);
+        }
+    }
+}
@@ -0,0 +1,500 @@
+//! Streaming of packets larger than the 64-byte FIFO.
+//!
+//! The FIFO is refilled (TX) or drained (RX) while the packet is on air. Servicing is paced by
+//! `FIFOTHR.FIFO_THR`: either by polling `TXBYTES`/`RXBYTES`, or by watching a GDO pin that
+//! has been configured to signal the FIFO threshold. Packets longer than 255 bytes are sent
+//! in infinite packet length mode, switching to fixed packet length mode once fewer than 256
+//! bytes remain, as described in section 15.4 "Packet Handling in Transmit Mode" of the data
+//! sheet (TI document SWRS061I).
+
+use hal::{delay::DelayNs, digital::InputPin, spi::SpiDevice};
+
+use crate::lowlevel::registers::*;
+use crate::{
+    off_state, Cc1101, Error, FifoThreshold, Gdo, GdoCfg, LengthConfig, MachineState, PollBudget,
+    RadioMode, UserError, FIFO_SIZE_MAX,
+};
+
+/// Span of the radio's internal packet byte counter (`PKTLEN` is 8 bits).
+pub(crate) const BYTE_COUNTER_SPAN: usize = 256;
+
+/// Number of status bytes appended to a received packet when `PKTCTRL1.APPEND_STATUS` is set.
+const RX_STATUS_LEN: usize = 2;
+
+/// Streaming configuration.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct StreamConfig {
+    /// FIFO threshold at which the FIFO is refilled (TX) or drained (RX).
+    pub threshold: FifoThreshold,
+    /// Bound on how long to wait for the FIFO to make progress.
+    pub budget: PollBudget,
+}
+
+/// Length of a streamed packet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StreamLength {
+    /// Known number of bytes following the sync word, may exceed 255.
+    Fixed(usize),
+    /// Variable length packet, the first byte after the sync word holds the length (max 255).
+    Variable,
+}
+
+/// FIFO threshold and packet length registers saved around a streaming operation.
+pub(crate) struct SavedRegisters {
+    pub(crate) fifo_thr: u8,
+    pub(crate) length_config: u8,
+    pub(crate) packet_length: u8,
+}
+
+/// GDO configuration saved around a streaming operation.
+pub(crate) struct SavedGdo {
+    pub(crate) gdo: Gdo,
+    pub(crate) cfg: u8,
+    pub(crate) inv: u8,
+}
+
+/// Number of bytes to write to a TX FIFO holding `in_fifo` bytes, with `left` bytes of the
+/// packet still to be written. None until the FIFO has drained below the threshold, unless
+/// the rest of the packet fits.
+pub(crate) fn tx_chunk(left: usize, in_fifo: usize, threshold: FifoThreshold) -> usize {
+    let room = FIFO_SIZE_MAX as usize - in_fifo;
+    if left > 0 && (in_fifo < threshold.tx_bytes() as usize || left <= room) {
+        left.min(room)
+    } else {
+        0
+    }
+}
+
+/// Number of bytes to read from an RX FIFO holding `in_fifo` bytes, with `read` of the
+/// `expected` bytes of the packet read. `expected` is unknown until the length byte is read.
+pub(crate) fn rx_chunk(
+    expected: Option<usize>,
+    read: usize,
+    in_fifo: usize,
+    threshold: FifoThreshold,
+) -> usize {
+    match expected {
+        // Read the length byte as soon as it is safe to, see below.
+        None if in_fifo > 1 => 1,
+        None => 0,
+        Some(expected) if in_fifo >= expected - read => expected - read,
+        // Never read the last byte of the RX FIFO before the packet has been fully received,
+        // it may be read twice (CC1101 errata, SWRZ020E).
+        Some(_) if in_fifo >= threshold.rx_bytes() as usize => in_fifo - 1,
+        Some(_) => 0,
+    }
+}
+
+/// Number of bytes expected in the RX FIFO for `length`, unknown for a variable length packet
+/// until its length byte is read. Fails if `buf_len` is too small.
+pub(crate) fn rx_expected(
+    length: StreamLength,
+    buf_len: usize,
+    status_len: usize,
+) -> Result<Option<usize>, UserError> {
+    match length {
+        StreamLength::Fixed(total) if total + status_len > buf_len => {
+            Err(UserError::BufferTooSmall(total + status_len))
+        }
+        StreamLength::Fixed(total) => Ok(Some(total + status_len)),
+        StreamLength::Variable if buf_len <= status_len => {
+            Err(UserError::BufferTooSmall(status_len + 1))
+        }
+        StreamLength::Variable => Ok(None),
+    }
+}
+
+/// Number of bytes expected in the RX FIFO for a variable length packet with length byte
+/// `length`. Fails if `buf_len` is too small.
+pub(crate) fn rx_expected_variable(
+    length: u8,
+    buf_len: usize,
+    status_len: usize,
+) -> Result<usize, UserError> {
+    let required = 1 + length as usize + status_len;
+    if required > buf_len {
+        Err(UserError::BufferTooSmall(required))
+    } else {
+        Ok(required)
+    }
+}
+
+/// Number of status bytes appended to a received packet.
+pub(crate) fn rx_status_len(rx_status_fields: bool) -> usize {
+    if rx_status_fields {
+        RX_STATUS_LEN
+    } else {
+        0
+    }
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Transmit `data` as one packet, refilling the TX FIFO by polling `TXBYTES`.
+    ///
+    /// `data` is everything following the sync word, including the length and address
+    /// bytes if the receiver expects them. It may exceed both the FIFO size and 255 bytes,
+    /// but must not be empty. `FIFOTHR.FIFO_THR` and the packet length settings are restored
+    /// afterwards. Returns once the radio has entered the state configured with [`Self::set_txoff_mode`],
+    /// or has drained the TX FIFO if that is TX.
+    pub fn transmit_stream<D: DelayNs>(
+        &mut self,
+        data: &[u8],
+        delay: &mut D,
+        config: StreamConfig,
+    ) -> Result<(), Error<SpiE>> {
+        self.stream_tx(data, delay, config, &mut || true)
+    }
+
+    /// Transmit `data` as one packet, refilling the TX FIFO when `pin` signals room.
+    ///
+    /// `gdo` is configured as [`GdoCfg::TX_FIFO_FILLED`] for the duration of the transfer
+    /// and restored afterwards. `pin` is the MCU input connected to it. See
+    /// [`Self::transmit_stream`].
+    pub fn transmit_stream_gdo<D: DelayNs, P: InputPin>(
+        &mut self,
+        data: &[u8],
+        gdo: Gdo,
+        pin: &mut P,
+        delay: &mut D,
+        config: StreamConfig,
+    ) -> Result<(), Error<SpiE>> {
+        let saved = self.configure_gdo(gdo, GdoCfg::TX_FIFO_FILLED)?;
+        // Deasserted below the TX FIFO threshold. On pin errors, fall back to polling.
+        let result = self.stream_tx(data, delay, config, &mut || pin.is_low().unwrap_or(true));
+        let restored = self.restore_gdo(saved);
+        result?;
+        restored
+    }
+
+    /// Receive one packet into `buf`, draining the RX FIFO by polling `RXBYTES`.
+    ///
+    /// `buf` receives everything following the sync word, including the length and
+    /// address bytes and the appended status bytes, if enabled. Returns the number of
+    /// bytes written to `buf`. `FIFOTHR.FIFO_THR` and the packet length settings are
+    /// restored afterwards.
+    pub fn receive_stream<D: DelayNs>(
+        &mut self,
+        buf: &mut [u8],
+        length: StreamLength,
+        delay: &mut D,
+        config: StreamConfig,
+    ) -> Result<usize, Error<SpiE>> {
+        self.stream_rx(buf, length, delay, config, &mut || true)
+    }
+
+    /// Receive one packet into `buf`, draining the RX FIFO when `pin` signals data.
+    ///
+    /// `gdo` is configured as [`GdoCfg::RX_FIFO_FILLED_END_OF_PKT`] for the duration of the
+    /// transfer and restored afterwards. `pin` is the MCU input connected to it. See
+    /// [`Self::receive_stream`].
+    pub fn receive_stream_gdo<D: DelayNs, P: InputPin>(
+        &mut self,
+        buf: &mut [u8],
+        length: StreamLength,
+        gdo: Gdo,
+        pin: &mut P,
+        delay: &mut D,
+        config: StreamConfig,
+    ) -> Result<usize, Error<SpiE>> {
+        let saved = self.configure_gdo(gdo, GdoCfg::RX_FIFO_FILLED_END_OF_PKT)?;
+        // Asserted at the RX FIFO threshold or end of packet. On pin errors, fall back to polling.
+        let result =
+            self.stream_rx(buf, length, delay, config, &mut || pin.is_high().unwrap_or(true));
+        let restored = self.restore_gdo(saved);
+        let read = result?;
+        restored?;
+        Ok(read)
+    }
+
+    fn stream_tx<D: DelayNs>(
+        &mut self,
+        data: &[u8],
+        delay: &mut D,
+        config: StreamConfig,
+        ready: &mut dyn FnMut() -> bool,
+    ) -> Result<(), Error<SpiE>> {
+        if data.is_empty() {
+            return Err(Error::UserInputError(UserError::BufferTooSmall(1)));
+        }
+        let saved = self.save_registers()?;
+        let result = self.stream_tx_inner(data, delay, config, ready);
+        if let Err(Error::TxUnderflow) = result {
+            // Acknowledge the underflow, the original error takes precedence.
+            let _ = self.flush_tx_fifo_buffer();
+        }
+        let restored = self.restore_registers(saved);
+        result?;
+        restored
+    }
+
+    fn stream_tx_inner<D: DelayNs>(
+        &mut self,
+        data: &[u8],
+        delay: &mut D,
+        config: StreamConfig,
+        ready: &mut dyn FnMut() -> bool,
+    ) -> Result<(), Error<SpiE>> {
+        let total = data.len();
+
+        self.set_radio_mode_within(RadioMode::Idle, delay, config.budget)?;
+        self.flush_tx_fifo_buffer()?;
+        self.set_fifo_threshold(config.threshold)?;
+        let mut fixed = self.set_stream_length(total)?;
+
+        let mut written = total.min(FIFO_SIZE_MAX as usize);
+        self.0.write_burst(multi::FIFO, &data[..written])?;
+        self.enable_tx()?;
+
+        let mut polls = 0;
+        while written < total || !fixed {
+            if ready() {
+                let in_fifo = self.get_tx_bytes()? as usize;
+
+                if !fixed && total - (written - in_fifo) < BYTE_COUNTER_SPAN {
+                    self.0.modify_register(config::PKTCTRL0, |r| {
+                        r.length_config(LengthConfig::FIXED.into())
+                    })?;
+                    fixed = true;
+                }
+
+                let chunk = tx_chunk(total - written, in_fifo, config.threshold);
+                if chunk > 0 {
+                    self.0.write_burst(multi::FIFO, &data[written..written + chunk])?;
+                    written += chunk;
+                    polls = 0;
+                    continue;
+                }
+            }
+
+            self.poll_wait(MachineState::TX, &mut polls, delay, config.budget)?;
+        }
+
+        // Wait for the remaining bytes to go on air, and the radio to enter the TX off state.
+        let txoff = off_state(self.0.read_register(config::MCSM1)?.txoff_mode());
+        let mut polls = 0;
+        loop {
+            match self.get_machine_state()? {
+                MachineState::TXFIFO_UNDERFLOW => return Err(Error::TxUnderflow),
+                // Staying in TX, the radio sends preamble once the TX FIFO has drained.
+                MachineState::TX if txoff == MachineState::TX && self.get_tx_bytes()? == 0 => {
+                    return Ok(())
+                }
+                MachineState::TX | MachineState::TX_END => {
+                    self.poll_wait(txoff, &mut polls, delay, config.budget)?
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn stream_rx<D: DelayNs>(
+        &mut self,
+        buf: &mut [u8],
+        length: StreamLength,
+        delay: &mut D,
+        config: StreamConfig,
+        ready: &mut dyn FnMut() -> bool,
+    ) -> Result<usize, Error<SpiE>> {
+        let saved = self.save_registers()?;
+        let result = self.stream_rx_inner(buf, length, delay, config, ready);
+        if result.is_err() {
+            // Drop the partial packet, the original error takes precedence.
+            let _ = self
+                .set_radio_mode_within(RadioMode::Idle, delay, config.budget)
+                .and_then(|_| self.flush_rx_fifo_buffer());
+        }
+        let restored = self.restore_registers(saved);
+        let read = result?;
+        restored?;
+        Ok(read)
+    }
+
+    fn stream_rx_inner<D: DelayNs>(
+        &mut self,
+        buf: &mut [u8],
+        length: StreamLength,
+        delay: &mut D,
+        config: StreamConfig,
+        ready: &mut dyn FnMut() -> bool,
+    ) -> Result<usize, Error<SpiE>> {
+        let status_len = rx_status_len(self.0.rx_status_fields);
+        let mut expected =
+            rx_expected(length, buf.len(), status_len).map_err(Error::UserInputError)?;
+        let mut fixed = true;
+        let total = match length {
+            StreamLength::Fixed(total) => total,
+            StreamLength::Variable => 0,
+        };
+
+        self.set_radio_mode_within(RadioMode::Idle, delay, config.budget)?;
+        self.flush_rx_fifo_buffer()?;
+        self.set_fifo_threshold(config.threshold)?;
+        match length {
+            StreamLength::Fixed(total) => fixed = self.set_stream_length(total)?,
+            StreamLength::Variable => {
+                let max_len = (buf.len() - status_len - 1).min(u8::MAX as usize) as u8;
+                self.0.modify_register(config::PKTCTRL0, |r| {
+                    r.length_config(LengthConfig::VARIABLE.into())
+                })?;
+                self.0.write_register(config::PKTLEN, |w| w.packet_length(max_len))?;
+            }
+        }
+        self.enable_rx()?;
+
+        let mut read = 0;
+        let mut polls = 0;
+        loop {
+            if expected == Some(read) {
+                return Ok(read);
+            }
+
+            if ready() {
+                let in_fifo = self.rx_bytes_stable()? as usize;
+
+                if !fixed && total - (read + in_fifo).min(total) < BYTE_COUNTER_SPAN {
+                    self.0.modify_register(config::PKTCTRL0, |r| {
+                        r.length_config(LengthConfig::FIXED.into())
+                    })?;
+                    fixed = true;
+                }
+
+                let chunk = rx_chunk(expected, read, in_fifo, config.threshold);
+                if chunk > 0 {
+                    self.0.read_burst(multi::FIFO, &mut buf[read..read + chunk])?;
+                    if expected.is_none() {
+                        expected = Some(
+                            rx_expected_variable(buf[0], buf.len(), status_len)
+                                .map_err(Error::UserInputError)?,
+                        );
+                    }
+                    read += chunk;
+                    polls = 0;
+                    continue;
+                }
+            }
+
+            self.poll_wait(MachineState::RX, &mut polls, delay, config.budget)?;
+        }
+    }
+
+    /// Program `PKTCTRL0.LENGTH_CONFIG` and `PKTLEN` for a streamed packet of `total` bytes.
+    /// Returns whether fixed packet length mode is in effect.
+    fn set_stream_length(&mut self, total: usize) -> Result<bool, Error<SpiE>> {
+        let fixed = total < BYTE_COUNTER_SPAN;
+        let mode = if fixed {
+            LengthConfig::FIXED
+        } else {
+            LengthConfig::INFINITE
+        };
+        self.0.modify_register(config::PKTCTRL0, |r| r.length_config(mode.into()))?;
+        self.0.write_register(config::PKTLEN, |w| {
+            w.packet_length((total % BYTE_COUNTER_SPAN) as u8)
+        })?;
+        Ok(fixed)
+    }
+
+    fn save_registers(&mut self) -> Result<SavedRegisters, Error<SpiE>> {
+        Ok(SavedRegisters {
+            fifo_thr: self.0.read_register(config::FIFOTHR)?.fifo_thr(),
+            length_config: self.0.read_register(config::PKTCTRL0)?.length_config(),
+            packet_length: self.0.read_register(config::PKTLEN)?.packet_length(),
+        })
+    }
+
+    fn restore_registers(&mut self, saved: SavedRegisters) -> Result<(), Error<SpiE>> {
+        self.0.modify_register(config::FIFOTHR, |r| r.fifo_thr(saved.fifo_thr))?;
+        self.0.modify_register(config::PKTCTRL0, |r| r.length_config(saved.length_config))?;
+        self.0.write_register(config::PKTLEN, |w| w.packet_length(saved.packet_length))?;
+        Ok(())
+    }
+
+    /// Configure `gdo` as active high `cfg`, returning the previous configuration.
+    fn configure_gdo(&mut self, gdo: Gdo, cfg: GdoCfg) -> Result<SavedGdo, Error<SpiE>> {
+        let cfg = cfg.into();
+        let saved = match gdo {
+            Gdo::Gdo0 => {
+                let r = self.0.read_register(config::IOCFG0)?;
+                self.0.modify_register(config::IOCFG0, |r| r.gdo0_inv(0).gdo0_cfg(cfg))?;
+                SavedGdo {
+                    gdo,
+                    cfg: r.gdo0_cfg(),
+                    inv: r.gdo0_inv(),
+                }
+            }
+            Gdo::Gdo1 => {
+                let r = self.0.read_register(config::IOCFG1)?;
+                self.0.modify_register(config::IOCFG1, |r| r.gdo1_inv(0).gdo1_cfg(cfg))?;
+                SavedGdo {
+                    gdo,
+                    cfg: r.gdo1_cfg(),
+                    inv: r.gdo1_inv(),
+                }
+            }
+            Gdo::Gdo2 => {
+                let r = self.0.read_register(config::IOCFG2)?;
+                self.0.modify_register(config::IOCFG2, |r| r.gdo2_inv(0).gdo2_cfg(cfg))?;
+                SavedGdo {
+                    gdo,
+                    cfg: r.gdo2_cfg(),
+                    inv: r.gdo2_inv(),
+                }
+            }
+        };
+        Ok(saved)
+    }
+
+    fn restore_gdo(&mut self, saved: SavedGdo) -> Result<(), Error<SpiE>> {
+        let SavedGdo {
+            gdo,
+            cfg,
+            inv,
+        } = saved;
+        match gdo {
+            Gdo::Gdo0 => {
+                self.0.modify_register(config::IOCFG0, |r| r.gdo0_inv(inv).gdo0_cfg(cfg))?
+            }
+            Gdo::Gdo1 => {
+                self.0.modify_register(config::IOCFG1, |r| r.gdo1_inv(inv).gdo1_cfg(cfg))?
+            }
+            Gdo::Gdo2 => {
+                self.0.modify_register(config::IOCFG2, |r| r.gdo2_inv(inv).gdo2_cfg(cfg))?
+            }
+        }
+        Ok(())
+    }
+
+    /// Read `RXBYTES` until two consecutive reads agree (CC1101 errata, SWRZ020E).
+    fn rx_bytes_stable(&mut self) -> Result<u8, Error<SpiE>> {
+        let mut last = self.get_rx_bytes()?;
+        loop {
+            let num_rxbytes = self.get_rx_bytes()?;
+            if num_rxbytes == last {
+                return Ok(num_rxbytes);
+            }
+            last = num_rxbytes;
+        }
+    }
+
+    /// Wait one poll interval, failing with [`Error::Timeout`] once the budget is spent.
+    fn poll_wait<D: DelayNs>(
+        &mut self,
+        expected: MachineState,
+        polls: &mut u32,
+        delay: &mut D,
+        budget: PollBudget,
+    ) -> Result<(), Error<SpiE>> {
+        if *polls >= budget.polls {
+            let last_seen = self.get_machine_state()?;
+            return Err(Error::Timeout {
+                expected,
+                last_seen,
+            });
+        }
+        *polls += 1;
+        delay.delay_us(budget.interval_us);
+        Ok(())
+    }
+}
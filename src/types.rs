@@ -1,3 +1,6 @@
+use crate::lowlevel::convert::from_rssi_to_rssi_dbm;
+use crate::lowlevel::types::{AddressCheck, LengthConfig, SyncCheck};
+
 /// Radio operational mode.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum RadioMode {
@@ -8,6 +11,72 @@ pub enum RadioMode {
     Receive,
 }
 
+/// Bound on how long to poll the radio for a state change.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PollBudget {
+    /// Delay between two consecutive polls, in microseconds.
+    pub interval_us: u32,
+    /// Maximum number of polls before giving up.
+    pub polls: u32,
+}
+
+impl PollBudget {
+    /// Poll at most `polls` times, waiting `interval_us` between polls.
+    pub const fn new(interval_us: u32, polls: u32) -> Self {
+        PollBudget {
+            interval_us,
+            polls,
+        }
+    }
+
+    /// Poll every `interval_us` until at least `timeout_us` has passed.
+    pub const fn from_timeout_us(timeout_us: u32, interval_us: u32) -> Self {
+        let interval_us = if interval_us == 0 {
+            1
+        } else {
+            interval_us
+        };
+        PollBudget {
+            interval_us,
+            polls: timeout_us.div_ceil(interval_us),
+        }
+    }
+}
+
+/// A setting as programmed, after rounding to what the registers can hold.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Rounded {
+    /// Requested value.
+    pub requested: u64,
+    /// Value achieved by the programmed registers.
+    pub actual: u64,
+}
+
+impl Rounded {
+    pub const fn new(requested: u64, actual: u64) -> Self {
+        Rounded {
+            requested,
+            actual,
+        }
+    }
+
+    /// Rounding error, `actual - requested`.
+    pub const fn error(&self) -> i64 {
+        self.actual as i64 - self.requested as i64
+    }
+}
+
+/// General purpose digital output pin.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Gdo {
+    /// GDO0, configured through `IOCFG0`.
+    Gdo0,
+    /// GDO1, configured through `IOCFG1`. Shared with SO while CSn is low.
+    Gdo1,
+    /// GDO2, configured through `IOCFG2`.
+    Gdo2,
+}
+
 /// Packet length configuration.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum PacketLength {
@@ -52,3 +121,105 @@ pub enum SyncMode {
     /// 30 of 32 sync bits + carrier sense.
     Match30of32Cs(u16),
 }
+
+/// A packet read from the RX FIFO.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RxPacket<'a> {
+    /// Payload, excluding the length and address bytes.
+    pub payload: &'a [u8],
+    /// Length byte, present in variable packet length mode.
+    pub length: Option<u8>,
+    /// Address byte, present when address check is enabled.
+    pub address: Option<u8>,
+    /// Appended status, present when `PKTCTRL1.APPEND_STATUS` is enabled.
+    pub status: Option<RxStatus>,
+}
+
+/// Status bytes appended to a received packet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RxStatus {
+    /// Received signal strength, in dBm.
+    pub rssi_dbm: i16,
+    /// Link Quality Indicator.
+    pub lqi: u8,
+    /// The CRC of the packet matched.
+    pub crc_ok: bool,
+}
+
+impl RxStatus {
+    pub(crate) fn from_bytes(status: [u8; 2]) -> Self {
+        RxStatus {
+            rssi_dbm: from_rssi_to_rssi_dbm(status[0]),
+            lqi: status[1] & 0x7F,
+            crc_ok: status[1] & 0x80 != 0,
+        }
+    }
+}
+
+/// Packet framing as read back from `PKTCTRL0`, `PKTCTRL1` and `PKTLEN`.
+#[derive(Copy, Clone)]
+pub(crate) struct PacketFraming {
+    pub length_config: u8,
+    pub packet_length: u8,
+    pub address: bool,
+    pub status: bool,
+}
+
+impl PacketFraming {
+    pub fn variable(&self) -> bool {
+        self.length_config == LengthConfig::VARIABLE as u8
+    }
+
+    pub fn infinite(&self) -> bool {
+        self.length_config == LengthConfig::INFINITE as u8
+    }
+
+    /// Payload length, given the length byte in variable packet length mode.
+    pub fn payload_len(&self, length: Option<u8>) -> usize {
+        let length = length.unwrap_or(self.packet_length) as usize;
+        length.saturating_sub(self.address as usize)
+    }
+}
+
+impl PacketLength {
+    /// `PKTCTRL0.LENGTH_CONFIG` and `PKTLEN` values for this configuration.
+    pub(crate) fn fields(self) -> (LengthConfig, u8) {
+        match self {
+            PacketLength::Fixed(limit) => (LengthConfig::FIXED, limit),
+            PacketLength::Variable(max_limit) => (LengthConfig::VARIABLE, max_limit),
+            PacketLength::Infinite => (LengthConfig::INFINITE, 0xFF), // PKTLEN reset (max)
+        }
+    }
+}
+
+impl AddressFilter {
+    /// `PKTCTRL1.ADR_CHK` and `ADDR` values for this configuration.
+    pub(crate) fn fields(self) -> (AddressCheck, u8) {
+        match self {
+            AddressFilter::Disabled => (AddressCheck::DISABLED, 0),
+            AddressFilter::Device(addr) => (AddressCheck::SELF, addr),
+            AddressFilter::DeviceLowBroadcast(addr) => (AddressCheck::SELF_LOW_BROADCAST, addr),
+            AddressFilter::DeviceHighLowBroadcast(addr) => {
+                (AddressCheck::SELF_HIGH_LOW_BROADCAST, addr)
+            }
+        }
+    }
+}
+
+impl SyncMode {
+    /// `MDMCFG2.SYNC_MODE` and `SYNC1:SYNC0` values for this configuration.
+    pub(crate) fn fields(self) -> (SyncCheck, u16) {
+        let reset: u16 = 0xD391; // SYNC1/SYNC0 reset (default sync word)
+
+        match self {
+            SyncMode::Disabled => (SyncCheck::DISABLED, reset),
+            SyncMode::Match15of16(w) => (SyncCheck::CHECK_15_16, w),
+            SyncMode::Match16of16(w) => (SyncCheck::CHECK_16_16, w),
+            SyncMode::Match30of32(w) => (SyncCheck::CHECK_30_32, w),
+            SyncMode::CarrierSenseOnly => (SyncCheck::CHECK_0_0_CS, reset),
+            SyncMode::Match15of16Cs(w) => (SyncCheck::CHECK_15_16_CS, w),
+            SyncMode::Match16of16Cs(w) => (SyncCheck::CHECK_16_16_CS, w),
+            SyncMode::Match30of32Cs(w) => (SyncCheck::CHECK_30_32_CS, w),
+        }
+    }
+}
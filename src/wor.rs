@@ -0,0 +1,370 @@
+//! Wake-on-Radio (WOR) configuration in real time units.
+//!
+//! In WOR mode the radio sleeps, and wakes up every Event 0 period to listen for a packet.
+//! Event 1 follows Event 0 after the crystal oscillator has had time to start, and RX is
+//! left again after the RX timeout unless a packet is being received. The register values
+//! are computed with the formulas of section 19.5 "Wake On Radio (WOR)" and table 31
+//! "RX_TIME, RX Timeout" of the data sheet (TI document SWRS061I).
+
+use hal::spi::SpiDevice;
+
+use crate::lowlevel::registers::*;
+use crate::lowlevel::FXOSC;
+use crate::{Cc1101, Error};
+
+/// Crystal periods per Event 0 step, at `WOR_RES` = 0.
+const EVENT0_CLOCKS: u128 = 750;
+
+/// Event 1 timeout in units of [`EVENT0_CLOCKS`], indexed by `WORCTRL.EVENT1`.
+const EVENT1_STEPS: [u128; 8] = [4, 6, 8, 12, 16, 24, 32, 48];
+
+/// `MCSM2.RX_TIME` value for no RX timeout.
+const RX_TIME_NONE: u8 = 7;
+
+/// RX timeout for the sync word search, `MCSM2.RX_TIME`.
+///
+/// The timeout is a fraction of the Event 0 period: the fraction named here at `WOR_RES` = 0,
+/// and 5, 9 or 13 times that for the higher resolutions, see table 31 "RX_TIME, RX Timeout"
+/// of the data sheet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum RxTime {
+    /// 1/8 of the Event 0 period
+    Div8 = 0,
+    /// 1/16 of the Event 0 period
+    Div16 = 1,
+    /// 1/32 of the Event 0 period
+    Div32 = 2,
+    /// 1/64 of the Event 0 period
+    Div64 = 3,
+    /// 1/128 of the Event 0 period
+    Div128 = 4,
+    /// 1/256 of the Event 0 period
+    Div256 = 5,
+    /// 1/512 of the Event 0 period
+    Div512 = 6,
+    /// Search until a sync word is found.
+    NoTimeout = 7,
+}
+
+impl From<RxTime> for u8 {
+    fn from(value: RxTime) -> Self {
+        value as Self
+    }
+}
+
+/// What to look for when the RX timeout expires, `MCSM2.RX_TIME_QUAL`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum RxTimeQualifier {
+    /// Stay in RX if a sync word has been found.
+    SyncWord = 0,
+    /// Stay in RX if a sync word has been found, or the preamble quality is reached.
+    SyncWordOrPqi = 1,
+}
+
+impl From<RxTimeQualifier> for u8 {
+    fn from(value: RxTimeQualifier) -> Self {
+        value as Self
+    }
+}
+
+/// RC oscillator calibration.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RcCalibration {
+    /// Calibrate the RC oscillator each time the crystal oscillator runs (`WORCTRL.RC_CAL`).
+    Automatic,
+    /// Use the given `RCCTRL1`/`RCCTRL0` values, e.g. from an earlier automatic calibration
+    /// read with [`Cc1101::get_rc_calibration`]. Saves power.
+    Manual {
+        rcctrl1: u8,
+        rcctrl0: u8,
+    },
+}
+
+/// Wake-on-Radio timing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct WakeOnRadioConfig {
+    event0_period_us: u64,
+    rx_timeout_us: Option<u32>,
+    xosc_startup_us: Option<u32>,
+    rssi_termination: bool,
+    qualifier: RxTimeQualifier,
+    rc_calibration: RcCalibration,
+    fxosc: u64,
+}
+
+/// Register values for a [`WakeOnRadioConfig`], and the timing they achieve.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct WakeOnRadioSettings {
+    /// `WOREVT1`/`WOREVT0.EVENT0`.
+    pub event0: u16,
+    /// `WORCTRL.WOR_RES`.
+    pub wor_res: u8,
+    /// `WORCTRL.EVENT1`.
+    pub event1: u8,
+    /// `MCSM2.RX_TIME`.
+    pub rx_time: u8,
+    /// `MCSM2.RX_TIME_RSSI`.
+    pub rx_time_rssi: bool,
+    /// `MCSM2.RX_TIME_QUAL`.
+    pub rx_time_qual: RxTimeQualifier,
+    /// `WORCTRL.RC_CAL` and `RCCTRL1`/`RCCTRL0`.
+    pub rc_calibration: RcCalibration,
+    /// Achieved Event 0 period, in microseconds.
+    pub event0_period_us: u64,
+    /// Achieved Event 1 timeout, in microseconds.
+    pub event1_timeout_us: u64,
+    /// Achieved RX timeout, in microseconds, or `None` if RX lasts until a packet is received.
+    pub rx_timeout_us: Option<u64>,
+}
+
+impl WakeOnRadioConfig {
+    /// Wake up every `event0_period_us`, and stay in RX until a packet is received.
+    pub const fn new(event0_period_us: u64) -> Self {
+        WakeOnRadioConfig {
+            event0_period_us,
+            rx_timeout_us: None,
+            xosc_startup_us: None,
+            rssi_termination: false,
+            qualifier: RxTimeQualifier::SyncWord,
+            rc_calibration: RcCalibration::Automatic,
+            fxosc: FXOSC,
+        }
+    }
+
+    /// Wake up every `event0_period_ms`, and stay in RX until a packet is received.
+    pub const fn from_period_ms(event0_period_ms: u32) -> Self {
+        Self::new(event0_period_ms as u64 * 1_000)
+    }
+
+    /// Leave RX after at least `rx_timeout_us`, unless a packet is being received.
+    ///
+    /// The timeout is a fixed fraction of the Event 0 period, the shortest one that is not
+    /// shorter than `rx_timeout_us` is used, see [`WakeOnRadioSettings::rx_timeout_us`].
+    pub const fn rx_timeout_us(mut self, rx_timeout_us: u32) -> Self {
+        self.rx_timeout_us = Some(rx_timeout_us);
+        self
+    }
+
+    /// Start-up time of the crystal oscillator. Event 1 is set to the shortest timeout that
+    /// is not shorter. Defaults to the longest timeout, as after reset.
+    pub const fn xosc_startup_us(mut self, xosc_startup_us: u32) -> Self {
+        self.xosc_startup_us = Some(xosc_startup_us);
+        self
+    }
+
+    /// Leave RX as soon as there is no carrier sense, without waiting for the RX timeout.
+    pub const fn rssi_termination(mut self, enable: bool) -> Self {
+        self.rssi_termination = enable;
+        self
+    }
+
+    /// What to look for to stay in RX when the RX timeout expires.
+    pub const fn rx_time_qualifier(mut self, qualifier: RxTimeQualifier) -> Self {
+        self.qualifier = qualifier;
+        self
+    }
+
+    /// RC oscillator calibration, automatic by default.
+    pub const fn rc_calibration(mut self, calibration: RcCalibration) -> Self {
+        self.rc_calibration = calibration;
+        self
+    }
+
+    /// Crystal frequency (in Hertz), 26 MHz by default. [`Cc1101::set_wake_on_radio`] uses the
+    /// crystal frequency of the driver.
+    pub const fn crystal(mut self, fxosc: u64) -> Self {
+        self.fxosc = fxosc;
+        self
+    }
+
+    /// Compute the register values, and the timing they achieve.
+    pub fn settings(&self) -> WakeOnRadioSettings {
+        // The finest resolution that can express the period.
+        let fxosc = self.fxosc;
+        let period_clocks = self.event0_period_us as u128 * fxosc as u128 / 1_000_000;
+        let (wor_res, event0) = (0..4u8)
+            .map(|res| (res, (period_clocks + event0_step(res) / 2) / event0_step(res)))
+            .find(|&(_, event0)| event0 <= u16::MAX as u128)
+            .unwrap_or((3, u16::MAX as u128));
+        let event0 = event0.max(1) as u16;
+
+        let rx_time = match self.rx_timeout_us {
+            None => RX_TIME_NONE,
+            Some(timeout_us) => rx_time_for(event0, wor_res, timeout_us, fxosc),
+        };
+
+        let event1 = match self.xosc_startup_us {
+            None => EVENT1_STEPS.len() - 1,
+            Some(startup_us) => EVENT1_STEPS
+                .iter()
+                .position(|&steps| {
+                    clocks_to_ns(steps * EVENT0_CLOCKS, fxosc) >= startup_us as u64 * 1_000
+                })
+                .unwrap_or(EVENT1_STEPS.len() - 1),
+        };
+
+        WakeOnRadioSettings {
+            event0,
+            wor_res,
+            event1: event1 as u8,
+            rx_time,
+            rx_time_rssi: self.rssi_termination,
+            rx_time_qual: self.qualifier,
+            rc_calibration: self.rc_calibration,
+            event0_period_us: clocks_to_ns(event0 as u128 * event0_step(wor_res), fxosc) / 1_000,
+            event1_timeout_us: clocks_to_ns(EVENT1_STEPS[event1] * EVENT0_CLOCKS, fxosc) / 1_000,
+            rx_timeout_us: rx_timeout_ns(event0, wor_res, rx_time, fxosc).map(|ns| ns / 1_000),
+        }
+    }
+}
+
+impl WakeOnRadioSettings {
+    /// Fraction of the Event 0 period spent in RX without receiving a packet, or `None` if RX
+    /// lasts until a packet is received. Crystal start-up and calibration are not included.
+    pub fn duty_cycle(&self) -> Option<f32> {
+        self.rx_timeout_us.map(|rx_us| rx_us as f32 / self.event0_period_us as f32)
+    }
+}
+
+/// Crystal periods per Event 0 step at `wor_res`.
+fn event0_step(wor_res: u8) -> u128 {
+    EVENT0_CLOCKS << (5 * wor_res)
+}
+
+fn clocks_to_ns(clocks: u128, fxosc: u64) -> u64 {
+    (clocks * 1_000_000_000 / fxosc as u128) as u64
+}
+
+/// RX timeout for `MCSM2.RX_TIME`, in nanoseconds, or `None` for no timeout (table 31), with a
+/// `fxosc` Hertz crystal.
+pub(crate) fn rx_timeout_ns(event0: u16, wor_res: u8, rx_time: u8, fxosc: u64) -> Option<u64> {
+    if rx_time >= RX_TIME_NONE {
+        return None;
+    }
+    // One eighth of the Event 0 step at WOR_RES = 0, scaled by 1, 5, 9 or 13.
+    let clocks = event0 as u128 * (1 + 4 * wor_res as u128) * EVENT0_CLOCKS;
+    Some(clocks_to_ns(clocks, fxosc) >> (3 + rx_time))
+}
+
+/// `MCSM2.RX_TIME` for the shortest RX timeout that is not shorter than `timeout_us`, or the
+/// longest one.
+pub(crate) fn rx_time_for(event0: u16, wor_res: u8, timeout_us: u32, fxosc: u64) -> u8 {
+    (0..RX_TIME_NONE)
+        .rev()
+        .find(|&rx_time| {
+            rx_timeout_ns(event0, wor_res, rx_time, fxosc).unwrap_or(0) >= timeout_us as u64 * 1_000
+        })
+        .unwrap_or(0)
+}
+
+impl<SPI, SpiE> Cc1101<SPI>
+where
+    SPI: SpiDevice<u8, Error = SpiE>,
+{
+    /// Configure Wake-on-Radio, returning the register values and achieved timing.
+    ///
+    /// Also powers up the RC oscillator. WOR is started with [`Self::start_wake_on_radio`].
+    pub fn set_wake_on_radio(
+        &mut self,
+        wor: &WakeOnRadioConfig,
+    ) -> Result<WakeOnRadioSettings, Error<SpiE>> {
+        let settings = wor.crystal(self.0.fxosc).settings();
+        self.0.modify_register(config::MCSM2, |r| {
+            r.rx_time_rssi(settings.rx_time_rssi as u8)
+                .rx_time_qual(settings.rx_time_qual.into())
+                .rx_time(settings.rx_time)
+        })?;
+        self.0.write_register(config::WOREVT1, |w| w.event((settings.event0 >> 8) as u8))?;
+        self.0.write_register(config::WOREVT0, |w| w.event((settings.event0 & 0xff) as u8))?;
+        self.0.modify_register(config::WORCTRL, |r| {
+            r.rc_pd(0)
+                .event(settings.event1)
+                .rc_cal((settings.rc_calibration == RcCalibration::Automatic) as u8)
+                .wor_res(settings.wor_res)
+        })?;
+        if let RcCalibration::Manual {
+            rcctrl1,
+            rcctrl0,
+        } = settings.rc_calibration
+        {
+            self.0.write_register(config::RCCTRL1, |w| w.rcctrl1(rcctrl1))?;
+            self.0.write_register(config::RCCTRL0, |w| w.rcctrl0(rcctrl0))?;
+        }
+        Ok(settings)
+    }
+
+    /// Result of the last RC oscillator calibration, for use with [`RcCalibration::Manual`].
+    pub fn get_rc_calibration(&mut self) -> Result<RcCalibration, Error<SpiE>> {
+        let rcctrl1 = self.0.read_register(status::RCCTRL1_STATUS)?.rcctrl1_status();
+        let rcctrl0 = self.0.read_register(status::RCCTRL0_STATUS)?.rcctrl0_status();
+        Ok(RcCalibration::Manual {
+            rcctrl1,
+            rcctrl0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings() {
+        let settings = WakeOnRadioConfig::from_period_ms(1_000).rx_timeout_us(2_000).settings();
+        assert_eq!(settings.wor_res, 0);
+        assert_eq!(settings.event0, 34_667);
+        assert_eq!(settings.event0_period_us, 1_000_009);
+        assert_eq!(settings.rx_time, 5);
+        assert_eq!(settings.rx_timeout_us, Some(3_906));
+        assert_eq!(settings.event1, 7);
+        assert_eq!(settings.event1_timeout_us, 1_384);
+
+        let settings = WakeOnRadioConfig::from_period_ms(10_000).xosc_startup_us(150).settings();
+        assert_eq!(settings.wor_res, 1);
+        assert_eq!(settings.event0, 10_833);
+        assert_eq!(settings.rx_time, RX_TIME_NONE);
+        assert_eq!(settings.rx_timeout_us, None);
+        assert_eq!(settings.event1, 1);
+        assert_eq!(settings.duty_cycle(), None);
+
+        let settings = WakeOnRadioConfig::from_period_ms(1_000).crystal(27_000_000).settings();
+        assert_eq!(settings.event0, 36_000);
+        assert_eq!(settings.event0_period_us, 1_000_000);
+    }
+
+    #[test]
+    fn test_set_wake_on_radio() {
+        let chip = core::cell::RefCell::new(crate::sim::SimChip::new());
+        let mut cc1101 = Cc1101::new(crate::sim::SimSpi::new(&chip)).unwrap();
+
+        let wor = WakeOnRadioConfig::from_period_ms(500).rx_timeout_us(1_000).rc_calibration(
+            RcCalibration::Manual {
+                rcctrl1: 0x41,
+                rcctrl0: 0x20,
+            },
+        );
+        let settings = cc1101.set_wake_on_radio(&wor).unwrap();
+        let expected = crate::RadioConfig::default().wake_on_radio(&settings);
+        assert_eq!(chip.borrow().config(), expected);
+        assert_eq!(expected.read(config::WORCTRL).rc_pd(), 0);
+        assert_eq!(expected.read(config::WORCTRL).rc_cal(), 0);
+        assert_eq!(expected.as_bytes()[0x28], 0x20);
+    }
+
+    #[test]
+    fn test_rx_timeout() {
+        // Table 31, WOR_RES = 0 and 3, EVENT0 = 1 (in microseconds, times 1000).
+        assert_eq!(rx_timeout_ns(1, 0, 0, FXOSC), Some(3_605));
+        assert_eq!(rx_timeout_ns(1, 3, 0, FXOSC), Some(46_875));
+        assert_eq!(rx_timeout_ns(1000, 1, 6, FXOSC), Some(281_700));
+        assert_eq!(rx_timeout_ns(1000, 1, 7, FXOSC), None);
+
+        // Longer than the longest timeout available.
+        let settings = WakeOnRadioConfig::new(30_000).rx_timeout_us(5_000).settings();
+        assert_eq!(settings.rx_time, 0);
+        assert_eq!(settings.rx_timeout_us, Some(3_750));
+        assert_eq!(settings.duty_cycle(), Some(0.125));
+    }
+}